@@ -0,0 +1,238 @@
+//! Renders a scene file to an image.
+//!
+//! Run `rtx --help` for the available options.
+use image::ImageResult;
+use rtx::io::scene;
+use rtx::adaptive::AdaptiveSampling;
+use rtx::control::{Progress, RenderControl};
+use rtx::denoise::Denoiser;
+use rtx::tone_mapping::ToneMapping;
+use rtx::{Aov, Film};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::sync::Mutex;
+use std::time::Duration;
+
+const USAGE: &str = "\
+usage: rtx <scene> [options]
+
+Renders a scene file to an image.
+
+options:
+    -W, --width <pixels>       width of the image (default: 800)
+    -H, --height <pixels>      height of the image (default: 600)
+    -s, --spp <samples>        samples per pixel, overrides `config.rays_per_pixel`.
+                               With adaptive sampling, the most samples a pixel gets
+    -A, --adaptive <threshold> stop sampling pixels whose relative error is below the threshold,
+                               overrides `config.adaptive_sampling.threshold`
+    -b, --bounces <bounces>    maximum number of bounces, overrides `config.max_bounces`
+    -d, --denoise              denoise the image with the default settings, unless `config.denoiser` is set
+    -e, --exposure <stops>     exposure of 8 bit images, overrides `tone_mapping.exposure`
+    -t, --time <seconds>       stop rendering after this time and save the image as far as it got
+    -a, --aovs <names>         comma separated AOVs to render (depth, normal, albedo, emission, object_index, position),
+                               overrides `config.aovs`
+    -o, --output <path>        where to save the image (default: render.png),
+                               `.exr` and `.hdr` files keep the linear colors without tone mapping.
+                               AOVs become layers of `.exr` files, other formats get a `<name>.<aov>.exr` per AOV
+    -m, --heatmap <path>       save a heatmap of the samples per pixel, to see where adaptive sampling spent them
+    -h, --help                 print this message";
+
+/// The width of the progress bar in characters
+const PROGRESS_BAR_WIDTH: usize = 40;
+
+/// The parsed command line
+struct Options {
+    scene: PathBuf,
+    width: usize,
+    height: usize,
+    samples_per_pixel: Option<usize>,
+    max_bounces: Option<usize>,
+    denoise: bool,
+    exposure: Option<f64>,
+    adaptive_threshold: Option<f64>,
+    time_budget: Option<Duration>,
+    aovs: Option<Vec<Aov>>,
+    output: PathBuf,
+    heatmap: Option<PathBuf>,
+}
+impl Options {
+    /// parses the arguments, without the name of the program
+    ///
+    /// returns: Result<Option<Options>, String>
+    ///     [None] if only the help was requested
+    fn parse(mut args: impl Iterator<Item = String>) -> Result<Option<Self>, String> {
+        let mut scene = None;
+        let mut options = Self {
+            scene: PathBuf::new(),
+            width: 800,
+            height: 600,
+            samples_per_pixel: None,
+            max_bounces: None,
+            denoise: false,
+            exposure: None,
+            adaptive_threshold: None,
+            time_budget: None,
+            aovs: None,
+            output: PathBuf::from("render.png"),
+            heatmap: None,
+        };
+        while let Some(arg) = args.next() {
+            let mut value = || args.next().ok_or_else(|| format!("missing value for `{arg}`"));
+            match arg.as_str() {
+                "-h" | "--help" => return Ok(None),
+                "-W" | "--width" => options.width = parse_count(&arg, value()?)?,
+                "-H" | "--height" => options.height = parse_count(&arg, value()?)?,
+                "-s" | "--spp" => options.samples_per_pixel = Some(parse_count(&arg, value()?)?),
+                "-b" | "--bounces" => options.max_bounces = Some(value()?.parse().map_err(|_| format!("invalid value for `{arg}`"))?),
+                "-d" | "--denoise" => options.denoise = true,
+                "-e" | "--exposure" => options.exposure = Some(value()?.parse().map_err(|_| format!("invalid value for `{arg}`"))?),
+                "-A" | "--adaptive" => options.adaptive_threshold = Some(value()?.parse().map_err(|_| format!("invalid value for `{arg}`"))?),
+                "-t" | "--time" => options.time_budget = Some(parse_seconds(&arg, value()?)?),
+                "-a" | "--aovs" => options.aovs = Some(parse_aovs(&value()?)?),
+                "-o" | "--output" => options.output = PathBuf::from(value()?),
+                "-m" | "--heatmap" => options.heatmap = Some(PathBuf::from(value()?)),
+                _ if arg.starts_with('-') => return Err(format!("unknown option `{arg}`")),
+                _ if scene.is_some() => return Err(format!("unexpected argument `{arg}`")),
+                _ => scene = Some(PathBuf::from(arg)),
+            }
+        }
+        options.scene = scene.ok_or("no scene file given")?;
+        Ok(Some(options))
+    }
+}
+/// parses a comma separated list of AOV names
+fn parse_aovs(value: &str) -> Result<Vec<Aov>, String> {
+    value.split(',')
+        .filter(|name| !name.is_empty())
+        .map(|name| Aov::from_name(name.trim()).ok_or_else(|| format!("unknown AOV `{name}`")))
+        .collect()
+}
+/// parses a positive number of seconds
+fn parse_seconds(arg: &str, value: String) -> Result<Duration, String> {
+    value.parse().ok()
+        .filter(|&seconds: &f64| seconds > 0.0)
+        .and_then(|seconds| Duration::try_from_secs_f64(seconds).ok())
+        .ok_or_else(|| format!("`{arg}` expects a positive number of seconds, got `{value}`"))
+}
+/// parses a number that has to be at least 1
+fn parse_count(arg: &str, value: String) -> Result<usize, String> {
+    value.parse().ok()
+        .filter(|&count| count > 0)
+        .ok_or_else(|| format!("`{arg}` expects a positive number, got `{value}`"))
+}
+
+fn main() -> ExitCode {
+    let options = match Options::parse(std::env::args().skip(1)) {
+        Ok(Some(options)) => options,
+        Ok(None) => {
+            println!("{USAGE}");
+            return ExitCode::SUCCESS;
+        }
+        Err(message) => {
+            eprintln!("error: {message}\n\n{USAGE}");
+            return ExitCode::FAILURE;
+        }
+    };
+    let mut scene = match scene::load(&options.scene) {
+        Ok(scene) => scene,
+        Err(error) => {
+            eprintln!("error: couldn't load scene {}: {error}", options.scene.display());
+            return ExitCode::FAILURE;
+        }
+    };
+    if let Some(samples_per_pixel) = options.samples_per_pixel {
+        scene.config.rays_per_pixel = samples_per_pixel;
+    }
+    if let Some(max_bounces) = options.max_bounces {
+        scene.config.max_bounces = max_bounces;
+    }
+    if options.denoise && scene.config.denoiser.is_none() {
+        scene.config.denoiser = Some(Denoiser::default());
+    }
+    if let Some(exposure) = options.exposure {
+        scene.tone_mapping.exposure = exposure;
+    }
+    if let Some(threshold) = options.adaptive_threshold {
+        let adaptive_sampling = scene.config.adaptive_sampling.unwrap_or_default();
+        scene.config.adaptive_sampling = Some(AdaptiveSampling { threshold, ..adaptive_sampling });
+    }
+    if let Some(aovs) = options.aovs {
+        scene.config.aovs = aovs;
+    }
+    if options.heatmap.is_some() && !scene.config.aovs.contains(&Aov::SampleCount) {
+        scene.config.aovs.push(Aov::SampleCount);
+    }
+
+    let control = match options.time_budget {
+        Some(time_budget) => RenderControl::new().with_time_budget(time_budget),
+        None => RenderControl::new(),
+    };
+    // tiles finish out of order on different threads, only the highest count gets drawn
+    let drawn = Mutex::new(0);
+    let (film, progress) = scene.render_with_control(options.width, options.height, &control, |progress| {
+        let mut drawn = drawn.lock().unwrap();
+        if progress.finished_samples > *drawn {
+            *drawn = progress.finished_samples;
+            draw_progress(&progress);
+        }
+    });
+    let elapsed = progress.elapsed;
+    eprintln!();
+    if !progress.is_complete() {
+        eprintln!("stopped after {:.2}s with {:.0}% of the samples", elapsed.as_secs_f64(), progress.fraction() * 100.0);
+    }
+
+    if let Err(error) = save(&film, &scene.tone_mapping, &options.output) {
+        eprintln!("error: couldn't save image to {}: {error}", options.output.display());
+        return ExitCode::FAILURE;
+    }
+    if let Some(path) = &options.heatmap {
+        let heatmap = film.aov(Aov::SampleCount).expect("the sample count is rendered for the heatmap").heatmap();
+        if let Err(error) = heatmap.to_rgb8().save(path) {
+            eprintln!("error: couldn't save heatmap to {}: {error}", path.display());
+            return ExitCode::FAILURE;
+        }
+    }
+    let camera_rays = progress.finished_samples;
+    println!(
+        "rendered {}x{} with {:.1} samples per pixel in {:.2}s ({:.0} camera rays/s), saved to {}",
+        options.width,
+        options.height,
+        camera_rays as f64 / (options.width * options.height) as f64,
+        elapsed.as_secs_f64(),
+        camera_rays as f64 / elapsed.as_secs_f64(),
+        options.output.display(),
+    );
+    ExitCode::SUCCESS
+}
+/// Saves the film in a format based on the file extension, tone mapped 8 bit sRGB unless it's an HDR format.
+/// The AOVs are layers of EXR files, other formats get an EXR file per AOV next to them.
+fn save(film: &Film, tone_mapping: &ToneMapping, path: &Path) -> ImageResult<()> {
+    let extension = path.extension().and_then(|extension| extension.to_str()).map(str::to_ascii_lowercase);
+    match extension.as_deref() {
+        Some("exr") if film.aovs().next().is_some() => return film.save_layered_exr(path),
+        Some("exr") => film.save_exr(path)?,
+        Some("hdr") => film.save_hdr(path)?,
+        _ => tone_mapping.apply_to_film(film).to_rgb8().save(path)?,
+    }
+    for (aov, buffer) in film.aovs() {
+        buffer.save_exr(path.with_extension(format!("{}.exr", aov.name())))?;
+    }
+    Ok(())
+}
+fn draw_progress(progress: &Progress) {
+    let fraction = progress.fraction();
+    let filled = (fraction * PROGRESS_BAR_WIDTH as f64) as usize;
+    let mut stderr = std::io::stderr().lock();
+    let _ = write!(
+        stderr,
+        "\r[{}{}] {:3}% ({}/{} tiles)",
+        "#".repeat(filled),
+        " ".repeat(PROGRESS_BAR_WIDTH - filled),
+        (fraction * 100.0) as usize,
+        progress.finished_tiles,
+        progress.total_tiles,
+    );
+    let _ = stderr.flush();
+}
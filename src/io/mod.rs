@@ -0,0 +1,3 @@
+//! Importers and exporters for files that describe scenes and their contents.
+pub mod obj;
+pub mod scene;
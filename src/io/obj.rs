@@ -0,0 +1,524 @@
+//! Loader for Wavefront OBJ files and their MTL material libraries.
+//!
+//! Every group (`g`/`o`) of the file becomes one [Mesh] per material used in it.
+//! Polygons with more than three vertices are triangulated as fans.
+//!
+//! # Examples
+//!
+//! ```
+//! use rtx::io::obj;
+//! use rtx::Scene;
+//!
+//! let model = obj::parse("
+//! v 0 0 0
+//! v 1 0 0
+//! v 1 1 0
+//! v 0 1 0
+//! g square
+//! f 1 2 3 4
+//! ").unwrap();
+//! assert_eq!(model.groups[0].mesh.triangle_count(), 2);
+//!
+//! let mut scene = Scene::default();
+//! scene.objects.extend(model.into_objects());
+//! ```
+use crate::math::Vector3;
+use crate::object::mesh::Mesh;
+use crate::object::{Material, Object};
+use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
+use std::path::{Path, PathBuf};
+
+/// The material of faces that don't have a material or whose material isn't defined in any library.
+pub const DEFAULT_MATERIAL: Material = Material::colored(Vector3::const_new(0.8, 0.8, 0.8));
+
+/// An error that occurred while loading an OBJ or MTL file
+#[derive(Debug)]
+pub enum ObjError {
+    /// A file couldn't be read
+    Io {
+        /// The file that couldn't be read
+        path: PathBuf,
+        /// The underlying error
+        error: std::io::Error,
+    },
+    /// A file contains something that isn't valid
+    Parse {
+        /// The file that contains the error. [None] if the source wasn't loaded from a file.
+        path: Option<PathBuf>,
+        /// The line of the error, starting at 1
+        line: usize,
+        /// What is wrong
+        message: String,
+    },
+}
+impl Display for ObjError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ObjError::Io { path, error } => write!(f, "couldn't read {}: {error}", path.display()),
+            ObjError::Parse { path: Some(path), line, message } => write!(f, "{}:{line}: {message}", path.display()),
+            ObjError::Parse { path: None, line, message } => write!(f, "line {line}: {message}"),
+        }
+    }
+}
+impl std::error::Error for ObjError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ObjError::Io { error, .. } => Some(error),
+            ObjError::Parse { .. } => None,
+        }
+    }
+}
+
+/// The contents of an OBJ file
+#[derive(Clone, Debug, Default)]
+pub struct ObjModel {
+    /// The parts of the model in the order they appear in the file
+    pub groups: Vec<ObjGroup>,
+}
+/// All faces of one group of an OBJ file that share the same material
+#[derive(Clone, Debug)]
+pub struct ObjGroup {
+    /// The name of the group (from `g` or `o`). Faces before the first group are in the group `default`.
+    pub name: String,
+    /// The name of the material (from `usemtl`), if there is one
+    pub material_name: Option<String>,
+    /// The material the faces are rendered with
+    pub material: Material,
+    /// The faces of the group
+    pub mesh: Mesh,
+}
+impl ObjModel {
+    /// Turns every group into an [Object] that can be added to a [Scene](crate::Scene)
+    pub fn into_objects(self) -> Vec<Object> {
+        self.groups.into_iter()
+            .map(|group| Object::new(group.mesh, group.material))
+            .collect()
+    }
+}
+
+/// Loads an OBJ file including the material libraries it references.
+///
+/// Material libraries are searched relative to the directory of the OBJ file.
+///
+/// # Arguments
+///
+/// * `path`: The path of the OBJ file
+///
+/// returns: Result<ObjModel, ObjError>
+pub fn load(path: impl AsRef<Path>) -> Result<ObjModel, ObjError> {
+    let path = path.as_ref();
+    let source = read(path)?;
+    let directory = path.parent().unwrap_or(Path::new(""));
+    let mut load_library = |name: &str| {
+        let library_path = directory.join(name);
+        parse_mtl(&read(&library_path)?).map_err(|error| error.in_file(&library_path))
+    };
+    Parser::new(&mut load_library).parse(&source)
+        .map_err(|error| error.in_file(path))
+}
+/// Parses the contents of an OBJ file.
+///
+/// `mtllib` statements are ignored, so every face gets the [DEFAULT_MATERIAL].
+/// Use [parse_with_materials] or [load] to get materials.
+pub fn parse(source: &str) -> Result<ObjModel, ObjError> {
+    parse_with_materials(source, &HashMap::new())
+}
+/// Parses the contents of an OBJ file, looking up the names of `usemtl` statements in `materials`.
+///
+/// `mtllib` statements are ignored.
+pub fn parse_with_materials(source: &str, materials: &HashMap<String, Material>) -> Result<ObjModel, ObjError> {
+    let mut no_libraries = |_: &str| Ok(HashMap::new());
+    let mut parser = Parser::new(&mut no_libraries);
+    parser.materials = materials.clone();
+    parser.parse(source)
+}
+/// Parses the contents of an MTL file.
+///
+/// Supported statements:
+/// * `Kd`: the diffuse color, mapped to [Material::base_color]
+/// * `Ke`: the emission, mapped to [Material::emission_color]
+/// * `Ns`: the specular exponent, mapped to [Material::roughness]
+/// * `Pr`: the roughness (PBR extension), mapped directly to [Material::roughness]
+/// * `Pm`: the metalness (PBR extension), mapped directly to [Material::metallic]
+///
+/// returns: Result<HashMap<String, Material>, ObjError>
+///     The materials by name.
+pub fn parse_mtl(source: &str) -> Result<HashMap<String, Material>, ObjError> {
+    let mut materials = HashMap::new();
+    let mut current: Option<(String, Material)> = None;
+    for (index, line) in source.lines().enumerate() {
+        let line_number = index + 1;
+        let mut tokens = Tokens::new(line, line_number);
+        let Some(keyword) = tokens.next() else {
+            continue;
+        };
+        if keyword == "newmtl" {
+            let name = tokens.rest("a material name")?;
+            if let Some((name, material)) = current.replace((name, DEFAULT_MATERIAL)) {
+                materials.insert(name, material);
+            }
+            continue;
+        }
+        let material = match &mut current {
+            Some((_, material)) => material,
+            None if ["Kd", "Ke", "Ns", "Pr", "Pm"].contains(&keyword) => {
+                return Err(parse_error(line_number, format!("`{keyword}` before the first `newmtl`")));
+            }
+            None => continue,
+        };
+        match keyword {
+            "Kd" => material.base_color = tokens.color()?,
+            "Ke" => material.emission_color = tokens.color()?,
+            "Ns" => material.roughness = roughness_from_exponent(tokens.number()?),
+            "Pr" => material.roughness = tokens.number()?.clamp(0.0, 1.0),
+            "Pm" => material.metallic = tokens.number()?.clamp(0.0, 1.0),
+            _ => {}
+        }
+    }
+    if let Some((name, material)) = current {
+        materials.insert(name, material);
+    }
+    Ok(materials)
+}
+/// Converts a Phong specular exponent into a roughness in `[0, 1]`
+fn roughness_from_exponent(exponent: f64) -> f64 {
+    (2.0 / (exponent.max(0.0) + 2.0)).sqrt().clamp(0.0, 1.0)
+}
+fn read(path: &Path) -> Result<String, ObjError> {
+    std::fs::read_to_string(path).map_err(|error| ObjError::Io { path: path.to_path_buf(), error })
+}
+fn parse_error(line: usize, message: impl Into<String>) -> ObjError {
+    ObjError::Parse { path: None, line, message: message.into() }
+}
+impl ObjError {
+    /// attaches the file to parse errors that don't have one yet
+    fn in_file(self, file: &Path) -> Self {
+        match self {
+            ObjError::Parse { path: None, line, message } => ObjError::Parse { path: Some(file.to_path_buf()), line, message },
+            error => error,
+        }
+    }
+}
+
+/// Loads the material library with the given name
+type LibraryLoader<'a> = &'a mut dyn FnMut(&str) -> Result<HashMap<String, Material>, ObjError>;
+/// A corner of a face: indices of the position, texture coordinate and normal
+type Corner = (usize, Option<usize>, Option<usize>);
+/// The faces of one group with one material, before they are turned into a [Mesh]
+struct PartialGroup {
+    name: String,
+    material_name: Option<String>,
+    triangles: Vec<[Corner; 3]>,
+}
+struct Parser<'a> {
+    load_library: LibraryLoader<'a>,
+    materials: HashMap<String, Material>,
+    positions: Vec<Vector3>,
+    uvs: Vec<(f64, f64)>,
+    normals: Vec<Vector3>,
+    groups: Vec<PartialGroup>,
+    /// index into `groups` of the group faces are currently added to
+    current: Option<usize>,
+    group_name: String,
+    material_name: Option<String>,
+}
+impl<'a> Parser<'a> {
+    fn new(load_library: LibraryLoader<'a>) -> Self {
+        Self {
+            load_library,
+            materials: HashMap::new(),
+            positions: Vec::new(),
+            uvs: Vec::new(),
+            normals: Vec::new(),
+            groups: Vec::new(),
+            current: None,
+            group_name: "default".to_string(),
+            material_name: None,
+        }
+    }
+    fn parse(mut self, source: &str) -> Result<ObjModel, ObjError> {
+        for (index, line) in source.lines().enumerate() {
+            self.parse_line(Tokens::new(line, index + 1))?;
+        }
+        Ok(self.finish())
+    }
+    fn parse_line(&mut self, mut tokens: Tokens) -> Result<(), ObjError> {
+        let Some(keyword) = tokens.next() else {
+            return Ok(());
+        };
+        match keyword {
+            "v" => {
+                let position = tokens.vector()?;
+                self.positions.push(position);
+            }
+            "vt" => {
+                let u = tokens.number()?;
+                let v = tokens.optional_number()?.unwrap_or(0.0);
+                self.uvs.push((u, v));
+            }
+            "vn" => {
+                let normal = tokens.vector()?;
+                self.normals.push(normal);
+            }
+            "f" => self.parse_face(tokens)?,
+            "g" | "o" => {
+                self.group_name = tokens.rest("a group name").unwrap_or_else(|_| "default".to_string());
+                self.current = None;
+            }
+            "usemtl" => {
+                self.material_name = Some(tokens.rest("a material name")?);
+                self.current = None;
+            }
+            "mtllib" => {
+                for library in tokens.by_ref() {
+                    let materials = (self.load_library)(library)?;
+                    self.materials.extend(materials);
+                }
+            }
+            // smoothing groups, lines, points, etc. aren't supported and are skipped
+            _ => {}
+        }
+        Ok(())
+    }
+    fn parse_face(&mut self, mut tokens: Tokens) -> Result<(), ObjError> {
+        let line = tokens.line;
+        let corners = tokens.by_ref()
+            .map(|corner| self.parse_corner(corner, line))
+            .collect::<Result<Vec<_>, _>>()?;
+        if corners.len() < 3 {
+            return Err(parse_error(line, format!("a face needs at least 3 vertices, found {}", corners.len())));
+        }
+        let current = match self.current {
+            Some(current) => current,
+            None => {
+                self.groups.push(PartialGroup {
+                    name: self.group_name.clone(),
+                    material_name: self.material_name.clone(),
+                    triangles: Vec::new(),
+                });
+                self.current = Some(self.groups.len() - 1);
+                self.groups.len() - 1
+            }
+        };
+        let group = &mut self.groups[current];
+        group.triangles.extend((1..corners.len() - 1).map(|i| [corners[0], corners[i], corners[i + 1]]));
+        Ok(())
+    }
+    /// parses one corner of a face (`v`, `v/vt`, `v//vn` or `v/vt/vn`)
+    fn parse_corner(&self, corner: &str, line: usize) -> Result<Corner, ObjError> {
+        let mut parts = corner.split('/');
+        let position = parts.next().filter(|part| !part.is_empty())
+            .ok_or_else(|| parse_error(line, format!("face vertex `{corner}` has no position")))?;
+        let position = resolve_index(position, self.positions.len(), "position", line)?;
+        let uv = match parts.next() {
+            Some(part) if !part.is_empty() => Some(resolve_index(part, self.uvs.len(), "texture coordinate", line)?),
+            _ => None,
+        };
+        let normal = match parts.next() {
+            Some(part) if !part.is_empty() => Some(resolve_index(part, self.normals.len(), "normal", line)?),
+            _ => None,
+        };
+        if parts.next().is_some() {
+            return Err(parse_error(line, format!("face vertex `{corner}` has too many parts")));
+        }
+        Ok((position, uv, normal))
+    }
+    fn finish(self) -> ObjModel {
+        let positions = &self.positions;
+        let groups = self.groups.into_iter()
+            .map(|mut group| {
+                // faces without area can't be hit and have no normal
+                group.triangles.retain(|triangle| face_normal(triangle, positions).is_some());
+                group
+            })
+            .filter(|group| !group.triangles.is_empty())
+            .map(|group| {
+                let material = group.material_name.as_ref()
+                    .and_then(|name| self.materials.get(name))
+                    .cloned()
+                    .unwrap_or(DEFAULT_MATERIAL);
+                let mesh = build_mesh(&group.triangles, &self.positions, &self.uvs, &self.normals);
+                ObjGroup { name: group.name, material_name: group.material_name, material, mesh }
+            })
+            .collect();
+        ObjModel { groups }
+    }
+}
+/// Turns the triangles of a group into a mesh. Every unique combination of position, texture coordinate and normal becomes a vertex.
+fn build_mesh(triangles: &[[Corner; 3]], positions: &[Vector3], uvs: &[(f64, f64)], normals: &[Vector3]) -> Mesh {
+    let has_uvs = triangles.iter().flatten().any(|corner| corner.1.is_some());
+    let has_normals = triangles.iter().flatten().any(|corner| corner.2.is_some());
+    let mut vertex_indices: HashMap<(usize, Option<usize>, Option<usize>, usize), usize> = HashMap::new();
+    let mut mesh_positions = Vec::new();
+    let mut mesh_uvs = Vec::new();
+    let mut mesh_normals = Vec::new();
+    let mut indices = Vec::with_capacity(triangles.len());
+    for (triangle_index, triangle) in triangles.iter().enumerate() {
+        let face_normal = face_normal(triangle, positions).expect("faces without area are skipped");
+        indices.push(triangle.map(|(position, uv, normal)| {
+            // corners without a normal get the flat normal of their face, so they can't be shared with other faces
+            let key = (position, uv, normal, if normal.is_none() && has_normals { triangle_index } else { 0 });
+            *vertex_indices.entry(key).or_insert_with(|| {
+                mesh_positions.push(positions[position]);
+                mesh_uvs.push(uv.map_or((0.0, 0.0), |uv| uvs[uv]));
+                mesh_normals.push(normal.map_or(face_normal, |normal| normals[normal]));
+                mesh_positions.len() - 1
+            })
+        }));
+    }
+    let mut mesh = Mesh::new(mesh_positions, indices);
+    if has_uvs {
+        mesh = mesh.with_uvs(mesh_uvs);
+    }
+    if has_normals {
+        mesh = mesh.with_normals(mesh_normals);
+    }
+    mesh
+}
+/// returns the normal of a face, or [None] if it has no area
+fn face_normal(triangle: &[Corner; 3], positions: &[Vector3]) -> Option<Vector3> {
+    let [a, b, c] = triangle.map(|corner| positions[corner.0]);
+    let normal = (b - a).cross(c - a);
+    (normal.len() > 0.0).then(|| normal.norm())
+}
+/// Turns a 1-based (or negative, relative to the end) OBJ index into a 0-based index.
+fn resolve_index(index: &str, count: usize, what: &str, line: usize) -> Result<usize, ObjError> {
+    let value = index.parse::<isize>()
+        .map_err(|_| parse_error(line, format!("invalid {what} index `{index}`")))?;
+    let resolved = match value {
+        0 => None,
+        1.. => Some(value as usize - 1),
+        _ => count.checked_sub(value.unsigned_abs()),
+    };
+    resolved.filter(|&resolved| resolved < count)
+        .ok_or_else(|| parse_error(line, format!("{what} index {value} is out of range (there are {count})")))
+}
+
+/// The whitespace separated tokens of a line without comments
+struct Tokens<'a> {
+    line: usize,
+    remaining: &'a str,
+}
+impl<'a> Tokens<'a> {
+    fn new(line: &'a str, line_number: usize) -> Self {
+        let without_comment = line.split('#').next().unwrap_or("");
+        Self { line: line_number, remaining: without_comment.trim() }
+    }
+    /// returns the rest of the line as a single value (names can contain spaces)
+    fn rest(&mut self, what: &str) -> Result<String, ObjError> {
+        let rest = std::mem::take(&mut self.remaining).trim();
+        if rest.is_empty() {
+            return Err(parse_error(self.line, format!("expected {what}")));
+        }
+        Ok(rest.to_string())
+    }
+    fn optional_number(&mut self) -> Result<Option<f64>, ObjError> {
+        self.next()
+            .map(|token| token.parse::<f64>()
+                .map_err(|_| parse_error(self.line, format!("`{token}` is not a number"))))
+            .transpose()
+    }
+    fn number(&mut self) -> Result<f64, ObjError> {
+        self.optional_number()?
+            .ok_or_else(|| parse_error(self.line, "expected a number"))
+    }
+    fn vector(&mut self) -> Result<Vector3, ObjError> {
+        Ok(Vector3::new(self.number()?, self.number()?, self.number()?))
+    }
+    /// parses an RGB color. A single value is used for all three channels.
+    fn color(&mut self) -> Result<Vector3, ObjError> {
+        let r = self.number()?;
+        match self.optional_number()? {
+            Some(g) => Ok(Vector3::new(r, g, self.number()?)),
+            None => Ok(Vector3::new(r, r, r)),
+        }
+    }
+}
+impl<'a> Iterator for Tokens<'a> {
+    type Item = &'a str;
+    fn next(&mut self) -> Option<Self::Item> {
+        let trimmed = self.remaining.trim_start();
+        if trimmed.is_empty() {
+            return None;
+        }
+        let end = trimmed.find(char::is_whitespace).unwrap_or(trimmed.len());
+        let (token, rest) = trimmed.split_at(end);
+        self.remaining = rest;
+        Some(token)
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn triangulates_polygons_and_resolves_relative_indices() {
+        let model = parse("
+v 0 0 0
+v 1 0 0
+v 1 1 0
+v 0 1 0
+v 0 0 1
+vn 0 0 1
+g quad
+f 1//1 2//1 3//1 4//1
+g pentagon
+f -5 -4 -3 -2 -1
+").unwrap();
+        assert_eq!(model.groups.len(), 2);
+        assert_eq!(model.groups[0].name, "quad");
+        assert_eq!(model.groups[0].mesh.triangle_count(), 2);
+        assert_eq!(model.groups[0].mesh.vertices().len(), 4);
+        assert!(model.groups[0].mesh.normals().is_some());
+        assert_eq!(model.groups[1].mesh.triangle_count(), 3);
+        assert!(model.groups[1].mesh.normals().is_none());
+    }
+    #[test]
+    fn skips_faces_without_area() {
+        let model = parse("
+v 0 0 0
+v 1 0 0
+v 1 1 0
+v 2 0 0
+vn 0 0 1
+f 1//1 2//1 3//1
+f 1 2 4
+g line
+f 1 2 4
+").unwrap();
+        assert_eq!(model.groups.len(), 1);
+        assert_eq!(model.groups[0].mesh.triangle_count(), 1);
+        assert!(model.groups[0].mesh.normals().unwrap().iter().all(|normal| normal.len().is_finite()));
+    }
+    #[test]
+    fn splits_groups_by_material() {
+        let materials = parse_mtl("
+newmtl red
+Kd 1 0 0
+Ns 1000
+newmtl lamp
+Ke 5
+").unwrap();
+        let model = parse_with_materials("
+v 0 0 0
+v 1 0 0
+v 1 1 0
+usemtl red
+f 1 2 3
+usemtl lamp
+f 1 3 2
+", &materials).unwrap();
+        assert_eq!(model.groups.len(), 2);
+        assert_eq!(model.groups[0].material.base_color, Vector3::x());
+        assert!(model.groups[0].material.roughness < 0.1);
+        assert_eq!(model.groups[1].material.emission_color, Vector3::new(5, 5, 5));
+    }
+    #[test]
+    fn errors_report_line_numbers() {
+        let error = parse("v 0 0 0\nv 1 0 0\n\nf 1 2 7\n").unwrap_err();
+        assert!(matches!(error, ObjError::Parse { line: 4, .. }), "{error}");
+        let error = parse("v 0 zero 0\n").unwrap_err();
+        assert!(matches!(error, ObjError::Parse { line: 1, .. }), "{error}");
+    }
+}
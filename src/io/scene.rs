@@ -0,0 +1,957 @@
+//! A text format that describes a whole [Scene]: its camera, its configuration, all objects and lights.
+//!
+//! Files consist of `key = value` entries. Values are numbers, strings, `true`/`false`,
+//! lists in square brackets (vectors are lists of three numbers) and blocks in curly braces.
+//! Blocks can be tagged with a type name (`sphere { ... }`) and `key { ... }` is short for `key = { ... }`.
+//! Everything after a `#` is a comment.
+//!
+//! ```text
+//! camera {
+//!     position = [-5.0, 0.0, 1.0]
+//!     direction = [1.0, 0.0, 0.0]
+//!     up = [0.0, 0.0, 1.0]
+//!     fov = 1.5707963267948966
+//!     fov_axis = "horizontal"
+//! }
+//! config {
+//!     rays_per_pixel = 64
+//!     max_bounces = 8
+//!     aovs = ["depth", "normal"]
+//!     sampler = "sobol"
+//!     denoiser {
+//!         iterations = 5
+//!     }
+//!     adaptive_sampling {
+//!         threshold = 0.05
+//!         min_samples = 16
+//!     }
+//! }
+//! object {
+//!     shape = sphere {
+//!         position = [0.0, 0.0, 1.0]
+//!         radius = 1.0
+//!     }
+//!     material {
+//!         base_color = [1.0, 0.2, 0.2]
+//!         emission_color = [0.0, 0.0, 0.0]
+//!         roughness = 0.3
+//!         metallic = 0.0
+//!     }
+//! }
+//! object {
+//!     shape = sphere {
+//!         position = [-2.0, 1.0, 0.5]
+//!         radius = 0.5
+//!     }
+//!     material {
+//!         base_color = [1.0, 1.0, 1.0]
+//!         roughness = 0.0
+//!         dielectric {
+//!             ior = 1.5
+//!             absorption = [0.5, 0.05, 0.5]
+//!         }
+//!     }
+//! }
+//! environment = gradient {
+//!     bottom = [0.1, 0.1, 0.1]
+//!     top = [0.4, 0.6, 1.0]
+//! }
+//! light = spot {
+//!     position = [0.0, 0.0, 5.0]
+//!     direction = [0.0, 0.0, -1.0]
+//!     inner_angle = 0.3
+//!     outer_angle = 0.5
+//!     color = [1.0, 0.9, 0.8]
+//!     intensity = 40.0
+//! }
+//! tone_mapping = aces {
+//!     exposure = 0.5
+//! }
+//! ```
+//!
+//! The environment is either `color { color }`, `gradient { bottom, top }`, `map { path, intensity }`
+//! or `sky { sun_direction, turbidity, ground_albedo, intensity }`,
+//! where `path` points to an image that is loaded with [EnvironmentMap::load] (so maps need the `images` feature).
+//! The tone mapping is `clamp`, `reinhard`, `extended_reinhard { white }`, `aces` or `agx`, each with an `exposure` in stops.
+//! Objects and the camera can have a `motion` block with a `keyframe { time, translation, rotation }` per [keyframe](Motion).
+//! The keyframes of objects are absolute, they place the shape in the world (see [Object::motion](crate::object::Object::motion)).
+//! The ones of the camera are relative to it: translations move it away from its `position` and rotations turn it around that point,
+//! see [Camera::motion].
+//! Keys of the `config` block may be left out, they default to the values of [Config::default].
+//! Numbers are written with as many digits as needed, so writing a scene and loading it again gives the same scene.
+//!
+//! Shapes are read and written by a [SceneFormat], which knows the built-in shapes.
+//! Other shapes can be stored after implementing [SceneShape] and registering them with [SceneFormat::register].
+//!
+//! # Examples
+//!
+//! ```
+//! use rtx::io::scene;
+//! use rtx::math::Vector3;
+//! use rtx::object::{Material, Object};
+//! use rtx::object::sphere::Sphere;
+//! use rtx::Scene;
+//!
+//! let mut scene = Scene::default();
+//! scene.add_object(Object::new(Sphere::new(Vector3::new(5, 0, 0), 1.0), Material::mirror()));
+//!
+//! let text = scene::write(&scene).unwrap();
+//! let loaded = scene::parse(&text).unwrap();
+//! assert_eq!(loaded.objects.len(), 1);
+//! ```
+mod fields;
+mod syntax;
+
+pub use fields::Fields;
+pub use syntax::{Block, Entry, Value};
+
+use crate::math::Vector3;
+use crate::object::plane::Plane;
+use crate::object::sphere::Sphere;
+use crate::object::triangle::Triangle;
+use crate::environment::sky::Sky;
+use crate::environment::{Environment, EnvironmentMap};
+use crate::light::Light;
+use crate::motion::{Motion, Shutter, Transform};
+use crate::adaptive::AdaptiveSampling;
+use crate::sampler::SamplerKind;
+use crate::camera::FovAxis;
+use crate::camera::lens::{Aperture, ApertureImage, ThinLens};
+use crate::camera::model::{CameraModel, Cubemap, Equirectangular, Fisheye, FisheyeMapping, Orthographic, Perspective};
+use crate::denoise::Denoiser;
+use crate::tone_mapping::{ToneMapOperator, ToneMapping};
+use crate::object::{CustomShape, Dielectric, Material, Object};
+use crate::{Aov, Camera, Config, Scene};
+use std::any::Any;
+use std::fmt::{Display, Formatter};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+/// An error that occurred while reading or writing a scene file
+#[derive(Debug)]
+pub enum SceneFileError {
+    /// A file couldn't be read or written
+    Io {
+        /// The file that couldn't be accessed
+        path: PathBuf,
+        /// The underlying error
+        error: std::io::Error,
+    },
+    /// The file isn't made of valid entries
+    Syntax {
+        /// The line of the error, starting at 1
+        line: usize,
+        /// The column of the error, starting at 1
+        column: usize,
+        message: String,
+    },
+    /// An entry is missing, unknown or has the wrong type
+    Schema {
+        /// The path of the offending key, e.g. `object[2].shape.radius`
+        key: String,
+        /// The line of the entry, starting at 1
+        line: usize,
+        message: String,
+    },
+    /// An object of the scene has a shape that isn't registered with the [SceneFormat], so it can't be written
+    UnregisteredShape {
+        /// The index of the object in [Scene::objects]
+        object: usize,
+    },
+    /// The environment map of the scene wasn't loaded from a file, so the scene file can't reference it
+    EnvironmentWithoutFile,
+    /// The aperture image of the camera wasn't loaded from a file, so the scene file can't reference it
+    ApertureWithoutFile,
+    /// The camera has a custom [Camera::model], which can't be written
+    CustomCameraModel,
+    /// An object of the scene has a custom [Material::bsdf], which can't be written
+    CustomBsdf {
+        /// The index of the object in [Scene::objects]
+        object: usize,
+    },
+}
+impl Display for SceneFileError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SceneFileError::Io { path, error } => write!(f, "couldn't access {}: {error}", path.display()),
+            SceneFileError::Syntax { line, column, message } => write!(f, "line {line}, column {column}: {message}"),
+            SceneFileError::Schema { key, line, message } => write!(f, "line {line}: `{key}`: {message}"),
+            SceneFileError::UnregisteredShape { object } => write!(f, "the shape of object {object} isn't registered"),
+            SceneFileError::EnvironmentWithoutFile => write!(f, "the environment map wasn't loaded from a file"),
+            SceneFileError::ApertureWithoutFile => write!(f, "the aperture image of the camera wasn't loaded from a file"),
+            SceneFileError::CustomCameraModel => write!(f, "the camera has a custom model"),
+            SceneFileError::CustomBsdf { object } => write!(f, "the material of object {object} has a custom bsdf"),
+        }
+    }
+}
+impl std::error::Error for SceneFileError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            SceneFileError::Io { error, .. } => Some(error),
+            _ => None,
+        }
+    }
+}
+
+/// A shape that can be stored in scene files.
+///
+/// # Examples
+///
+/// ```
+/// use rtx::io::scene::{Block, Fields, SceneFileError, SceneFormat, SceneShape};
+/// use rtx::math::Vector3;
+/// use rtx::object::CustomShape;
+///
+/// struct Floor {
+///     height: f64,
+/// }
+/// impl CustomShape for Floor {
+///     fn distance(&self, ray_position: Vector3, ray_direction: Vector3) -> Option<f64> {
+///         let distance = (self.height - ray_position.z) / ray_direction.z;
+///         (distance > 0.0).then_some(distance)
+///     }
+///     fn normal(&self, _world_position: Vector3) -> Vector3 {
+///         Vector3::z()
+///     }
+/// }
+/// impl SceneShape for Floor {
+///     const NAME: &'static str = "floor";
+///     fn read(fields: &mut Fields) -> Result<Self, SceneFileError> {
+///         Ok(Self { height: fields.number("height")? })
+///     }
+///     fn write(&self) -> Block {
+///         Block::new().number("height", self.height)
+///     }
+/// }
+///
+/// let mut format = SceneFormat::new();
+/// format.register::<Floor>();
+/// ```
+pub trait SceneShape: CustomShape + Send + Sync + Sized {
+    /// The type name of the shape in files, e.g. `sphere`
+    const NAME: &'static str;
+    /// Reads the shape from the entries of its block.
+    ///
+    /// Entries that are not read are reported as unknown keys.
+    fn read(fields: &mut Fields) -> Result<Self, SceneFileError>;
+    /// Describes the shape as the entries of a block, in a way that [SceneShape::read] gives back the same shape.
+    fn write(&self) -> Block;
+}
+
+/// A registered shape type
+#[derive(Clone, Copy)]
+struct ShapeType {
+    name: &'static str,
+    read: fn(&mut Fields, Material) -> Result<Object, SceneFileError>,
+    write: fn(&Object) -> Option<Block>,
+}
+fn read_shape<T: SceneShape>(fields: &mut Fields, material: Material) -> Result<Object, SceneFileError> {
+    Ok(Object::new(T::read(fields)?, material))
+}
+fn write_shape<T: SceneShape>(object: &Object) -> Option<Block> {
+    object.with_shape(T::write)
+}
+
+/// Reads and writes scene files with a set of known shapes.
+///
+/// [SceneFormat::new] knows [Sphere], [Plane] and [Triangle].
+#[derive(Clone)]
+pub struct SceneFormat {
+    shapes: Vec<ShapeType>,
+}
+impl Default for SceneFormat {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+impl SceneFormat {
+    /// creates a format that knows all built-in shapes
+    pub fn new() -> Self {
+        let mut format = Self { shapes: Vec::new() };
+        format.register::<Sphere>();
+        format.register::<Plane>();
+        format.register::<Triangle>();
+        format
+    }
+    /// Makes a shape type known, so it can be read and written.
+    /// A previously registered type with the same [SceneShape::NAME] gets replaced.
+    pub fn register<T: SceneShape>(&mut self) -> &mut Self {
+        self.shapes.retain(|shape| shape.name != T::NAME);
+        self.shapes.push(ShapeType { name: T::NAME, read: read_shape::<T>, write: write_shape::<T> });
+        self
+    }
+    /// Loads a scene from a file.
+    ///
+    /// # Arguments
+    ///
+    /// * `path`: The path of the file.
+    ///
+    /// returns: Result<Scene, SceneFileError>
+    pub fn load(&self, path: impl AsRef<Path>) -> Result<Scene, SceneFileError> {
+        let path = path.as_ref();
+        let source = std::fs::read_to_string(path)
+            .map_err(|error| SceneFileError::Io { path: path.to_path_buf(), error })?;
+        self.parse(&source)
+    }
+    /// Parses the contents of a scene file.
+    ///
+    /// returns: Result<Scene, SceneFileError>
+    pub fn parse(&self, source: &str) -> Result<Scene, SceneFileError> {
+        let root = syntax::parse(source)?;
+        let mut fields = Fields::new(&root, String::new(), 1);
+        let camera = read_camera(&mut fields.block("camera")?)?;
+        let config = match fields.optional_block("config")? {
+            Some(mut config) => read_config(&mut config)?,
+            None => Config::default(),
+        };
+        let mut scene = Scene::new(config, camera);
+        for mut object in fields.blocks("object")? {
+            scene.add_object(self.read_object(&mut object)?);
+        }
+        if let Some(mut environment) = fields.optional_block("environment")? {
+            scene.environment = read_environment(&mut environment)?;
+        }
+        for mut light in fields.blocks("light")? {
+            scene.add_light(read_light(&mut light)?);
+        }
+        if let Some(mut tone_mapping) = fields.optional_block("tone_mapping")? {
+            scene.tone_mapping = read_tone_mapping(&mut tone_mapping)?;
+        }
+        fields.finish()?;
+        Ok(scene)
+    }
+    /// Saves a scene to a file.
+    ///
+    /// # Arguments
+    ///
+    /// * `scene`: The scene to save.
+    /// * `path`: The path of the file, which gets overwritten.
+    ///
+    /// returns: Result<(), SceneFileError>
+    pub fn save(&self, scene: &Scene, path: impl AsRef<Path>) -> Result<(), SceneFileError> {
+        let path = path.as_ref();
+        std::fs::write(path, self.write(scene)?)
+            .map_err(|error| SceneFileError::Io { path: path.to_path_buf(), error })
+    }
+    /// Writes a scene as the contents of a scene file.
+    ///
+    /// returns: Result<String, SceneFileError>
+    ///     An error if the shape of an object isn't registered, its material has a custom bsdf,
+    ///     or an image of the environment or aperture wasn't loaded from a file.
+    pub fn write(&self, scene: &Scene) -> Result<String, SceneFileError> {
+        let mut root = Block::new()
+            .block("camera", write_camera(&scene.camera)?)
+            .block("config", write_config(&scene.config));
+        for (i, object) in scene.objects.iter().enumerate() {
+            if object.material.bsdf.is_some() {
+                return Err(SceneFileError::CustomBsdf { object: i });
+            }
+            root = root.block("object", self.write_object(object).ok_or(SceneFileError::UnregisteredShape { object: i })?);
+        }
+        root = root.block("environment", write_environment(&scene.environment)?);
+        for light in &scene.lights {
+            root = root.block("light", write_light(light));
+        }
+        root = root.block("tone_mapping", write_tone_mapping(&scene.tone_mapping));
+        Ok(syntax::write(&root))
+    }
+}
+impl SceneFormat {
+    fn read_object(&self, fields: &mut Fields) -> Result<Object, SceneFileError> {
+        let material = read_material(&mut fields.block("material")?)?;
+        let mut shape = fields.block("shape")?;
+        let Some(tag) = shape.tag() else {
+            return Err(shape.error("expected a shape type in front of the block, e.g. `sphere { ... }`"));
+        };
+        let Some(shape_type) = self.shapes.iter().find(|shape| shape.name == tag) else {
+            return Err(shape.error(format!("unknown shape type `{tag}`")));
+        };
+        let mut object = (shape_type.read)(&mut shape, material)?;
+        shape.finish()?;
+        if let Some(mut motion) = fields.optional_block("motion")? {
+            object.motion = Some(read_motion(&mut motion)?);
+        }
+        fields.finish()?;
+        Ok(object)
+    }
+    fn write_object(&self, object: &Object) -> Option<Block> {
+        let (name, mut shape) = self.shapes.iter()
+            .find_map(|shape| (shape.write)(object).map(|block| (shape.name, block)))?;
+        shape.tag = Some(name.to_string());
+        let block = Block::new()
+            .block("shape", shape)
+            .block("material", write_material(&object.material));
+        Some(match &object.motion {
+            Some(motion) => block.block("motion", write_motion(motion)),
+            None => block,
+        })
+    }
+}
+
+/// Loads a scene file that only uses built-in shapes. See [SceneFormat::load].
+pub fn load(path: impl AsRef<Path>) -> Result<Scene, SceneFileError> {
+    SceneFormat::new().load(path)
+}
+/// Parses a scene file that only uses built-in shapes. See [SceneFormat::parse].
+pub fn parse(source: &str) -> Result<Scene, SceneFileError> {
+    SceneFormat::new().parse(source)
+}
+/// Saves a scene that only uses built-in shapes. See [SceneFormat::save].
+pub fn save(scene: &Scene, path: impl AsRef<Path>) -> Result<(), SceneFileError> {
+    SceneFormat::new().save(scene, path)
+}
+/// Writes a scene that only uses built-in shapes. See [SceneFormat::write].
+pub fn write(scene: &Scene) -> Result<String, SceneFileError> {
+    SceneFormat::new().write(scene)
+}
+
+fn read_camera(fields: &mut Fields) -> Result<Camera, SceneFileError> {
+    let mut camera = Camera::new(fields.vector("position")?, fields.vector("direction")?, fields.number("fov")?);
+    if let Some(up) = fields.optional_vector("up")? {
+        camera.set_up(up);
+    }
+    camera.set_roll(fields.optional_number("roll")?.unwrap_or(0.0));
+    if let Some(name) = fields.optional_string("fov_axis")? {
+        camera.fov_axis = FovAxis::from_name(&name)
+            .ok_or_else(|| fields.key_error("fov_axis", format!("unknown fov axis `{name}`")))?;
+    }
+    if let Some(mut lens) = fields.optional_block("lens")? {
+        camera.lens = Some(read_lens(&mut lens)?);
+    }
+    if let Some(mut model) = fields.optional_block("model")? {
+        camera.model = read_camera_model(&mut model)?;
+    }
+    if let Some(mut motion) = fields.optional_block("motion")? {
+        camera.motion = Some(read_motion(&mut motion)?);
+    }
+    fields.finish()?;
+    Ok(camera)
+}
+fn read_camera_model(fields: &mut Fields) -> Result<Arc<dyn CameraModel>, SceneFileError> {
+    let model: Arc<dyn CameraModel> = match fields.tag() {
+        Some("perspective") => Arc::new(Perspective),
+        Some("orthographic") => Arc::new(Orthographic { width: fields.number("width")? }),
+        Some("fisheye") => {
+            let default = Fisheye::default();
+            let mapping = match fields.optional_string("mapping")? {
+                Some(name) => FisheyeMapping::from_name(&name)
+                    .ok_or_else(|| fields.key_error("mapping", format!("unknown fisheye mapping `{name}`")))?,
+                None => default.mapping,
+            };
+            Arc::new(Fisheye { fov: fields.optional_number("fov")?.unwrap_or(default.fov), mapping })
+        }
+        Some("equirectangular") => Arc::new(Equirectangular),
+        Some("cubemap") => Arc::new(Cubemap),
+        Some(tag) => return Err(fields.error(format!("unknown camera model `{tag}`"))),
+        None => return Err(fields.error("expected a camera model in front of the block, e.g. `fisheye { }`")),
+    };
+    fields.finish()?;
+    Ok(model)
+}
+/// the block of a built-in model, [None] for the default perspective one
+fn write_camera_model(model: &dyn CameraModel) -> Result<Option<Block>, SceneFileError> {
+    let model: &dyn Any = model;
+    Ok(if model.is::<Perspective>() {
+        None
+    } else if let Some(orthographic) = model.downcast_ref::<Orthographic>() {
+        Some(Block::tagged("orthographic").number("width", orthographic.width))
+    } else if let Some(fisheye) = model.downcast_ref::<Fisheye>() {
+        Some(Block::tagged("fisheye").number("fov", fisheye.fov).string("mapping", fisheye.mapping.name()))
+    } else if model.is::<Equirectangular>() {
+        Some(Block::tagged("equirectangular"))
+    } else if model.is::<Cubemap>() {
+        Some(Block::tagged("cubemap"))
+    } else {
+        return Err(SceneFileError::CustomCameraModel);
+    })
+}
+fn read_lens(fields: &mut Fields) -> Result<ThinLens, SceneFileError> {
+    let default = ThinLens::default();
+    let lens = ThinLens {
+        focal_length: fields.optional_number("focal_length")?.unwrap_or(default.focal_length),
+        f_number: match fields.optional_number("f_number")? {
+            Some(f_number) if f_number <= 0.0 => return Err(fields.key_error("f_number", "must be greater than 0")),
+            f_number => f_number.unwrap_or(default.f_number),
+        },
+        focus_distance: fields.optional_number("focus_distance")?.unwrap_or(default.focus_distance),
+        sensor_width: fields.optional_number("sensor_width")?.unwrap_or(default.sensor_width),
+        aperture: match fields.optional_block("aperture")? {
+            Some(mut aperture) => read_aperture(&mut aperture)?,
+            None => default.aperture,
+        },
+    };
+    fields.finish()?;
+    Ok(lens)
+}
+fn write_lens(lens: &ThinLens) -> Result<Block, SceneFileError> {
+    Ok(Block::new()
+        .number("focal_length", lens.focal_length)
+        .number("f_number", lens.f_number)
+        .number("focus_distance", lens.focus_distance)
+        .number("sensor_width", lens.sensor_width)
+        .block("aperture", write_aperture(&lens.aperture)?))
+}
+fn read_aperture(fields: &mut Fields) -> Result<Aperture, SceneFileError> {
+    let aperture = match fields.tag() {
+        Some("disk") => Aperture::Disk,
+        Some("polygon") => Aperture::Polygon {
+            blades: match fields.integer("blades")? {
+                ..3 => return Err(fields.key_error("blades", "a polygon needs at least 3 blades")),
+                blades => blades as usize,
+            },
+            rotation: fields.optional_number("rotation")?.unwrap_or(0.0),
+        },
+        Some("image") => {
+            let path = fields.string("path")?;
+            Aperture::Image(Arc::new(load_aperture_image(fields, path)?))
+        }
+        Some(tag) => return Err(fields.error(format!("unknown aperture type `{tag}`"))),
+        None => return Err(fields.error("expected an aperture type in front of the block, e.g. `disk { }`")),
+    };
+    fields.finish()?;
+    Ok(aperture)
+}
+#[cfg(feature = "images")]
+fn load_aperture_image(fields: &Fields, path: String) -> Result<ApertureImage, SceneFileError> {
+    ApertureImage::load(path).map_err(|error| fields.key_error("path", format!("couldn't load the aperture image: {error}")))
+}
+#[cfg(not(feature = "images"))]
+fn load_aperture_image(fields: &Fields, _path: String) -> Result<ApertureImage, SceneFileError> {
+    Err(fields.key_error("path", "loading aperture images requires the `images` feature"))
+}
+fn write_aperture(aperture: &Aperture) -> Result<Block, SceneFileError> {
+    Ok(match aperture {
+        Aperture::Disk => Block::tagged("disk"),
+        Aperture::Polygon { blades, rotation } => Block::tagged("polygon")
+            .integer("blades", *blades as u64)
+            .number("rotation", *rotation),
+        Aperture::Image(image) => {
+            let path = image.path().ok_or(SceneFileError::ApertureWithoutFile)?;
+            Block::tagged("image").string("path", path.to_string_lossy())
+        }
+    })
+}
+fn write_camera(camera: &Camera) -> Result<Block, SceneFileError> {
+    let block = Block::new()
+        .vector("position", camera.position)
+        .vector("direction", camera.get_direction())
+        .vector("up", camera.get_up())
+        .number("roll", camera.get_roll())
+        .number("fov", camera.fov)
+        .string("fov_axis", camera.fov_axis.name());
+    let block = match &camera.lens {
+        Some(lens) => block.block("lens", write_lens(lens)?),
+        None => block,
+    };
+    let block = match write_camera_model(&*camera.model)? {
+        Some(model) => block.block("model", model),
+        None => block,
+    };
+    Ok(match &camera.motion {
+        Some(motion) => block.block("motion", write_motion(motion)),
+        None => block,
+    })
+}
+fn read_config(fields: &mut Fields) -> Result<Config, SceneFileError> {
+    let default = Config::default();
+    let config = Config {
+        rays_per_pixel: fields.optional_integer("rays_per_pixel")?.map_or(default.rays_per_pixel, |n| n as usize),
+        max_bounces: fields.optional_integer("max_bounces")?.map_or(default.max_bounces, |n| n as usize),
+        aovs: match fields.optional_list("aovs")? {
+            Some(aovs) => aovs.iter()
+                .map(|aov| match aov {
+                    Value::String(name) => Aov::from_name(name)
+                        .ok_or_else(|| fields.key_error("aovs", format!("unknown AOV `{name}`"))),
+                    _ => Err(fields.key_error("aovs", "expected a list of AOV names")),
+                })
+                .collect::<Result<_, _>>()?,
+            None => default.aovs,
+        },
+        denoiser: match fields.optional_block("denoiser")? {
+            Some(mut denoiser) => Some(read_denoiser(&mut denoiser)?),
+            None => default.denoiser,
+        },
+        adaptive_sampling: match fields.optional_block("adaptive_sampling")? {
+            Some(mut adaptive_sampling) => Some(read_adaptive_sampling(&mut adaptive_sampling)?),
+            None => default.adaptive_sampling,
+        },
+        sampler: match fields.optional_string("sampler")? {
+            Some(name) => SamplerKind::from_name(&name)
+                .ok_or_else(|| fields.key_error("sampler", format!("unknown sampler `{name}`")))?,
+            None => default.sampler,
+        },
+        seed: fields.optional_integer("seed")?.unwrap_or(default.seed),
+        threads: fields.optional_integer("threads")?.map_or(default.threads, |n| n as usize),
+        tile_size: fields.optional_integer("tile_size")?.map_or(default.tile_size, |n| n as usize),
+        shutter: match fields.optional_block("shutter")? {
+            Some(mut shutter) => read_shutter(&mut shutter)?,
+            None => default.shutter,
+        },
+    };
+    fields.finish()?;
+    Ok(config)
+}
+fn write_config(config: &Config) -> Block {
+    let block = Block::new()
+        .integer("rays_per_pixel", config.rays_per_pixel as u64)
+        .integer("max_bounces", config.max_bounces as u64)
+        .string("sampler", config.sampler.name())
+        .integer("seed", config.seed)
+        .integer("threads", config.threads as u64)
+        .integer("tile_size", config.tile_size as u64)
+        .block("shutter", Block::new().number("open", config.shutter.open).number("close", config.shutter.close))
+        .value("aovs", Value::List(config.aovs.iter().map(|aov| Value::String(aov.name().to_string())).collect()));
+    let block = match &config.denoiser {
+        Some(denoiser) => block.block("denoiser", write_denoiser(denoiser)),
+        None => block,
+    };
+    match &config.adaptive_sampling {
+        Some(adaptive_sampling) => block.block("adaptive_sampling", write_adaptive_sampling(adaptive_sampling)),
+        None => block,
+    }
+}
+fn read_shutter(fields: &mut Fields) -> Result<Shutter, SceneFileError> {
+    let shutter = Shutter::new(fields.optional_number("open")?.unwrap_or(0.0), fields.optional_number("close")?.unwrap_or(0.0));
+    fields.finish()?;
+    Ok(shutter)
+}
+fn read_motion(fields: &mut Fields) -> Result<Motion, SceneFileError> {
+    let keyframes = fields.blocks("keyframe")?.into_iter()
+        .map(|mut keyframe| {
+            let time = keyframe.number("time")?;
+            let translation = keyframe.optional_vector("translation")?.unwrap_or(Vector3::zeros());
+            let rotation = keyframe.optional_vector("rotation")?.unwrap_or(Vector3::zeros());
+            keyframe.finish()?;
+            Ok((time, Transform::new(translation, rotation)))
+        })
+        .collect::<Result<Vec<_>, SceneFileError>>()?;
+    if keyframes.is_empty() {
+        return Err(fields.error("expected at least one `keyframe { ... }`"));
+    }
+    fields.finish()?;
+    Ok(Motion::new(keyframes))
+}
+fn write_motion(motion: &Motion) -> Block {
+    motion.keyframes().iter().fold(Block::new(), |block, (time, transform)| {
+        block.block("keyframe", Block::new()
+            .number("time", *time)
+            .vector("translation", transform.translation)
+            .vector("rotation", transform.rotation))
+    })
+}
+fn read_denoiser(fields: &mut Fields) -> Result<Denoiser, SceneFileError> {
+    let default = Denoiser::default();
+    let denoiser = Denoiser {
+        iterations: fields.optional_integer("iterations")?.map_or(default.iterations, |n| n as usize),
+        color_sigma: fields.optional_number("color_sigma")?.unwrap_or(default.color_sigma),
+        normal_sigma: fields.optional_number("normal_sigma")?.unwrap_or(default.normal_sigma),
+        albedo_sigma: fields.optional_number("albedo_sigma")?.unwrap_or(default.albedo_sigma),
+    };
+    fields.finish()?;
+    Ok(denoiser)
+}
+fn write_denoiser(denoiser: &Denoiser) -> Block {
+    Block::new()
+        .integer("iterations", denoiser.iterations as u64)
+        .number("color_sigma", denoiser.color_sigma)
+        .number("normal_sigma", denoiser.normal_sigma)
+        .number("albedo_sigma", denoiser.albedo_sigma)
+}
+fn read_adaptive_sampling(fields: &mut Fields) -> Result<AdaptiveSampling, SceneFileError> {
+    let default = AdaptiveSampling::default();
+    let adaptive_sampling = AdaptiveSampling {
+        threshold: fields.optional_number("threshold")?.unwrap_or(default.threshold),
+        min_samples: fields.optional_integer("min_samples")?.map_or(default.min_samples, |n| n as usize),
+    };
+    fields.finish()?;
+    Ok(adaptive_sampling)
+}
+fn write_adaptive_sampling(adaptive_sampling: &AdaptiveSampling) -> Block {
+    Block::new()
+        .number("threshold", adaptive_sampling.threshold)
+        .integer("min_samples", adaptive_sampling.min_samples as u64)
+}
+fn read_material(fields: &mut Fields) -> Result<Material, SceneFileError> {
+    let mut material = Material::new(
+        fields.vector("base_color")?,
+        fields.optional_vector("emission_color")?.unwrap_or(Vector3::zeros()),
+        fields.optional_number("roughness")?.unwrap_or(1.0),
+    );
+    material.metallic = fields.optional_number("metallic")?.unwrap_or(0.0);
+    if let Some(mut dielectric) = fields.optional_block("dielectric")? {
+        material.dielectric = Some(Dielectric {
+            ior: dielectric.number("ior")?,
+            absorption: dielectric.optional_vector("absorption")?.unwrap_or(Vector3::zeros()),
+        });
+        dielectric.finish()?;
+    }
+    fields.finish()?;
+    Ok(material)
+}
+fn write_material(material: &Material) -> Block {
+    let block = Block::new()
+        .vector("base_color", material.base_color)
+        .vector("emission_color", material.emission_color)
+        .number("roughness", material.roughness)
+        .number("metallic", material.metallic);
+    match material.dielectric {
+        Some(dielectric) => block.block("dielectric", Block::new()
+            .number("ior", dielectric.ior)
+            .vector("absorption", dielectric.absorption)),
+        None => block,
+    }
+}
+fn read_environment(fields: &mut Fields) -> Result<Environment, SceneFileError> {
+    let environment = match fields.tag() {
+        Some("color") => Environment::Color(fields.vector("color")?),
+        Some("gradient") => Environment::Gradient { bottom: fields.vector("bottom")?, top: fields.vector("top")? },
+        Some("map") => {
+            let path = fields.string("path")?;
+            let intensity = fields.optional_number("intensity")?.unwrap_or(1.0);
+            Environment::Map(std::sync::Arc::new(load_environment_map(fields, path)?.with_intensity(intensity)))
+        }
+        Some("sky") => Environment::Sky(Box::new(
+            Sky::new(
+                fields.vector("sun_direction")?,
+                fields.optional_number("turbidity")?.unwrap_or(3.0),
+                fields.optional_vector("ground_albedo")?.unwrap_or(Vector3::ones() * 0.2),
+            ).with_intensity(fields.optional_number("intensity")?.unwrap_or(1.0))
+        )),
+        Some(tag) => return Err(fields.error(format!("unknown environment type `{tag}`"))),
+        None => return Err(fields.error("expected an environment type in front of the block, e.g. `color { ... }`")),
+    };
+    fields.finish()?;
+    Ok(environment)
+}
+#[cfg(feature = "images")]
+fn load_environment_map(fields: &Fields, path: String) -> Result<EnvironmentMap, SceneFileError> {
+    EnvironmentMap::load(path).map_err(|error| fields.key_error("path", format!("couldn't load the environment map: {error}")))
+}
+#[cfg(not(feature = "images"))]
+fn load_environment_map(fields: &Fields, _path: String) -> Result<EnvironmentMap, SceneFileError> {
+    Err(fields.key_error("path", "loading environment maps requires the `images` feature"))
+}
+fn write_environment(environment: &Environment) -> Result<Block, SceneFileError> {
+    Ok(match environment {
+        Environment::Color(color) => Block::tagged("color").vector("color", *color),
+        Environment::Gradient { bottom, top } => Block::tagged("gradient")
+            .vector("bottom", *bottom)
+            .vector("top", *top),
+        Environment::Map(map) => {
+            let path = map.path().ok_or(SceneFileError::EnvironmentWithoutFile)?;
+            Block::tagged("map")
+                .string("path", path.to_string_lossy())
+                .number("intensity", map.intensity)
+        }
+        Environment::Sky(sky) => Block::tagged("sky")
+            .vector("sun_direction", sky.sun_direction())
+            .number("turbidity", sky.turbidity())
+            .vector("ground_albedo", sky.ground_albedo())
+            .number("intensity", sky.intensity),
+    })
+}
+fn read_light(fields: &mut Fields) -> Result<Light, SceneFileError> {
+    let light = match fields.tag() {
+        Some("point") => Light::point(fields.vector("position")?, fields.vector("color")?, fields.number("intensity")?),
+        Some("spot") => Light::spot(
+            fields.vector("position")?,
+            fields.vector("direction")?,
+            fields.number("inner_angle")?,
+            fields.number("outer_angle")?,
+            fields.vector("color")?,
+            fields.number("intensity")?,
+        ),
+        Some("directional") => Light::directional(
+            fields.vector("direction")?,
+            fields.optional_number("angular_diameter")?.unwrap_or(0.0),
+            fields.vector("color")?,
+            fields.number("intensity")?,
+        ),
+        Some(tag) => return Err(fields.error(format!("unknown light type `{tag}`"))),
+        None => return Err(fields.error("expected a light type in front of the block, e.g. `point { ... }`")),
+    };
+    fields.finish()?;
+    Ok(light)
+}
+fn write_light(light: &Light) -> Block {
+    match *light {
+        Light::Point { position, color, intensity } => Block::tagged("point")
+            .vector("position", position)
+            .vector("color", color)
+            .number("intensity", intensity),
+        Light::Spot { position, direction, inner_angle, outer_angle, color, intensity } => Block::tagged("spot")
+            .vector("position", position)
+            .vector("direction", direction)
+            .number("inner_angle", inner_angle)
+            .number("outer_angle", outer_angle)
+            .vector("color", color)
+            .number("intensity", intensity),
+        Light::Directional { direction, angular_diameter, color, intensity } => Block::tagged("directional")
+            .vector("direction", direction)
+            .number("angular_diameter", angular_diameter)
+            .vector("color", color)
+            .number("intensity", intensity),
+    }
+}
+fn read_tone_mapping(fields: &mut Fields) -> Result<ToneMapping, SceneFileError> {
+    let operator = match fields.tag() {
+        Some("clamp") => ToneMapOperator::Clamp,
+        Some("reinhard") => ToneMapOperator::Reinhard,
+        Some("extended_reinhard") => ToneMapOperator::ExtendedReinhard { white: fields.number("white")? },
+        Some("aces") => ToneMapOperator::Aces,
+        Some("agx") => ToneMapOperator::Agx,
+        Some(tag) => return Err(fields.error(format!("unknown tone mapping operator `{tag}`"))),
+        None => return Err(fields.error("expected a tone mapping operator in front of the block, e.g. `aces { ... }`")),
+    };
+    let tone_mapping = ToneMapping::new(fields.optional_number("exposure")?.unwrap_or(0.0), operator);
+    fields.finish()?;
+    Ok(tone_mapping)
+}
+fn write_tone_mapping(tone_mapping: &ToneMapping) -> Block {
+    let block = match tone_mapping.operator {
+        ToneMapOperator::Clamp => Block::tagged("clamp"),
+        ToneMapOperator::Reinhard => Block::tagged("reinhard"),
+        ToneMapOperator::ExtendedReinhard { white } => Block::tagged("extended_reinhard").number("white", white),
+        ToneMapOperator::Aces => Block::tagged("aces"),
+        ToneMapOperator::Agx => Block::tagged("agx"),
+    };
+    block.number("exposure", tone_mapping.exposure)
+}
+
+impl SceneShape for Sphere {
+    const NAME: &'static str = "sphere";
+    fn read(fields: &mut Fields) -> Result<Self, SceneFileError> {
+        Ok(Sphere::new(fields.vector("position")?, fields.number("radius")?))
+    }
+    fn write(&self) -> Block {
+        Block::new()
+            .vector("position", self.position)
+            .number("radius", self.radius)
+    }
+}
+impl SceneShape for Plane {
+    const NAME: &'static str = "plane";
+    fn read(fields: &mut Fields) -> Result<Self, SceneFileError> {
+        Ok(Plane::new(fields.vector("position")?, fields.vector("normal")?))
+    }
+    fn write(&self) -> Block {
+        Block::new()
+            .vector("position", self.position)
+            .vector("normal", self.normal)
+    }
+}
+impl SceneShape for Triangle {
+    const NAME: &'static str = "triangle";
+    fn read(fields: &mut Fields) -> Result<Self, SceneFileError> {
+        let vertices = fields.vectors("vertices")?;
+        let vertices = vertices.try_into()
+            .map_err(|_| fields.key_error("vertices", "expected exactly three vertices"))?;
+        Ok(Triangle::new(vertices))
+    }
+    fn write(&self) -> Block {
+        Block::new().vectors("vertices", &self.vertices)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scene() -> Scene {
+        let config = Config::default().with_rays_per_pixel(3).with_aovs(vec![Aov::ObjectIndex, Aov::Albedo])
+            .with_denoiser(Some(Denoiser { iterations: 3, ..Denoiser::default() }))
+            .with_adaptive_sampling(Some(AdaptiveSampling { threshold: 0.01, min_samples: 2 }))
+            .with_sampler(SamplerKind::Halton).with_seed(u64::MAX)
+            .with_shutter(Shutter::new(-0.25, 0.25));
+        let camera = Camera::look_at(Vector3::new(-1.5, 0.1, 1e-20), Vector3::new(-0.5, 0.4, -0.2), Vector3::y(), 1.234)
+            .with_roll(0.1)
+            .with_lens(ThinLens::new(85.0, 1.8, 3.0).with_aperture(Aperture::Polygon { blades: 7, rotation: 0.2 }))
+            .with_fov_axis(FovAxis::Diagonal)
+            .with_motion(Motion::linear(Transform::default(), Transform::new(Vector3::new(0.1, 0, 0), Vector3::z() * 0.05)));
+        let mut scene = Scene::new(config, camera);
+        scene.add_object(Object::new(Sphere::new(Vector3::new(3, 0, 1), 1.0 / 3.0), Material::new(Vector3::new(0.1, 0.2, 0.3), Vector3::ones(), 0.7).with_metallic(0.25)));
+        scene.add_object(Object::new(Plane::new(Vector3::zeros(), Vector3::z()), Material::colored(Vector3::ones() * 0.5))
+            .with_motion(Motion::new(vec![(0.0, Transform::default()), (0.5, Transform::translation(Vector3::z())), (1.0, Transform::rotation(Vector3::x(), 0.3))])));
+        scene.add_object(Object::new(Triangle::new([Vector3::zeros(), Vector3::x(), Vector3::new(0.0, f64::MAX, 0.0)]), Material::mirror()));
+        scene.add_object(Object::new(Sphere::new(Vector3::zeros(), 0.5), Material::dielectric(1.33, Vector3::new(0.1, 0.2, 0.3))));
+        scene.add_light(Light::point(Vector3::new(0, 0, 3), Vector3::ones(), 10.0));
+        scene.add_light(Light::spot(Vector3::zeros(), Vector3::new(1, 2, 3), 0.1, 0.3, Vector3::x(), 0.1 + 0.2));
+        scene.add_light(Light::directional(-Vector3::z(), 0.0093, Vector3::ones(), 2.0));
+        scene.environment = Environment::Gradient { bottom: Vector3::zeros(), top: Vector3::new(0.3, 0.5, 1.0) };
+        scene.tone_mapping = ToneMapping::new(-0.5, ToneMapOperator::ExtendedReinhard { white: 6.0 });
+        scene
+    }
+    #[test]
+    fn round_trip_is_lossless() {
+        let text = write(&scene()).unwrap();
+        let loaded = parse(&text).unwrap();
+        assert_eq!(write(&loaded).unwrap(), text);
+        assert_eq!(loaded.config.aovs, [Aov::ObjectIndex, Aov::Albedo]);
+        assert_eq!(loaded.config.denoiser, scene().config.denoiser);
+        assert_eq!(loaded.config.adaptive_sampling, scene().config.adaptive_sampling);
+        assert_eq!((loaded.config.sampler, loaded.config.seed), (SamplerKind::Halton, u64::MAX));
+        assert_eq!(loaded.objects[0].with_shape(|sphere: &Sphere| sphere.radius), Some(1.0 / 3.0));
+        assert_eq!(loaded.camera.position.z, 1e-20);
+        assert_eq!(loaded.config.shutter, Shutter::new(-0.25, 0.25));
+        assert_eq!((&loaded.camera.motion, &loaded.objects[1].motion), (&scene().camera.motion, &scene().objects[1].motion));
+        assert_eq!(loaded.objects[0].motion, None);
+        assert_eq!((loaded.camera.get_up(), loaded.camera.get_roll(), loaded.camera.fov_axis), (Vector3::y(), 0.1, FovAxis::Diagonal));
+        assert_eq!((loaded.camera.fov, &loaded.camera.lens), (scene().camera.fov, &scene().camera.lens));
+        assert_eq!(loaded.objects[3].material.dielectric, Some(Dielectric { ior: 1.33, absorption: Vector3::new(0.1, 0.2, 0.3) }));
+        assert_eq!(loaded.lights, scene().lights);
+        assert_eq!(loaded.tone_mapping, scene().tone_mapping);
+    }
+    #[test]
+    fn camera_models_round_trip() {
+        let models: [Arc<dyn CameraModel>; 4] = [
+            Arc::new(Orthographic { width: 2.5 }),
+            Arc::new(Fisheye { fov: 3.5, mapping: FisheyeMapping::Equisolid }),
+            Arc::new(Equirectangular),
+            Arc::new(Cubemap),
+        ];
+        for model in models {
+            let mut scene = scene();
+            scene.camera.model = model.clone();
+            let text = write(&scene).unwrap();
+            assert_eq!(format!("{:?}", parse(&text).unwrap().camera.model), format!("{model:?}"));
+        }
+        #[derive(Debug)]
+        struct Custom;
+        impl CameraModel for Custom {
+            fn ray(&self, _: &Camera, _: (f64, f64), _: f64, _: (f64, f64)) -> Option<(Vector3, Vector3)> {
+                None
+            }
+        }
+        let mut scene = scene();
+        scene.camera = scene.camera.with_model(Custom);
+        assert!(matches!(write(&scene), Err(SceneFileError::CustomCameraModel)));
+    }
+    #[test]
+    fn schema_errors_name_the_key() {
+        let text = write(&scene()).unwrap().replace("radius = ", "radius = \"big\" # ");
+        match parse(&text).err() {
+            Some(SceneFileError::Schema { key, .. }) => assert_eq!(key, "object[0].shape.radius"),
+            other => panic!("expected a schema error, got {other:?}"),
+        }
+        let text = write(&scene()).unwrap().replace("roughness", "rough");
+        match parse(&text).err() {
+            Some(SceneFileError::Schema { key, message, .. }) => {
+                assert_eq!(key, "object[0].material.rough");
+                assert_eq!(message, "unknown key");
+            }
+            other => panic!("expected a schema error, got {other:?}"),
+        }
+        for (valid, invalid, invalid_key) in [("f_number = 1.8", "f_number = 0", "camera.lens.f_number"), ("blades = 7", "blades = 2", "camera.lens.aperture.blades")] {
+            match parse(&write(&scene()).unwrap().replace(valid, invalid)).err() {
+                Some(SceneFileError::Schema { key, .. }) => assert_eq!(key, invalid_key),
+                other => panic!("expected a schema error, got {other:?}"),
+            }
+        }
+    }
+    #[test]
+    fn unregistered_shapes_are_not_written() {
+        let mut scene = scene();
+        scene.add_object(Object::new(crate::object::mesh::Mesh::new(vec![Vector3::zeros(); 3], vec![[0, 1, 2]]), Material::mirror()));
+        assert!(matches!(write(&scene), Err(SceneFileError::UnregisteredShape { object: 4 })));
+    }
+}
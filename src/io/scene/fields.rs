@@ -0,0 +1,219 @@
+use crate::io::scene::syntax::{Block, Entry, Value};
+use crate::io::scene::SceneFileError;
+use crate::math::Vector3;
+use std::str::FromStr;
+
+/// Reads the entries of a [Block] while keeping track of where in the file they are.
+///
+/// Every getter marks the entry as used, [Fields::finish] then reports the entries that weren't.
+/// All errors contain the full key path (e.g. `object[2].shape.radius`).
+pub struct Fields<'a> {
+    block: &'a Block,
+    path: String,
+    line: usize,
+    used: Vec<bool>,
+}
+impl<'a> Fields<'a> {
+    pub(crate) fn new(block: &'a Block, path: String, line: usize) -> Self {
+        Self { block, path, line, used: vec![false; block.entries.len()] }
+    }
+    /// returns the key path of this block
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+    /// returns the type name of this block
+    pub fn tag(&self) -> Option<&str> {
+        self.block.tag.as_deref()
+    }
+    /// creates an error about this block
+    pub fn error(&self, message: impl Into<String>) -> SceneFileError {
+        SceneFileError::Schema { key: self.path.clone(), line: self.line, message: message.into() }
+    }
+    /// creates an error about the entry with the given key
+    pub fn key_error(&self, key: &str, message: impl Into<String>) -> SceneFileError {
+        let line = self.block.entries.iter()
+            .find(|entry| entry.key == key)
+            .map_or(self.line, |entry| entry.line);
+        SceneFileError::Schema { key: self.key_path(key), line, message: message.into() }
+    }
+    /// Checks that every entry of the block was read.
+    ///
+    /// returns: Result<(), SceneFileError>
+    ///     An error naming the first unknown key.
+    pub fn finish(&self) -> Result<(), SceneFileError> {
+        match self.used.iter().position(|used| !used) {
+            Some(i) => {
+                let entry = &self.block.entries[i];
+                Err(SceneFileError::Schema { key: self.key_path(&entry.key), line: entry.line, message: "unknown key".to_string() })
+            }
+            None => Ok(()),
+        }
+    }
+    /// reads a required floating point number
+    pub fn number(&mut self, key: &str) -> Result<f64, SceneFileError> {
+        self.required(key, Self::optional_number)
+    }
+    /// reads a floating point number, if it is present
+    pub fn optional_number(&mut self, key: &str) -> Result<Option<f64>, SceneFileError> {
+        self.optional_parsed(key, "a number")
+    }
+    /// reads a required non-negative integer
+    pub fn integer(&mut self, key: &str) -> Result<u64, SceneFileError> {
+        self.required(key, Self::optional_integer)
+    }
+    /// reads a non-negative integer, if it is present
+    pub fn optional_integer(&mut self, key: &str) -> Result<Option<u64>, SceneFileError> {
+        self.optional_parsed(key, "a non-negative integer")
+    }
+    /// reads a required vector of three numbers
+    pub fn vector(&mut self, key: &str) -> Result<Vector3, SceneFileError> {
+        self.required(key, Self::optional_vector)
+    }
+    /// reads a vector of three numbers, if it is present
+    pub fn optional_vector(&mut self, key: &str) -> Result<Option<Vector3>, SceneFileError> {
+        let Some(entry) = self.take(key)? else {
+            return Ok(None);
+        };
+        vector_of(&entry.value).map(Some)
+            .ok_or_else(|| self.entry_error(entry, "expected a vector of three numbers"))
+    }
+    /// reads a required list of vectors
+    pub fn vectors(&mut self, key: &str) -> Result<Vec<Vector3>, SceneFileError> {
+        self.required(key, Self::optional_vectors)
+    }
+    /// reads a list of vectors, if it is present
+    pub fn optional_vectors(&mut self, key: &str) -> Result<Option<Vec<Vector3>>, SceneFileError> {
+        let Some(entry) = self.take(key)? else {
+            return Ok(None);
+        };
+        let vectors = match &entry.value {
+            Value::List(values) => values.iter().map(vector_of).collect::<Option<Vec<_>>>(),
+            _ => None,
+        };
+        vectors.map(Some).ok_or_else(|| self.entry_error(entry, "expected a list of vectors"))
+    }
+    /// reads a required string
+    pub fn string(&mut self, key: &str) -> Result<String, SceneFileError> {
+        self.required(key, Self::optional_string)
+    }
+    /// reads a string, if it is present
+    pub fn optional_string(&mut self, key: &str) -> Result<Option<String>, SceneFileError> {
+        match self.take(key)? {
+            Some(Entry { value: Value::String(string), .. }) => Ok(Some(string.clone())),
+            Some(entry) => Err(self.entry_error(entry, "expected a string")),
+            None => Ok(None),
+        }
+    }
+    /// reads a required boolean
+    pub fn bool(&mut self, key: &str) -> Result<bool, SceneFileError> {
+        self.required(key, Self::optional_bool)
+    }
+    /// reads a boolean, if it is present
+    pub fn optional_bool(&mut self, key: &str) -> Result<Option<bool>, SceneFileError> {
+        match self.take(key)? {
+            Some(Entry { value: Value::Bool(bool), .. }) => Ok(Some(*bool)),
+            Some(entry) => Err(self.entry_error(entry, "expected `true` or `false`")),
+            None => Ok(None),
+        }
+    }
+    /// reads a required list of values
+    pub fn list(&mut self, key: &str) -> Result<&'a [Value], SceneFileError> {
+        self.required(key, Self::optional_list)
+    }
+    /// reads a list of values, if it is present
+    pub fn optional_list(&mut self, key: &str) -> Result<Option<&'a [Value]>, SceneFileError> {
+        match self.take(key)? {
+            Some(Entry { value: Value::List(values), .. }) => Ok(Some(values)),
+            Some(entry) => Err(self.entry_error(entry, "expected a list")),
+            None => Ok(None),
+        }
+    }
+    /// reads a required nested block
+    pub fn block(&mut self, key: &str) -> Result<Fields<'a>, SceneFileError> {
+        self.required(key, Self::optional_block)
+    }
+    /// reads a nested block, if it is present
+    pub fn optional_block(&mut self, key: &str) -> Result<Option<Fields<'a>>, SceneFileError> {
+        match self.take(key)? {
+            Some(entry @ Entry { value: Value::Block(block), .. }) => {
+                Ok(Some(Fields::new(block, self.key_path(&entry.key), entry.line)))
+            }
+            Some(entry) => Err(self.entry_error(entry, "expected a block")),
+            None => Ok(None),
+        }
+    }
+    /// Reads all blocks with the given key. Unlike all other keys, these may appear multiple times.
+    ///
+    /// The blocks get the paths `key[0]`, `key[1]`, ...
+    pub fn blocks(&mut self, key: &str) -> Result<Vec<Fields<'a>>, SceneFileError> {
+        let mut blocks = Vec::new();
+        for (i, entry) in self.block.entries.iter().enumerate() {
+            if entry.key != key {
+                continue;
+            }
+            self.used[i] = true;
+            let path = format!("{}[{}]", self.key_path(key), blocks.len());
+            match &entry.value {
+                Value::Block(block) => blocks.push(Fields::new(block, path, entry.line)),
+                _ => return Err(SceneFileError::Schema { key: path, line: entry.line, message: "expected a block".to_string() }),
+            }
+        }
+        Ok(blocks)
+    }
+}
+impl<'a> Fields<'a> {
+    fn key_path(&self, key: &str) -> String {
+        if self.path.is_empty() {
+            key.to_string()
+        } else {
+            format!("{}.{key}", self.path)
+        }
+    }
+    fn entry_error(&self, entry: &Entry, message: &str) -> SceneFileError {
+        SceneFileError::Schema { key: self.key_path(&entry.key), line: entry.line, message: message.to_string() }
+    }
+    /// marks the entry with the given key as used and returns it
+    fn take(&mut self, key: &str) -> Result<Option<&'a Entry>, SceneFileError> {
+        let block = self.block;
+        let mut matches = block.entries.iter().enumerate().filter(|(_, entry)| entry.key == key);
+        let Some((i, entry)) = matches.next() else {
+            return Ok(None);
+        };
+        if let Some((_, duplicate)) = matches.next() {
+            return Err(self.entry_error(duplicate, "duplicate key"));
+        }
+        self.used[i] = true;
+        Ok(Some(entry))
+    }
+    fn required<T>(&mut self, key: &str, read: impl FnOnce(&mut Self, &str) -> Result<Option<T>, SceneFileError>) -> Result<T, SceneFileError> {
+        read(self, key)?.ok_or_else(|| SceneFileError::Schema {
+            key: self.key_path(key),
+            line: self.line,
+            message: "missing required key".to_string(),
+        })
+    }
+    fn optional_parsed<T: FromStr>(&mut self, key: &str, expected: &str) -> Result<Option<T>, SceneFileError> {
+        match self.take(key)? {
+            Some(entry @ Entry { value: Value::Number(number), .. }) => number.parse().map(Some)
+                .map_err(|_| self.entry_error(entry, &format!("expected {expected}"))),
+            Some(entry) => Err(self.entry_error(entry, &format!("expected {expected}"))),
+            None => Ok(None),
+        }
+    }
+}
+/// converts a list of three numbers into a vector
+fn vector_of(value: &Value) -> Option<Vector3> {
+    let Value::List(values) = value else {
+        return None;
+    };
+    let components = values.iter()
+        .map(|value| match value {
+            Value::Number(number) => number.parse::<f64>().ok(),
+            _ => None,
+        })
+        .collect::<Option<Vec<_>>>()?;
+    match components[..] {
+        [x, y, z] => Some(Vector3::new(x, y, z)),
+        _ => None,
+    }
+}
@@ -0,0 +1,325 @@
+use crate::math::Vector3;
+use crate::io::scene::SceneFileError;
+use std::fmt::Write;
+
+/// A value in a scene file
+#[derive(Clone, Debug, PartialEq)]
+pub enum Value {
+    /// A number, kept as it was written so integers and floats can both be read without losing precision
+    Number(String),
+    /// A quoted string
+    String(String),
+    /// `true` or `false`
+    Bool(bool),
+    /// A list of values in square brackets, e.g. a vector `[1, 2, 3]`
+    List(Vec<Value>),
+    /// A block of entries in curly braces, optionally tagged with a type name (`sphere { ... }`)
+    Block(Block),
+}
+/// A `key = value` pair in a [Block]
+#[derive(Clone, Debug, PartialEq)]
+pub struct Entry {
+    pub key: String,
+    pub value: Value,
+    /// The line the entry starts on
+    pub line: usize,
+}
+/// A list of entries, optionally with a type name.
+///
+/// Blocks are both the result of parsing and the way to write values.
+/// Custom shapes describe themselves with one when the scene is written.
+///
+/// # Examples
+///
+/// ```
+/// use rtx::io::scene::Block;
+/// use rtx::math::Vector3;
+///
+/// let block = Block::tagged("sphere")
+///     .vector("position", Vector3::zeros())
+///     .number("radius", 1.0);
+/// ```
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Block {
+    /// The type name in front of the block
+    pub tag: Option<String>,
+    /// The entries of the block in the order they were written
+    pub entries: Vec<Entry>,
+}
+impl Block {
+    /// creates a new empty block without a type name
+    pub fn new() -> Self {
+        Self::default()
+    }
+    /// creates a new empty block with a type name
+    pub fn tagged(tag: impl Into<String>) -> Self {
+        Self { tag: Some(tag.into()), entries: Vec::new() }
+    }
+    /// adds an entry with an arbitrary value
+    pub fn value(mut self, key: impl Into<String>, value: Value) -> Self {
+        self.entries.push(Entry { key: key.into(), value, line: 0 });
+        self
+    }
+    /// adds a floating point number
+    pub fn number(self, key: impl Into<String>, number: f64) -> Self {
+        self.value(key, number_value(number))
+    }
+    /// adds an integer
+    pub fn integer(self, key: impl Into<String>, integer: u64) -> Self {
+        self.value(key, Value::Number(integer.to_string()))
+    }
+    /// adds a vector as a list of three numbers
+    pub fn vector(self, key: impl Into<String>, vector: Vector3) -> Self {
+        self.value(key, vector_value(vector))
+    }
+    /// adds a list of vectors
+    pub fn vectors(self, key: impl Into<String>, vectors: &[Vector3]) -> Self {
+        self.value(key, Value::List(vectors.iter().map(|&vector| vector_value(vector)).collect()))
+    }
+    /// adds a string
+    pub fn string(self, key: impl Into<String>, string: impl Into<String>) -> Self {
+        self.value(key, Value::String(string.into()))
+    }
+    /// adds a boolean
+    pub fn bool(self, key: impl Into<String>, bool: bool) -> Self {
+        self.value(key, Value::Bool(bool))
+    }
+    /// adds a nested block
+    pub fn block(self, key: impl Into<String>, block: Block) -> Self {
+        self.value(key, Value::Block(block))
+    }
+}
+/// creates the value of a floating point number, written so that it is read back exactly
+pub fn number_value(number: f64) -> Value {
+    // `Debug` uses the shortest representation that round-trips and switches to exponents for very large/small numbers
+    Value::Number(format!("{number:?}"))
+}
+/// creates the value of a vector
+pub fn vector_value(vector: Vector3) -> Value {
+    Value::List(vec![number_value(vector.x), number_value(vector.y), number_value(vector.z)])
+}
+
+/// Parses the entries of a whole file
+pub fn parse(source: &str) -> Result<Block, SceneFileError> {
+    let mut parser = Parser { tokens: tokenize(source)?, position: 0 };
+    let entries = parser.entries(None)?;
+    Ok(Block { tag: None, entries })
+}
+/// Writes the entries of a block as the contents of a file
+pub fn write(block: &Block) -> String {
+    let mut output = String::new();
+    for entry in &block.entries {
+        write_entry(&mut output, entry, 0);
+    }
+    output
+}
+fn write_entry(output: &mut String, entry: &Entry, indentation: usize) {
+    let indent = "    ".repeat(indentation);
+    match &entry.value {
+        // untagged blocks are written without the `=`, as sections
+        Value::Block(block) if block.tag.is_none() => {
+            let _ = write!(output, "{indent}{} ", entry.key);
+        }
+        _ => {
+            let _ = write!(output, "{indent}{} = ", entry.key);
+        }
+    }
+    write_value(output, &entry.value, indentation);
+    output.push('\n');
+}
+fn write_value(output: &mut String, value: &Value, indentation: usize) {
+    match value {
+        Value::Number(number) => output.push_str(number),
+        Value::String(string) => {
+            output.push('"');
+            for c in string.chars() {
+                match c {
+                    '"' => output.push_str("\\\""),
+                    '\\' => output.push_str("\\\\"),
+                    '\n' => output.push_str("\\n"),
+                    c => output.push(c),
+                }
+            }
+            output.push('"');
+        }
+        Value::Bool(bool) => output.push_str(if *bool { "true" } else { "false" }),
+        Value::List(values) => {
+            output.push('[');
+            for (i, value) in values.iter().enumerate() {
+                if i > 0 {
+                    output.push_str(", ");
+                }
+                write_value(output, value, indentation);
+            }
+            output.push(']');
+        }
+        Value::Block(block) => {
+            if let Some(tag) = &block.tag {
+                let _ = write!(output, "{tag} ");
+            }
+            output.push_str("{\n");
+            for entry in &block.entries {
+                write_entry(output, entry, indentation + 1);
+            }
+            output.push_str(&"    ".repeat(indentation));
+            output.push('}');
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum TokenKind {
+    Identifier(String),
+    Number(String),
+    String(String),
+    Symbol(char),
+}
+#[derive(Clone, Debug)]
+struct Token {
+    kind: TokenKind,
+    line: usize,
+    column: usize,
+}
+fn syntax_error(line: usize, column: usize, message: impl Into<String>) -> SceneFileError {
+    SceneFileError::Syntax { line, column, message: message.into() }
+}
+fn tokenize(source: &str) -> Result<Vec<Token>, SceneFileError> {
+    let mut tokens = Vec::new();
+    for (line_index, line) in source.lines().enumerate() {
+        let line_number = line_index + 1;
+        let mut chars = line.char_indices().peekable();
+        while let Some((start, c)) = chars.next() {
+            let column = start + 1;
+            let kind = match c {
+                '#' => break,
+                c if c.is_whitespace() => continue,
+                '{' | '}' | '[' | ']' | '=' | ',' => TokenKind::Symbol(c),
+                '"' => {
+                    let mut string = String::new();
+                    loop {
+                        match chars.next() {
+                            Some((_, '"')) => break,
+                            Some((_, '\\')) => match chars.next() {
+                                Some((_, 'n')) => string.push('\n'),
+                                Some((_, c @ ('"' | '\\'))) => string.push(c),
+                                _ => return Err(syntax_error(line_number, column, "invalid escape sequence in string")),
+                            },
+                            Some((_, c)) => string.push(c),
+                            None => return Err(syntax_error(line_number, column, "unterminated string")),
+                        }
+                    }
+                    TokenKind::String(string)
+                }
+                c if c.is_ascii_digit() || c == '-' || c == '+' || c == '.' => {
+                    let mut end = start + c.len_utf8();
+                    while let Some(&(i, c)) = chars.peek() {
+                        if !(c.is_ascii_alphanumeric() || matches!(c, '.' | '-' | '+')) {
+                            break;
+                        }
+                        end = i + c.len_utf8();
+                        chars.next();
+                    }
+                    TokenKind::Number(line[start..end].to_string())
+                }
+                c if c.is_alphabetic() || c == '_' => {
+                    let mut end = start + c.len_utf8();
+                    while let Some(&(i, c)) = chars.peek() {
+                        if !(c.is_alphanumeric() || c == '_') {
+                            break;
+                        }
+                        end = i + c.len_utf8();
+                        chars.next();
+                    }
+                    TokenKind::Identifier(line[start..end].to_string())
+                }
+                c => return Err(syntax_error(line_number, column, format!("unexpected character `{c}`"))),
+            };
+            tokens.push(Token { kind, line: line_number, column });
+        }
+    }
+    Ok(tokens)
+}
+struct Parser {
+    tokens: Vec<Token>,
+    position: usize,
+}
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.position)
+    }
+    fn next(&mut self) -> Result<Token, SceneFileError> {
+        let token = self.tokens.get(self.position).cloned()
+            .ok_or_else(|| {
+                let (line, column) = self.tokens.last().map_or((1, 1), |t| (t.line, t.column));
+                syntax_error(line, column, "unexpected end of file")
+            })?;
+        self.position += 1;
+        Ok(token)
+    }
+    fn is_symbol(&self, symbol: char) -> bool {
+        self.peek().is_some_and(|token| token.kind == TokenKind::Symbol(symbol))
+    }
+    fn expect_symbol(&mut self, symbol: char) -> Result<(), SceneFileError> {
+        let token = self.next()?;
+        if token.kind != TokenKind::Symbol(symbol) {
+            return Err(syntax_error(token.line, token.column, format!("expected `{symbol}`")));
+        }
+        Ok(())
+    }
+    /// parses entries until the closing symbol (or the end of the file, if there is none)
+    fn entries(&mut self, closing: Option<char>) -> Result<Vec<Entry>, SceneFileError> {
+        let mut entries = Vec::new();
+        loop {
+            match (self.peek(), closing) {
+                (None, None) => return Ok(entries),
+                (Some(token), Some(closing)) if token.kind == TokenKind::Symbol(closing) => {
+                    self.position += 1;
+                    return Ok(entries);
+                }
+                _ => {}
+            }
+            let token = self.next()?;
+            let TokenKind::Identifier(key) = token.kind else {
+                return Err(syntax_error(token.line, token.column, "expected a key"));
+            };
+            let value = if self.is_symbol('{') {
+                self.position += 1;
+                Value::Block(Block { tag: None, entries: self.entries(Some('}'))? })
+            } else {
+                self.expect_symbol('=')?;
+                self.value()?
+            };
+            entries.push(Entry { key, value, line: token.line });
+        }
+    }
+    fn value(&mut self) -> Result<Value, SceneFileError> {
+        let token = self.next()?;
+        match token.kind {
+            TokenKind::Number(number) => Ok(Value::Number(number)),
+            TokenKind::String(string) => Ok(Value::String(string)),
+            TokenKind::Symbol('{') => Ok(Value::Block(Block { tag: None, entries: self.entries(Some('}'))? })),
+            TokenKind::Symbol('[') => {
+                let mut values = Vec::new();
+                while !self.is_symbol(']') {
+                    values.push(self.value()?);
+                    if !self.is_symbol(']') {
+                        self.expect_symbol(',')?;
+                    }
+                }
+                self.position += 1;
+                Ok(Value::List(values))
+            }
+            TokenKind::Identifier(identifier) => match identifier.as_str() {
+                "true" => Ok(Value::Bool(true)),
+                "false" => Ok(Value::Bool(false)),
+                "inf" | "NaN" => Ok(Value::Number(identifier)),
+                _ if self.is_symbol('{') => {
+                    self.position += 1;
+                    Ok(Value::Block(Block { tag: Some(identifier), entries: self.entries(Some('}'))? }))
+                }
+                _ => Err(syntax_error(token.line, token.column, format!("unexpected `{identifier}`"))),
+            },
+            TokenKind::Symbol(symbol) => Err(syntax_error(token.line, token.column, format!("unexpected `{symbol}`"))),
+        }
+    }
+}
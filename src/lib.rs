@@ -1,5 +1,18 @@
 pub mod math;
 pub mod raytracing;
+pub mod io;
 pub use raytracing::camera::Camera;
-pub use raytracing::scene::{ Scene, Config };
-pub use raytracing::object;
\ No newline at end of file
+pub use raytracing::camera;
+pub use raytracing::scene::{ Scene, Config, ProgressiveRenderer };
+pub use raytracing::film::Film;
+pub use raytracing::aov::Aov;
+pub use raytracing::object;
+pub use raytracing::bsdf;
+pub use raytracing::light;
+pub use raytracing::motion;
+pub use raytracing::environment;
+pub use raytracing::tone_mapping;
+pub use raytracing::denoise;
+pub use raytracing::adaptive;
+pub use raytracing::sampler;
+pub use raytracing::control;
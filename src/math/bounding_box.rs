@@ -0,0 +1,102 @@
+use crate::math::Vector3;
+
+/// An axis aligned bounding box
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct BoundingBox {
+    /// The corner of the box with the smallest components
+    pub min: Vector3,
+    /// The corner of the box with the largest components
+    pub max: Vector3,
+}
+impl Default for BoundingBox {
+    fn default() -> Self {
+        Self::empty()
+    }
+}
+impl BoundingBox {
+    /// creates the smallest box containing both corners
+    pub fn new(a: Vector3, b: Vector3) -> Self {
+        Self { min: a.min(b), max: a.max(b) }
+    }
+    /// creates a box that contains nothing. Growing it by any point or box results in exactly that point or box.
+    pub const fn empty() -> Self {
+        Self {
+            min: Vector3::const_new(f64::INFINITY, f64::INFINITY, f64::INFINITY),
+            max: Vector3::const_new(f64::NEG_INFINITY, f64::NEG_INFINITY, f64::NEG_INFINITY),
+        }
+    }
+    /// returns whether the box contains no points at all
+    pub fn is_empty(&self) -> bool {
+        self.min.x > self.max.x || self.min.y > self.max.y || self.min.z > self.max.z
+    }
+    /// returns the smallest box containing both boxes
+    pub fn union(&self, other: &BoundingBox) -> Self {
+        Self { min: self.min.min(other.min), max: self.max.max(other.max) }
+    }
+    /// returns the smallest box containing this box and the given point
+    pub fn grow(&self, point: Vector3) -> Self {
+        Self { min: self.min.min(point), max: self.max.max(point) }
+    }
+    /// returns the box enlarged by `amount` in every direction
+    pub fn expand(&self, amount: f64) -> Self {
+        Self { min: self.min - amount, max: self.max + amount }
+    }
+    /// returns the center of the box
+    pub fn center(&self) -> Vector3 {
+        (self.min + self.max) * 0.5
+    }
+    /// returns the size of the box along each axis
+    pub fn extent(&self) -> Vector3 {
+        self.max - self.min
+    }
+    /// returns the surface area of the box. Empty boxes have no surface.
+    pub fn surface_area(&self) -> f64 {
+        if self.is_empty() {
+            return 0.0;
+        }
+        let extent = self.extent();
+        2.0 * (extent.x * extent.y + extent.y * extent.z + extent.z * extent.x)
+    }
+    /// returns the index of the axis along which the box is the largest
+    pub fn largest_axis(&self) -> usize {
+        let extent = self.extent();
+        if extent.x >= extent.y && extent.x >= extent.z {
+            0
+        } else if extent.y >= extent.z {
+            1
+        } else {
+            2
+        }
+    }
+    /// returns whether the point lies inside the box (including its surface)
+    pub fn contains(&self, point: Vector3) -> bool {
+        (self.min.x..=self.max.x).contains(&point.x)
+            && (self.min.y..=self.max.y).contains(&point.y)
+            && (self.min.z..=self.max.z).contains(&point.z)
+    }
+    /// Calculates the distance at which a ray enters the box.
+    ///
+    /// # Arguments
+    ///
+    /// * `ray_position`: The position of the ray.
+    /// * `inverse_direction`: The component-wise inverse of the ray's direction (`1 / direction`).
+    /// * `max_distance`: Hits further away than this are ignored.
+    ///
+    /// returns: Option<f64>
+    ///     The distance at which the ray enters the box, 0 if the ray starts inside the box
+    ///     or [None] if the box isn't hit within `max_distance`.
+    pub fn intersect(&self, ray_position: Vector3, inverse_direction: Vector3, max_distance: f64) -> Option<f64> {
+        let t0 = (self.min - ray_position) * inverse_direction;
+        let t1 = (self.max - ray_position) * inverse_direction;
+        let near = t0.min(t1);
+        let far = t0.max(t1);
+        let t_min = near.x.max(near.y).max(near.z).max(0.0);
+        let t_max = far.x.min(far.y).min(far.z).min(max_distance);
+        // rays lying exactly on a slab produce NaNs (0 * inf), which `min`/`max` skip
+        if t_min <= t_max {
+            Some(t_min)
+        } else {
+            None
+        }
+    }
+}
@@ -4,7 +4,7 @@ use crate::math::mat::Mat3x3;
 impl<T> Add<T> for Mat3x3 where for<'a> &'a Mat3x3: Add<T, Output = Mat3x3> {
     type Output = Mat3x3;
     fn add(self, rhs: T) -> Self::Output {
-        (&self) + rhs
+        Add::add(&self, rhs)
     }
 }
 impl<T: Scalar> Add<T> for &Mat3x3 {
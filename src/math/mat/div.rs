@@ -4,7 +4,7 @@ use std::ops::{Div, DivAssign};
 impl<T> Div<T> for Mat3x3 where for<'a> &'a Mat3x3: Div<T, Output = Mat3x3> {
     type Output = Mat3x3;
     fn div(self, rhs: T) -> Self::Output {
-        (&self) / rhs
+        Div::div(&self, rhs)
     }
 }
 impl<T: Scalar> Div<T> for &Mat3x3 {
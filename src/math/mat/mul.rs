@@ -6,7 +6,7 @@ use crate::math::vector::Vector3;
 impl<I, O> Mul<I> for Mat3x3 where for<'a> &'a Mat3x3: Mul<I, Output = O> {
     type Output = O;
     fn mul(self, rhs: I) -> Self::Output {
-        (&self) * rhs
+        Mul::mul(&self, rhs)
     }
 }
 impl<T: Scalar> Mul<T> for &Mat3x3 {
@@ -52,6 +52,6 @@ impl Mul<&Vector3> for &Mat3x3 {
 impl Mul<Vector3> for &Mat3x3 {
     type Output = Vector3;
     fn mul(self, rhs: Vector3) -> Self::Output {
-        self * &rhs
+        Mul::mul(self, &rhs)
     }
 }
\ No newline at end of file
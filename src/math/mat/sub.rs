@@ -36,7 +36,7 @@ impl Sub<&Mat3x3> for &Mat3x3 {
 impl<T> Sub<T> for Mat3x3 where for<'a> &'a Mat3x3: Sub<T, Output = Mat3x3> {
     type Output = Mat3x3;
     fn sub(self, rhs: T) -> Self::Output {
-        (&self) - rhs
+        Sub::sub(&self, rhs)
     }
 }
 impl<T> SubAssign<T> for Mat3x3 where Mat3x3: Sub<T, Output = Mat3x3> {
@@ -1,5 +1,7 @@
 mod vector;
 mod mat;
 mod general;
+mod bounding_box;
 pub use vector::Vector3;
-pub use mat::Mat3x3;
\ No newline at end of file
+pub use mat::Mat3x3;
+pub use bounding_box::BoundingBox;
\ No newline at end of file
@@ -34,8 +34,12 @@ impl Vector3 {
         }
     }
     pub fn random_direction() -> Self {
-        let z = fastrand::f64() * 2.0 - 1.0;
-        let theta = fastrand::f64() * 2.0 * std::f64::consts::PI;
+        Self::direction_from((fastrand::f64(), fastrand::f64()))
+    }
+    /// Maps two numbers in `[0, 1)` to a direction, evenly spread over the sphere if the numbers are.
+    pub fn direction_from(u: (f64, f64)) -> Self {
+        let z = u.0 * 2.0 - 1.0;
+        let theta = u.1 * 2.0 * std::f64::consts::PI;
         let r = (1.0 - z * z).sqrt();
         Self {
             x: r * theta.cos(),
@@ -72,7 +76,7 @@ impl Vector3 {
         Vector3 { x, y, z }
     }
     /// updates the vector to a new value
-    pub fn update(&mut self, new: Vector3) -> () {
+    pub fn update(&mut self, new: Vector3) {
         self.x = new.x;
         self.y = new.y;
         self.z = new.z;
@@ -105,6 +109,23 @@ impl Vector3 {
     pub fn norm(&self) -> Self {
         self / self.len()
     }
+    /// returns the component-wise minimum of two vectors
+    pub fn min(&self, other: Self) -> Self {
+        Vector3::new(self.x.min(other.x), self.y.min(other.y), self.z.min(other.z))
+    }
+    /// returns the component-wise maximum of two vectors
+    pub fn max(&self, other: Self) -> Self {
+        Vector3::new(self.x.max(other.x), self.y.max(other.y), self.z.max(other.z))
+    }
+    /// returns the component along the given axis (0 = x, 1 = y, 2 = z)
+    pub fn axis(&self, axis: usize) -> f64 {
+        match axis {
+            0 => self.x,
+            1 => self.y,
+            2 => self.z,
+            _ => panic!("invalid axis {axis}"),
+        }
+    }
 }
 impl From<&Vector3> for Vector3 {
     fn from(vector: &Vector3) -> Self {
@@ -26,7 +26,7 @@ impl Add<Vector3> for &Vector3 {
 impl<T> Add<T> for Vector3 where for<'a> &'a Vector3: Add<T, Output = Vector3> {
     type Output = Vector3;
     fn add(self, rhs: T) -> Self::Output {
-        &self + rhs
+        Add::add(&self, rhs)
     }
 }
 impl<T> AddAssign<T> for Vector3 where Vector3: Add<T, Output = Vector3> {
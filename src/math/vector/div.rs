@@ -5,7 +5,7 @@ use std::ops::{Div, DivAssign};
 impl<T> Div<T> for Vector3 where for<'a> &'a Vector3: Div<T, Output = Vector3> {
     type Output = Self;
     fn div(self, other: T) -> Self {
-        (&self) / other
+        Div::div(&self, other)
     }
 }
 impl<T: Scalar> Div<T> for &Vector3 {
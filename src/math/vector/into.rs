@@ -19,9 +19,9 @@ impl<T: Into<f64> + Clone> From<[T; 3]> for Vector3 where {
         Self::new(Into::<f64>::into(array[0].clone()), Into::<f64>::into(array[1].clone()), Into::<f64>::into(array[2].clone()))
     }
 }
-impl<T: From<f64>> Into<[T; 3]> for Vector3 {
-    fn into(self) -> [T; 3] {
-        [T::from(self.x), T::from(self.y), T::from(self.z)]
+impl<T: From<f64>> From<Vector3> for [T; 3] {
+    fn from(vec: Vector3) -> Self {
+        [T::from(vec.x), T::from(vec.y), T::from(vec.z)]
     }
 }
 #[cfg(feature = "gpu")]
@@ -5,7 +5,7 @@ use crate::math::vector::Vector3;
 impl<T> Mul<T> for Vector3 where for<'a> &'a Vector3: Mul<T, Output = Vector3> {
     type Output = Self;
     fn mul(self, scalar: T) -> Self {
-        (&self) * scalar
+        Mul::mul(&self, scalar)
     }
 }
 impl<T: Scalar> Mul<T> for &Vector3 {
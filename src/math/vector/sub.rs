@@ -41,6 +41,6 @@ impl<T> SubAssign<T> for Vector3 where Vector3: Sub<T, Output = Vector3> {
 impl<T> Sub<T> for Vector3 where for<'a> &'a Vector3: Sub<T, Output = Vector3> {
     type Output = Self;
     fn sub(self, scalar: T) -> Self {
-        (&self) - scalar
+        Sub::sub(&self, scalar)
     }
 }
\ No newline at end of file
@@ -0,0 +1,112 @@
+//! Adaptive sampling: pixels stop getting rays once their brightness is known well enough.
+use crate::math::Vector3;
+use crate::raytracing::bsdf::luminance;
+
+/// The settings of adaptive sampling, see [Config::adaptive_sampling](crate::Config::adaptive_sampling).
+///
+/// Every pixel keeps the mean and variance of the brightness of its rays.
+/// Once the 95% confidence interval of the mean is narrower than the threshold, relative to the mean, the pixel gets no more rays.
+/// [Config::rays_per_pixel](crate::Config::rays_per_pixel) is the most rays a pixel can get.
+///
+/// # Examples
+///
+/// ```
+/// use rtx::adaptive::AdaptiveSampling;
+/// use rtx::{Aov, Config, Scene};
+///
+/// let mut scene = Scene::default();
+/// scene.config = Config::default()
+///     .with_rays_per_pixel(256)
+///     .with_adaptive_sampling(Some(AdaptiveSampling::default()))
+///     .with_aovs(vec![Aov::SampleCount]);
+/// let film = scene.render(16, 8);
+/// // the scene is empty, so every pixel stops after the fewest rays
+/// assert_eq!(film.aov(Aov::SampleCount).unwrap().get(0, 0).x, 16.0);
+/// let heatmap = film.aov(Aov::SampleCount).unwrap().heatmap();
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct AdaptiveSampling {
+    /// The largest error that is left in a pixel: half the width of the confidence interval of its brightness, divided by the brightness
+    pub threshold: f64,
+    /// The fewest rays a pixel gets, so it doesn't stop because its first rays happened to agree
+    pub min_samples: usize,
+}
+impl Default for AdaptiveSampling {
+    fn default() -> Self {
+        Self {
+            threshold: 0.05,
+            min_samples: 16,
+        }
+    }
+}
+impl AdaptiveSampling {
+    /// whether the pixel got enough rays
+    pub(crate) fn is_converged(&self, statistics: &PixelStatistics) -> bool {
+        // the variance needs at least two rays
+        if statistics.count < self.min_samples.max(2) {
+            return false;
+        }
+        let error = 1.96 * (statistics.variance() / statistics.count as f64).sqrt();
+        // black pixels without any noise are done too
+        error <= self.threshold * statistics.mean.max(1e-3)
+    }
+}
+/// The mean and variance of the brightness of the rays of a pixel, updated one ray at a time with Welford's algorithm
+#[derive(Clone, Copy, Debug, Default)]
+pub(crate) struct PixelStatistics {
+    count: usize,
+    mean: f64,
+    /// the summed squared differences from the mean
+    m2: f64,
+}
+impl PixelStatistics {
+    pub fn add(&mut self, color: Vector3) {
+        let value = luminance(color);
+        self.count += 1;
+        let delta = value - self.mean;
+        self.mean += delta / self.count as f64;
+        self.m2 += delta * (value - self.mean);
+    }
+    /// the unbiased variance of the brightness of a single ray
+    pub fn variance(&self) -> f64 {
+        match self.count {
+            0 | 1 => 0.0,
+            count => self.m2 / (count - 1) as f64,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn welford_matches_the_two_pass_variance() {
+        let values = [0.5, 2.0, 0.25, 1.0, 4.0, 0.0];
+        let mut statistics = PixelStatistics::default();
+        for value in values {
+            statistics.add(Vector3::ones() * value);
+        }
+        let mean = values.iter().sum::<f64>() / 6.0;
+        let variance = values.iter().map(|value| (value - mean).powi(2)).sum::<f64>() / 5.0;
+        assert!((statistics.mean - mean).abs() < 1e-12);
+        assert!((statistics.variance() - variance).abs() < 1e-12);
+    }
+    #[test]
+    fn noisy_pixels_need_more_rays() {
+        let adaptive = AdaptiveSampling { threshold: 0.05, min_samples: 4 };
+        let (mut flat, mut noisy) = (PixelStatistics::default(), PixelStatistics::default());
+        for i in 0..64 {
+            flat.add(Vector3::ones() * 0.5);
+            noisy.add(Vector3::ones() * (i % 2) as f64);
+            // the minimum holds even without noise
+            assert_eq!(adaptive.is_converged(&flat), i >= 3);
+        }
+        assert!(!adaptive.is_converged(&noisy));
+        // a confidence interval of 1.96 * 0.5 / sqrt(n) is 5% of 0.5 after 1537 rays
+        for i in 64..1600 {
+            noisy.add(Vector3::ones() * (i % 2) as f64);
+        }
+        assert!(adaptive.is_converged(&noisy));
+    }
+}
@@ -0,0 +1,149 @@
+//! Arbitrary output variables: buffers with information about the first hit of the camera rays, for compositing and denoising.
+use crate::math::Vector3;
+
+/// A buffer that can be rendered alongside the color, see [Config::aovs](crate::Config::aovs).
+///
+/// All of them but [Aov::SampleCount] are taken from the first object that the camera rays of a pixel hit
+/// and averaged over the rays that hit something.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Aov {
+    /// The distance along the viewing direction of the camera for [perspective](crate::camera::model::Perspective)
+    /// and [orthographic](crate::camera::model::Orthographic) cameras, and along the ray for the other models like panoramas.
+    /// Infinite if nothing was hit
+    Depth,
+    /// The normal in world space, facing the camera
+    Normal,
+    /// The [base color](crate::object::Material::base_color) of the material
+    Albedo,
+    /// The [emission color](crate::object::Material::emission_color) of the material
+    Emission,
+    /// The index into [Scene::objects](crate::Scene::objects), -1 if nothing was hit
+    ObjectIndex,
+    /// The position in world space
+    Position,
+    /// The number of camera rays of the pixel, which differs between pixels with [adaptive sampling](crate::adaptive).
+    /// See [Film::heatmap](crate::Film::heatmap) to view it
+    SampleCount,
+}
+impl Aov {
+    pub const ALL: [Aov; 7] = [Aov::Depth, Aov::Normal, Aov::Albedo, Aov::Emission, Aov::ObjectIndex, Aov::Position, Aov::SampleCount];
+
+    /// the name in scene files and of the layer in EXR files
+    pub fn name(&self) -> &'static str {
+        match self {
+            Aov::Depth => "depth",
+            Aov::Normal => "normal",
+            Aov::Albedo => "albedo",
+            Aov::Emission => "emission",
+            Aov::ObjectIndex => "object_index",
+            Aov::Position => "position",
+            Aov::SampleCount => "sample_count",
+        }
+    }
+    /// finds the AOV with the given [Aov::name]
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|aov| aov.name() == name)
+    }
+    /// The names of the channels in EXR files.
+    /// Single channel AOVs still fill all three color channels of their [Film](crate::Film), so they can be viewed on their own.
+    pub fn channels(&self) -> &'static [&'static str] {
+        match self {
+            Aov::Depth => &["Z"],
+            Aov::Normal | Aov::Position => &["X", "Y", "Z"],
+            Aov::Albedo | Aov::Emission => &["R", "G", "B"],
+            Aov::ObjectIndex => &["id"],
+            Aov::SampleCount => &["count"],
+        }
+    }
+    /// whether the AOV comes from the first hits, the others are filled in by the renderer
+    pub(crate) fn is_first_hit(&self) -> bool {
+        *self != Aov::SampleCount
+    }
+}
+/// The first hit of a camera ray
+pub(crate) struct FirstHit {
+    pub depth: f64,
+    pub normal: Vector3,
+    pub albedo: Vector3,
+    pub emission: Vector3,
+    pub object: usize,
+    pub position: Vector3,
+}
+/// Sums up the first hits of the camera rays of a pixel
+#[derive(Default)]
+pub(crate) struct AovAccumulator {
+    hits: usize,
+    depth: f64,
+    normal: Vector3,
+    albedo: Vector3,
+    emission: Vector3,
+    /// the object of the first ray that hit something
+    object: Option<usize>,
+    position: Vector3,
+}
+impl AovAccumulator {
+    pub fn add(&mut self, hit: Option<FirstHit>) {
+        let Some(hit) = hit else {
+            return;
+        };
+        self.hits += 1;
+        self.depth += hit.depth;
+        self.normal += hit.normal;
+        self.albedo += hit.albedo;
+        self.emission += hit.emission;
+        self.object.get_or_insert(hit.object);
+        self.position += hit.position;
+    }
+    /// the averaged value of an AOV, single values are repeated in all three components
+    pub fn value(&self, aov: Aov) -> Vector3 {
+        if self.hits == 0 {
+            return match aov {
+                Aov::Depth => Vector3::ones() * f64::INFINITY,
+                Aov::ObjectIndex => -Vector3::ones(),
+                _ => Vector3::zeros(),
+            };
+        }
+        let hits = self.hits as f64;
+        match aov {
+            Aov::Depth => Vector3::ones() * (self.depth / hits),
+            // averaging shortens normals at edges
+            Aov::Normal if self.normal != Vector3::zeros() => self.normal.norm(),
+            Aov::Normal => Vector3::zeros(),
+            Aov::Albedo => self.albedo / hits,
+            Aov::Emission => self.emission / hits,
+            Aov::ObjectIndex => Vector3::ones() * self.object.map_or(-1.0, |object| object as f64),
+            Aov::Position => self.position / hits,
+            // the accumulator only sees the rays with first hits, the renderer counts them all
+            Aov::SampleCount => Vector3::zeros(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn misses_are_ignored_when_averaging() {
+        let hit = |depth: f64, object| Some(FirstHit {
+            depth,
+            normal: Vector3::z() * depth,
+            albedo: Vector3::ones(),
+            emission: Vector3::zeros(),
+            object,
+            position: Vector3::x() * depth,
+        });
+        let mut accumulator = AovAccumulator::default();
+        assert_eq!(accumulator.value(Aov::Depth).x, f64::INFINITY);
+        assert_eq!(accumulator.value(Aov::ObjectIndex).x, -1.0);
+        accumulator.add(hit(1.0, 3));
+        accumulator.add(None);
+        accumulator.add(hit(3.0, 5));
+        assert_eq!(accumulator.value(Aov::Depth), Vector3::ones() * 2.0);
+        assert_eq!(accumulator.value(Aov::Normal), Vector3::z());
+        assert_eq!(accumulator.value(Aov::Albedo), Vector3::ones());
+        assert_eq!(accumulator.value(Aov::ObjectIndex), Vector3::ones() * 3.0);
+        assert_eq!(accumulator.value(Aov::Position), Vector3::x() * 2.0);
+        assert!(Aov::ALL.iter().all(|aov| Aov::from_name(aov.name()) == Some(*aov)));
+    }
+}
@@ -0,0 +1,234 @@
+//! Scattering functions that describe how light is reflected by a surface.
+//!
+//! All directions point away from the surface: `outgoing` towards where the light goes (the previous hit or the camera),
+//! `incoming` towards where it comes from. The normal is always on the side of `outgoing`.
+use crate::math::Vector3;
+use std::f64::consts::PI;
+use std::fmt::Debug;
+
+/// The smallest GGX alpha that is used, to keep the distribution finite for perfectly smooth surfaces.
+const MIN_ALPHA: f64 = 1e-3;
+/// The reflectance at normal incidence of non-metals
+const DIELECTRIC_F0: f64 = 0.04;
+
+/// A direction that was sampled from a [Bsdf]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct BsdfSample {
+    /// The sampled incoming direction
+    pub direction: Vector3,
+    /// `eval * cos(normal, direction) / pdf`, the factor the light along the sampled direction gets multiplied with
+    pub weight: Vector3,
+    /// The probability density of sampling the direction, in solid angle
+    pub pdf: f64,
+}
+
+/// A bidirectional scattering distribution function.
+///
+/// # Examples
+///
+/// ```
+/// use rtx::bsdf::{Bsdf, Microfacet};
+/// use rtx::math::Vector3;
+///
+/// let gold = Microfacet::new(Vector3::new(1.0, 0.78, 0.34), 0.3, 1.0);
+/// let outgoing = Vector3::new(1, 0, 1).norm();
+/// if let Some(sample) = gold.sample(outgoing, Vector3::z(), (0.3, 0.7)) {
+///     assert!(sample.pdf > 0.0);
+///     assert_eq!(sample.pdf, gold.pdf(outgoing, sample.direction, Vector3::z()));
+/// }
+/// ```
+pub trait Bsdf: Debug + Send + Sync {
+    /// Samples an incoming direction.
+    ///
+    /// # Arguments
+    ///
+    /// * `outgoing`: The normalized direction the light leaves the surface in.
+    /// * `normal`: The normalized surface normal, on the side of `outgoing`.
+    /// * `u`: Two uniformly distributed random numbers in `[0, 1)`.
+    ///
+    /// returns: Option<BsdfSample>
+    ///     [None] if no direction could be sampled (e.g. it would go below the surface).
+    fn sample(&self, outgoing: Vector3, normal: Vector3, u: (f64, f64)) -> Option<BsdfSample>;
+    /// Calculates how much of the light coming from `incoming` is scattered towards `outgoing`, per color channel.
+    ///
+    /// The result doesn't contain the cosine of `incoming` and the normal.
+    fn eval(&self, outgoing: Vector3, incoming: Vector3, normal: Vector3) -> Vector3;
+    /// Calculates the probability density of [Bsdf::sample] returning `incoming`, in solid angle.
+    fn pdf(&self, outgoing: Vector3, incoming: Vector3, normal: Vector3) -> f64;
+}
+
+/// A rough surface made of tiny mirrors, with a GGX (Trowbridge-Reitz) distribution, Smith shadowing and Schlick Fresnel.
+///
+/// Non-metals add a diffuse lobe below the specular one. Metals tint their reflection with the base color.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Microfacet {
+    /// The diffuse color of non-metals or the reflectance of metals
+    pub base_color: Vector3,
+    /// The perceived roughness, from 0 (polished) to 1 (matte)
+    pub roughness: f64,
+    /// How metallic the surface is, from 0 (plastic, stone, ...) to 1 (metal)
+    pub metallic: f64,
+}
+impl Microfacet {
+    /// creates a new microfacet bsdf
+    pub const fn new(base_color: Vector3, roughness: f64, metallic: f64) -> Self {
+        Self { base_color, roughness, metallic }
+    }
+    fn alpha(&self) -> f64 {
+        (self.roughness * self.roughness).max(MIN_ALPHA)
+    }
+    /// the reflectance at normal incidence
+    fn f0(&self) -> Vector3 {
+        Vector3::ones() * DIELECTRIC_F0 * (1.0 - self.metallic) + self.base_color * self.metallic
+    }
+    fn diffuse_color(&self) -> Vector3 {
+        self.base_color * (1.0 - self.metallic)
+    }
+    /// the probability of sampling the specular lobe instead of the diffuse one
+    fn specular_probability(&self, cos_outgoing: f64) -> f64 {
+        let specular = luminance(schlick(self.f0(), cos_outgoing));
+        let diffuse = luminance(self.diffuse_color());
+        if specular + diffuse <= 0.0 {
+            return 0.5;
+        }
+        specular / (specular + diffuse)
+    }
+    /// the GGX normal distribution
+    fn distribution(&self, cos_half: f64) -> f64 {
+        let alpha2 = self.alpha() * self.alpha();
+        let denominator = (alpha2 - 1.0) * cos_half * cos_half + 1.0;
+        alpha2 / (PI * denominator * denominator)
+    }
+    /// Smith's masking function for one direction
+    fn masking(&self, cos: f64) -> f64 {
+        let alpha2 = self.alpha() * self.alpha();
+        2.0 * cos / (cos + (alpha2 + (1.0 - alpha2) * cos * cos).sqrt())
+    }
+}
+impl Bsdf for Microfacet {
+    fn sample(&self, outgoing: Vector3, normal: Vector3, u: (f64, f64)) -> Option<BsdfSample> {
+        let cos_outgoing = outgoing.dot(normal);
+        if cos_outgoing <= 0.0 {
+            return None;
+        }
+        let (tangent, bitangent) = orthonormal_basis(normal);
+        let specular_probability = self.specular_probability(cos_outgoing);
+        let incoming = if u.0 < specular_probability {
+            // sample the half vector proportional to D(h) * cos(h)
+            let u0 = u.0 / specular_probability;
+            let alpha2 = self.alpha() * self.alpha();
+            let cos_theta = ((1.0 - u0) / (1.0 + (alpha2 - 1.0) * u0)).sqrt();
+            let sin_theta = (1.0 - cos_theta * cos_theta).max(0.0).sqrt();
+            let phi = 2.0 * PI * u.1;
+            let half = tangent * (sin_theta * phi.cos()) + bitangent * (sin_theta * phi.sin()) + normal * cos_theta;
+            half * 2 * outgoing.dot(half) - outgoing
+        } else {
+            // cosine weighted hemisphere
+            let u0 = (u.0 - specular_probability) / (1.0 - specular_probability);
+            let radius = u0.sqrt();
+            let phi = 2.0 * PI * u.1;
+            tangent * (radius * phi.cos()) + bitangent * (radius * phi.sin()) + normal * (1.0 - u0).max(0.0).sqrt()
+        };
+        let cos_incoming = incoming.dot(normal);
+        if cos_incoming <= 0.0 {
+            return None;
+        }
+        let pdf = self.pdf(outgoing, incoming, normal);
+        if pdf <= 0.0 {
+            return None;
+        }
+        Some(BsdfSample {
+            direction: incoming,
+            weight: self.eval(outgoing, incoming, normal) * cos_incoming / pdf,
+            pdf,
+        })
+    }
+    fn eval(&self, outgoing: Vector3, incoming: Vector3, normal: Vector3) -> Vector3 {
+        let cos_outgoing = outgoing.dot(normal);
+        let cos_incoming = incoming.dot(normal);
+        if cos_outgoing <= 0.0 || cos_incoming <= 0.0 {
+            return Vector3::zeros();
+        }
+        let half = (outgoing + incoming).norm();
+        let fresnel = schlick(self.f0(), outgoing.dot(half));
+        let specular = fresnel * (self.distribution(half.dot(normal)) * self.masking(cos_outgoing) * self.masking(cos_incoming)
+            / (4.0 * cos_outgoing * cos_incoming));
+        let diffuse = self.diffuse_color() * (Vector3::ones() - fresnel) / PI;
+        specular + diffuse
+    }
+    fn pdf(&self, outgoing: Vector3, incoming: Vector3, normal: Vector3) -> f64 {
+        let cos_outgoing = outgoing.dot(normal);
+        let cos_incoming = incoming.dot(normal);
+        if cos_outgoing <= 0.0 || cos_incoming <= 0.0 {
+            return 0.0;
+        }
+        let half = (outgoing + incoming).norm();
+        let specular_probability = self.specular_probability(cos_outgoing);
+        let specular = self.distribution(half.dot(normal)) * half.dot(normal) / (4.0 * outgoing.dot(half));
+        let diffuse = cos_incoming / PI;
+        specular_probability * specular + (1.0 - specular_probability) * diffuse
+    }
+}
+
+/// Schlick's approximation of the Fresnel reflectance
+fn schlick(f0: Vector3, cos: f64) -> Vector3 {
+    f0 + (Vector3::ones() - f0) * (1.0 - cos).clamp(0.0, 1.0).powi(5)
+}
+/// the perceived brightness of a color
+pub(crate) fn luminance(color: Vector3) -> f64 {
+    color.dot(Vector3::new(0.2126, 0.7152, 0.0722))
+}
+/// Builds two vectors that form an orthonormal basis with the normal (Duff et al. 2017).
+pub(crate) fn orthonormal_basis(normal: Vector3) -> (Vector3, Vector3) {
+    let sign = 1f64.copysign(normal.z);
+    let a = -1.0 / (sign + normal.z);
+    let b = normal.x * normal.y * a;
+    (
+        Vector3::new(1.0 + sign * normal.x * normal.x * a, sign * b, -sign * normal.x),
+        Vector3::new(b, sign + normal.y * normal.y * a, -normal.y),
+    )
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// estimates the fraction of light that is reflected in total, by sampling with a fixed seed
+    fn albedo(bsdf: &impl Bsdf, outgoing: Vector3) -> Vector3 {
+        let count = 20_000;
+        let mut rng = fastrand::Rng::with_seed(3);
+        (0..count)
+            .filter_map(|_| bsdf.sample(outgoing, Vector3::z(), (rng.f64(), rng.f64())))
+            .fold(Vector3::zeros(), |acc, sample| acc + sample.weight)
+            / count
+    }
+    #[test]
+    fn energy_is_conserved() {
+        for roughness in [0.1, 0.5, 1.0] {
+            for metallic in [0.0, 1.0] {
+                let bsdf = Microfacet::new(Vector3::ones(), roughness, metallic);
+                let albedo = albedo(&bsdf, Vector3::new(0.5, 0.0, 1.0).norm());
+                assert!(albedo.x <= 1.02, "roughness {roughness}, metallic {metallic}: {albedo}");
+            }
+        }
+    }
+    #[test]
+    fn sample_pdf_matches_pdf() {
+        let bsdf = Microfacet::new(Vector3::new(0.8, 0.3, 0.1), 0.4, 0.5);
+        let outgoing = Vector3::new(0.3, -0.2, 1.0).norm();
+        let mut rng = fastrand::Rng::with_seed(5);
+        for _ in 0..100 {
+            if let Some(sample) = bsdf.sample(outgoing, Vector3::z(), (rng.f64(), rng.f64())) {
+                assert!((sample.pdf - bsdf.pdf(outgoing, sample.direction, Vector3::z())).abs() < 1e-9);
+                assert!((sample.direction.len() - 1.0).abs() < 1e-9);
+            }
+        }
+    }
+    #[test]
+    fn basis_is_orthonormal() {
+        for normal in [Vector3::z(), -Vector3::z(), Vector3::new(1, 2, 3).norm(), Vector3::new(-0.3, 0.1, -0.9).norm()] {
+            let (t, b) = orthonormal_basis(normal);
+            assert!(t.dot(normal).abs() < 1e-12 && b.dot(normal).abs() < 1e-12 && t.dot(b).abs() < 1e-12);
+            assert!((t.len() - 1.0).abs() < 1e-12 && (b.len() - 1.0).abs() < 1e-12);
+        }
+    }
+}
@@ -0,0 +1,299 @@
+use crate::math::{BoundingBox, Vector3};
+
+/// The number of buckets the centroids are sorted into when searching for the best split.
+const SAH_BINS: usize = 12;
+/// Leaves with at most this many primitives are never split.
+const MAX_LEAF_SIZE: usize = 4;
+/// The cost of traversing a node relative to intersecting a single primitive.
+const TRAVERSAL_COST: f64 = 1.0;
+/// The deepest level of a node, with the root at level 0. Deeper nodes are kept as leaves.
+///
+/// The gpu traverses meshes with a stack of 64 nodes, that holds at most one node per level above
+/// the current one and the two children of the current one. Limiting the depth keeps it from overflowing,
+/// so both renderers always traverse the same tree.
+pub(crate) const MAX_DEPTH: usize = 62;
+
+/// A node of a [Bvh].
+///
+/// Nodes are stored in a flat list. Interior nodes have `count == 0` and their children at
+/// `first` and `first + 1`. Leaves reference `count` primitives starting at `first` in [Bvh::indices].
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct Node {
+    pub bounds: BoundingBox,
+    pub first: usize,
+    pub count: usize,
+}
+impl Node {
+    pub fn is_leaf(&self) -> bool {
+        self.count > 0
+    }
+}
+
+/// A bounding volume hierarchy over a list of primitives, built with the surface area heuristic.
+///
+/// The hierarchy only knows about the bounding boxes of the primitives and refers to them by their index.
+/// Intersecting the primitives themselves is left to the caller.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct Bvh {
+    pub nodes: Vec<Node>,
+    pub indices: Vec<usize>,
+}
+impl Bvh {
+    /// Builds a new hierarchy over primitives with the given bounding boxes.
+    pub fn build(bounds: &[BoundingBox]) -> Self {
+        let mut bvh = Self {
+            nodes: Vec::with_capacity(bounds.len() * 2),
+            indices: (0..bounds.len()).collect(),
+        };
+        if bounds.is_empty() {
+            return bvh;
+        }
+        let centroids = bounds.iter().map(BoundingBox::center).collect::<Vec<_>>();
+        bvh.nodes.push(Node { bounds: BoundingBox::empty(), first: 0, count: bounds.len() });
+        bvh.subdivide(0, 0, bounds, &centroids);
+        bvh
+    }
+    /// Updates the bounds of all nodes to new primitive bounds without changing the structure of the tree.
+    ///
+    /// This is a lot cheaper than rebuilding but the tree gets worse, the further the primitives moved.
+    /// `bounds` has to contain as many boxes as the hierarchy was built with.
+    pub fn refit(&mut self, bounds: &[BoundingBox]) {
+        assert_eq!(bounds.len(), self.indices.len(), "refitting requires the same number of primitives");
+        // children are always stored after their parents
+        for i in (0..self.nodes.len()).rev() {
+            let node = self.nodes[i];
+            self.nodes[i].bounds = if node.is_leaf() {
+                self.leaf_bounds(&node, bounds)
+            } else {
+                self.nodes[node.first].bounds.union(&self.nodes[node.first + 1].bounds)
+            };
+        }
+    }
+    /// Finds the closest primitive hit by a ray.
+    ///
+    /// # Arguments
+    ///
+    /// * `ray_position`: The position of the ray.
+    /// * `ray_direction`: The direction of the ray.
+    /// * `intersect`: Calculates the distance to the primitive with the given index, if it is hit.
+    ///
+    /// returns: Option<(f64, usize)>
+    ///     The distance to and the index of the closest hit primitive.
+    pub fn closest_hit(&self, ray_position: Vector3, ray_direction: Vector3, mut intersect: impl FnMut(usize) -> Option<f64>) -> Option<(f64, usize)> {
+        let root = self.nodes.first()?;
+        let inverse_direction = Vector3::ones() / ray_direction;
+        root.bounds.intersect(ray_position, inverse_direction, f64::INFINITY)?;
+        let mut closest: Option<(f64, usize)> = None;
+        let max_distance = |closest: &Option<(f64, usize)>| closest.map_or(f64::INFINITY, |(dst, _)| dst);
+        let mut stack = vec![0];
+        while let Some(index) = stack.pop() {
+            let node = &self.nodes[index];
+            if node.is_leaf() {
+                for &primitive in &self.indices[node.first..node.first + node.count] {
+                    if let Some(dst) = intersect(primitive) {
+                        if dst < max_distance(&closest) {
+                            closest = Some((dst, primitive));
+                        }
+                    }
+                }
+                continue;
+            }
+            let limit = max_distance(&closest);
+            let left = self.nodes[node.first].bounds.intersect(ray_position, inverse_direction, limit);
+            let right = self.nodes[node.first + 1].bounds.intersect(ray_position, inverse_direction, limit);
+            // push the farther child first, so the closer one gets visited first
+            match (left, right) {
+                (Some(l), Some(r)) if l <= r => stack.extend([node.first + 1, node.first]),
+                (Some(_), Some(_)) => stack.extend([node.first, node.first + 1]),
+                (Some(_), None) => stack.push(node.first),
+                (None, Some(_)) => stack.push(node.first + 1),
+                (None, None) => {}
+            }
+        }
+        closest
+    }
+    /// Calls `visit` for every primitive in a leaf whose bounds contain the given point.
+    ///
+    /// # Arguments
+    ///
+    /// * `point`: The point to search for.
+    /// * `tolerance`: How far outside the bounds of a node the point may be and still be considered inside.
+    /// * `visit`: Gets called with the index of each primitive.
+    pub fn visit_containing(&self, point: Vector3, tolerance: f64, mut visit: impl FnMut(usize)) {
+        if self.nodes.is_empty() {
+            return;
+        }
+        let mut stack = vec![0];
+        while let Some(index) = stack.pop() {
+            let node = &self.nodes[index];
+            if !node.bounds.expand(tolerance).contains(point) {
+                continue;
+            }
+            if node.is_leaf() {
+                self.indices[node.first..node.first + node.count].iter().for_each(|&i| visit(i));
+            } else {
+                stack.extend([node.first, node.first + 1]);
+            }
+        }
+    }
+    /// returns the bounds of everything in the hierarchy
+    pub fn bounds(&self) -> BoundingBox {
+        self.nodes.first().map_or(BoundingBox::empty(), |root| root.bounds)
+    }
+}
+impl Bvh {
+    fn leaf_bounds(&self, node: &Node, bounds: &[BoundingBox]) -> BoundingBox {
+        self.indices[node.first..node.first + node.count].iter()
+            .fold(BoundingBox::empty(), |acc, &i| acc.union(&bounds[i]))
+    }
+    fn subdivide(&mut self, node_index: usize, depth: usize, bounds: &[BoundingBox], centroids: &[Vector3]) {
+        self.nodes[node_index].bounds = self.leaf_bounds(&self.nodes[node_index], bounds);
+        let node = self.nodes[node_index];
+        if node.count <= MAX_LEAF_SIZE || depth >= MAX_DEPTH {
+            return;
+        }
+        let Some((axis, split)) = self.find_split(&node, bounds, centroids) else {
+            return;
+        };
+        // partition the primitives of this node around the split plane
+        let range = &mut self.indices[node.first..node.first + node.count];
+        let mut left_count = 0;
+        for i in 0..range.len() {
+            if centroids[range[i]].axis(axis) < split {
+                range.swap(i, left_count);
+                left_count += 1;
+            }
+        }
+        if left_count == 0 || left_count == node.count {
+            return;
+        }
+        let left = self.nodes.len();
+        self.nodes.push(Node { bounds: BoundingBox::empty(), first: node.first, count: left_count });
+        self.nodes.push(Node { bounds: BoundingBox::empty(), first: node.first + left_count, count: node.count - left_count });
+        self.nodes[node_index].first = left;
+        self.nodes[node_index].count = 0;
+        self.subdivide(left, depth + 1, bounds, centroids);
+        self.subdivide(left + 1, depth + 1, bounds, centroids);
+    }
+    /// Searches for the split plane with the lowest surface area heuristic cost by binning the centroids.
+    ///
+    /// returns: Option<(axis, position)>
+    ///     [None] if no split is cheaper than keeping the node as a leaf.
+    fn find_split(&self, node: &Node, bounds: &[BoundingBox], centroids: &[Vector3]) -> Option<(usize, f64)> {
+        let primitives = &self.indices[node.first..node.first + node.count];
+        let centroid_bounds = primitives.iter()
+            .fold(BoundingBox::empty(), |acc, &i| acc.grow(centroids[i]));
+        let leaf_cost = node.count as f64;
+        let parent_area = node.bounds.surface_area();
+        if parent_area <= 0.0 {
+            return None;
+        }
+        let mut best: Option<(f64, usize, f64)> = None;
+        for axis in 0..3 {
+            let min = centroid_bounds.min.axis(axis);
+            let max = centroid_bounds.max.axis(axis);
+            if max <= min {
+                continue;
+            }
+            let scale = SAH_BINS as f64 / (max - min);
+            let bin_of = |i: usize| (((centroids[i].axis(axis) - min) * scale) as usize).min(SAH_BINS - 1);
+            let mut bins = [(BoundingBox::empty(), 0usize); SAH_BINS];
+            for &i in primitives {
+                let bin = &mut bins[bin_of(i)];
+                bin.0 = bin.0.union(&bounds[i]);
+                bin.1 += 1;
+            }
+            // sweep from the right to know the cost of everything right of each plane
+            let mut right_area = [0.0; SAH_BINS];
+            let mut right_count = [0; SAH_BINS];
+            let mut acc = (BoundingBox::empty(), 0);
+            for b in (1..SAH_BINS).rev() {
+                acc = (acc.0.union(&bins[b].0), acc.1 + bins[b].1);
+                right_area[b] = acc.0.surface_area();
+                right_count[b] = acc.1;
+            }
+            let mut left = (BoundingBox::empty(), 0);
+            for b in 1..SAH_BINS {
+                left = (left.0.union(&bins[b - 1].0), left.1 + bins[b - 1].1);
+                if left.1 == 0 || right_count[b] == 0 {
+                    continue;
+                }
+                let cost = TRAVERSAL_COST
+                    + (left.0.surface_area() * left.1 as f64 + right_area[b] * right_count[b] as f64) / parent_area;
+                if best.is_none_or(|(best_cost, _, _)| cost < best_cost) {
+                    best = Some((cost, axis, min + b as f64 / scale));
+                }
+            }
+        }
+        best.filter(|(cost, _, _)| *cost < leaf_cost)
+            .map(|(_, axis, split)| (axis, split))
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn boxes(count: usize) -> Vec<BoundingBox> {
+        (0..count)
+            .map(|i| {
+                let center = Vector3::new((i * 7 % 13) as f64, (i * 3 % 5) as f64, i as f64);
+                BoundingBox::new(center - 0.25, center + 0.25)
+            })
+            .collect()
+    }
+    #[test]
+    fn every_primitive_is_referenced_once() {
+        let bvh = Bvh::build(&boxes(100));
+        let mut seen = vec![0; 100];
+        bvh.nodes.iter()
+            .filter(|n| n.is_leaf())
+            .for_each(|n| bvh.indices[n.first..n.first + n.count].iter().for_each(|&i| seen[i] += 1));
+        assert!(seen.iter().all(|&count| count == 1));
+    }
+    #[test]
+    fn depth_is_limited() {
+        // exponentially spaced boxes make the surface area heuristic split off a few boxes at a time
+        let count = 300;
+        let bounds = (0..count)
+            .map(|i| 2f64.powi(i))
+            .map(|x| BoundingBox::new(Vector3::new(x, 0, 0), Vector3::new(x * 1.1, 1, 1)))
+            .collect::<Vec<_>>();
+        let bvh = Bvh::build(&bounds);
+        let mut depths = vec![0; bvh.nodes.len()];
+        for (i, node) in bvh.nodes.iter().enumerate().filter(|(_, n)| !n.is_leaf()) {
+            depths[node.first] = depths[i] + 1;
+            depths[node.first + 1] = depths[i] + 1;
+        }
+        assert_eq!(depths.iter().max(), Some(&MAX_DEPTH));
+        let referenced = bvh.nodes.iter().filter(|n| n.is_leaf()).map(|n| n.count).sum::<usize>();
+        assert_eq!(referenced, count as usize);
+    }
+    #[test]
+    fn finds_same_hit_as_brute_force() {
+        let bounds = boxes(100);
+        let bvh = Bvh::build(&bounds);
+        for i in 0..50 {
+            let origin = Vector3::new(-5.0, (i % 5) as f64, i as f64 * 2.0);
+            let direction = Vector3::new(1.0, 0.01 * i as f64, 0.3).norm();
+            let intersect = |p: usize| bounds[p].intersect(origin, Vector3::ones() / direction, f64::INFINITY)
+                .filter(|dst| *dst > 0.0);
+            let expected = (0..bounds.len())
+                .filter_map(|p| intersect(p).map(|d| (d, p)))
+                .min_by(|a, b| a.0.total_cmp(&b.0))
+                .map(|(d, _)| d);
+            let actual = bvh.closest_hit(origin, direction, intersect).map(|(d, _)| d);
+            assert_eq!(actual, expected);
+        }
+    }
+    #[test]
+    fn refit_covers_moved_primitives() {
+        let bounds = boxes(50);
+        let mut bvh = Bvh::build(&bounds);
+        let moved = bounds.iter()
+            .map(|b| BoundingBox::new(b.min + 10.0, b.max + 10.0))
+            .collect::<Vec<_>>();
+        bvh.refit(&moved);
+        let root = bvh.bounds();
+        assert!(moved.iter().all(|b| root.contains(b.min) && root.contains(b.max)));
+    }
+}
@@ -1,31 +1,159 @@
+pub mod lens;
+pub mod model;
+pub mod stereo;
+
 use crate::math::{Mat3x3, Vector3};
+use crate::raytracing::motion::Motion;
+use crate::raytracing::ray::Ray;
+use lens::ThinLens;
+use model::{CameraModel, Perspective};
+use std::sync::Arc;
+#[cfg(feature = "gpu")]
+use lens::Aperture;
 #[cfg(feature = "gpu")]
 use crate::raytracing::gpu::GpuSerialize;
 
+/// Which extent of the image the [Camera::fov] spans
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum FovAxis {
+    /// from the left edge of the image to the right one
+    #[default]
+    Horizontal,
+    /// from the bottom edge of the image to the top one
+    Vertical,
+    /// from one corner of the image to the opposite one
+    Diagonal,
+}
+impl FovAxis {
+    pub const ALL: [FovAxis; 3] = [FovAxis::Horizontal, FovAxis::Vertical, FovAxis::Diagonal];
+
+    /// the name in scene files
+    pub fn name(&self) -> &'static str {
+        match self {
+            FovAxis::Horizontal => "horizontal",
+            FovAxis::Vertical => "vertical",
+            FovAxis::Diagonal => "diagonal",
+        }
+    }
+    /// finds the axis with the given [FovAxis::name]
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|axis| axis.name() == name)
+    }
+}
+
 #[derive(Debug, Clone)]
-/// represents a camera in the scene
+/// A camera that sees the scene through its [model](Camera::model), by default a pinhole with a rectilinear projection,
+/// so straight lines stay straight.
+///
+/// Camera space has the x-axis to the right of the image, the y-axis to its top and the z-axis in the viewing direction.
+///
+/// # Examples
+///
+/// ```
+/// use rtx::Camera;
+/// use rtx::camera::FovAxis;
+///
+/// // looking down at the origin, with the top of the image towards +y
+/// let camera = Camera::look_at((0, 0, 5).into(), (0, 0, 0).into(), (0, 1, 0).into(), 60f64.to_radians())
+///     .with_fov_axis(FovAxis::Vertical);
+/// assert!((camera.ray_direction((0.5, 0.5), 1.5) + rtx::math::Vector3::z()).len() < 1e-12);
+/// ```
 pub struct Camera {
-    /// The _horizontal_ fov of the camera in radians.
+    /// The fov of the camera in radians, along the [Camera::fov_axis].
     pub fov: f64,
+    /// Which extent of the image the fov spans
+    pub fov_axis: FovAxis,
+    /// The lens that blurs what is out of focus, without one everything is sharp.
+    /// Setting it directly keeps the fov, unlike [Camera::with_lens].
+    pub lens: Option<ThinLens>,
+    /// The projection from the image to the rays, the fov and lens are only used by the [Perspective] one
+    pub model: Arc<dyn CameraModel>,
     /// The position of the camera in the world
     pub position: Vector3,
+    /// Moves the camera while the shutter is open, see [Config::shutter](crate::Config::shutter).
+    /// Unlike the motion of [objects](crate::object::Object::motion), the keyframes are relative to the camera:
+    /// the translations move it away from [Camera::position] and the rotations turn it around that point.
+    /// Only the cpu renderer supports motion.
+    pub motion: Option<Motion>,
+    /// The point that the rotations of the [Camera::motion] turn the camera around, [None] for its position.
+    /// The eyes of a [stereo rig](stereo::StereoRig) turn around the camera of the rig.
+    motion_center: Option<Vector3>,
     direction: Vector3,
+    up: Vector3,
+    roll: f64,
     to_cam_space: Mat3x3,
     to_world_space: Mat3x3,
 }
 
 impl Camera {
-    /// creates a new camera to be used for renders
+    /// Creates a new camera to be used for renders, with +z as the up direction.
+    ///
+    /// # Arguments
+    ///
+    /// * `position`: Where the camera is
+    /// * `direction`: Where it looks, doesn't have to be normalized
+    /// * `fov`: The horizontal fov in radians
+    ///
+    /// returns: Camera
     pub fn new(position: Vector3, direction: Vector3, fov: f64) -> Self {
-        let to_world_space = Self::derive_to_world_space_mat(direction);
+        Self::with_orientation(position, direction, Vector3::z(), 0.0, fov)
+    }
+    /// Creates a camera at `eye` that looks at `target`.
+    ///
+    /// # Arguments
+    ///
+    /// * `eye`: Where the camera is
+    /// * `target`: The point in the center of the image
+    /// * `up`: The direction that points to the top of the image, as far as it can while the camera looks at the target
+    /// * `fov`: The horizontal fov in radians
+    ///
+    /// returns: Camera
+    pub fn look_at(eye: Vector3, target: Vector3, up: Vector3, fov: f64) -> Self {
+        Self::with_orientation(eye, target - eye, up, 0.0, fov)
+    }
+    fn with_orientation(position: Vector3, direction: Vector3, up: Vector3, roll: f64, fov: f64) -> Self {
+        let to_world_space = Self::derive_to_world_space_mat(direction, up, roll);
         Camera {
             fov,
+            fov_axis: FovAxis::default(),
+            lens: None,
+            model: Arc::new(Perspective),
             position,
+            motion: None,
+            motion_center: None,
             direction,
+            up,
+            roll,
             to_cam_space: to_world_space.inverse(),
             to_world_space,
         }
     }
+    /// turns the camera around its direction, see [Camera::set_roll]
+    pub fn with_roll(mut self, roll: f64) -> Self {
+        self.set_roll(roll);
+        self
+    }
+    pub fn with_fov_axis(mut self, fov_axis: FovAxis) -> Self {
+        self.fov_axis = fov_axis;
+        self
+    }
+    /// puts a lens on the camera, the horizontal fov becomes the one of its sensor and focal length
+    pub fn with_lens(mut self, lens: ThinLens) -> Self {
+        self.fov = lens.fov();
+        self.fov_axis = FovAxis::Horizontal;
+        self.lens = Some(lens);
+        self
+    }
+    /// changes the projection, e.g. to an [orthographic](model::Orthographic) or a [fisheye](model::Fisheye) one
+    pub fn with_model(mut self, model: impl CameraModel) -> Self {
+        self.model = Arc::new(model);
+        self
+    }
+    /// moves the camera with the given [Camera::motion]
+    pub fn with_motion(mut self, motion: Motion) -> Self {
+        self.motion = Some(motion);
+        self
+    }
     /// returns the direction the camera is facing.
     pub fn get_direction(&self) -> Vector3 {
         self.direction
@@ -33,19 +161,131 @@ impl Camera {
     /// sets the direction the camera is facing.
     /// This has to be a separate function, because the matrices have to be updated.
     pub fn set_direction(&mut self, direction: Vector3) {
-        let to_world_space = Self::derive_to_world_space_mat(self.direction);
+        self.direction = direction;
+        self.update_matrices();
+    }
+    /// returns the direction that the top of the image points to, before the roll
+    pub fn get_up(&self) -> Vector3 {
+        self.up
+    }
+    /// Sets the direction that the top of the image points to, as far as it can while the camera keeps its direction.
+    /// If the camera looks along it, another axis is used instead.
+    pub fn set_up(&mut self, up: Vector3) {
+        self.up = up;
+        self.update_matrices();
+    }
+    /// returns the roll in radians
+    pub fn get_roll(&self) -> f64 {
+        self.roll
+    }
+    /// Turns the camera counterclockwise around its direction, by the given angle in radians, so the image turns clockwise.
+    pub fn set_roll(&mut self, roll: f64) {
+        self.roll = roll;
+        self.update_matrices();
+    }
+    fn update_matrices(&mut self) {
+        let to_world_space = Self::derive_to_world_space_mat(self.direction, self.up, self.roll);
         self.to_cam_space = to_world_space.inverse();
         self.to_world_space = to_world_space;
-        self.direction = direction;
     }
     /// derives the matrix from camera space to world space
-    fn derive_to_world_space_mat(direction: Vector3) -> Mat3x3 {
-        let cam_forward = direction.norm();
-        let cam_right = cam_forward.cross(Vector3::new(0.,0.,-1.));
-        let cam_up = cam_forward.cross(cam_right);
-        Mat3x3::new(cam_right,
-                    cam_up,
-                    cam_forward).transpose()
+    fn derive_to_world_space_mat(direction: Vector3, up: Vector3, roll: f64) -> Mat3x3 {
+        let (right, up, forward) = Self::basis(direction, up, roll);
+        Mat3x3::new(right, up, forward).transpose()
+    }
+    /// The right, up and forward axes of camera space in world space
+    fn basis(direction: Vector3, up: Vector3, roll: f64) -> (Vector3, Vector3, Vector3) {
+        let forward = direction.norm();
+        let mut right = forward.cross(up);
+        if right.len() < 1e-9 * up.len() {
+            // the camera looks straight up or down, the axis that is furthest from the direction takes the place of up
+            let fallback = [Vector3::x(), Vector3::y(), Vector3::z()].into_iter()
+                .min_by(|a, b| a.dot(forward).abs().total_cmp(&b.dot(forward).abs()))
+                .unwrap();
+            right = forward.cross(fallback);
+        }
+        let right = right.norm();
+        let up = right.cross(forward);
+        let (sin, cos) = roll.sin_cos();
+        (right * cos + up * sin, up * cos - right * sin, forward)
+    }
+    /// The half width and half height of the image, at a distance of 1 in front of the camera.
+    ///
+    /// # Arguments
+    ///
+    /// * `aspect_ratio`: The width of the image divided by its height
+    ///
+    /// returns: (f64, f64)
+    pub fn film_size(&self, aspect_ratio: f64) -> (f64, f64) {
+        let extent = (self.fov / 2.0).tan();
+        match self.fov_axis {
+            FovAxis::Horizontal => (extent, extent / aspect_ratio),
+            FovAxis::Vertical => (extent * aspect_ratio, extent),
+            FovAxis::Diagonal => {
+                let diagonal = aspect_ratio.hypot(1.0);
+                (extent * aspect_ratio / diagonal, extent / diagonal)
+            }
+        }
+    }
+    /// The direction of the ray through a point of the image with the [Perspective] model and without a lens, in world space.
+    ///
+    /// # Arguments
+    ///
+    /// * `uv`: The point on the image, from (0, 0) in the bottom left corner to (1, 1) in the top right one
+    /// * `aspect_ratio`: The width of the image divided by its height
+    ///
+    /// returns: Vector3
+    ///     The normalized direction
+    pub fn ray_direction(&self, uv: (f64, f64), aspect_ratio: f64) -> Vector3 {
+        let (width, height) = self.film_size(aspect_ratio);
+        let cam_space_dir = Vector3::new((uv.0 * 2.0 - 1.0) * width, (uv.1 * 2.0 - 1.0) * height, 1.0);
+        self.rotate_to_world_space(cam_space_dir).norm()
+    }
+    /// The ray through a point of the image that the [model](Camera::model) gives, in world space.
+    ///
+    /// # Arguments
+    ///
+    /// * `uv`: The point on the image, from (0, 0) in the bottom left corner to (1, 1) in the top right one
+    /// * `aspect_ratio`: The width of the image divided by its height
+    /// * `lens_sample`: Two numbers from 0 to 1 that pick the point on the lens
+    ///
+    /// returns: Option<(Vector3, Vector3)>
+    ///     The start of the ray and its normalized direction, [None] where the model doesn't see anything
+    pub fn ray(&self, uv: (f64, f64), aspect_ratio: f64, lens_sample: (f64, f64)) -> Option<(Vector3, Vector3)> {
+        self.model.ray(self, uv, aspect_ratio, lens_sample)
+    }
+    /// The ray through a point of the image at a point in time, moved by the [Camera::motion], see [Camera::ray].
+    ///
+    /// # Arguments
+    ///
+    /// * `uv`: The point on the image, from (0, 0) in the bottom left corner to (1, 1) in the top right one
+    /// * `aspect_ratio`: The width of the image divided by its height
+    /// * `lens_sample`: Two numbers from 0 to 1 that pick the point on the lens
+    /// * `time`: The time of the ray
+    ///
+    /// returns: Option<(Vector3, Vector3)>
+    pub fn ray_at(&self, uv: (f64, f64), aspect_ratio: f64, lens_sample: (f64, f64), time: f64) -> Option<(Vector3, Vector3)> {
+        let (position, direction) = self.ray(uv, aspect_ratio, lens_sample)?;
+        Some(self.moved(position, direction, time))
+    }
+    /// the ray through a point of the image at a point in time, with the axis that the depth of what it hits is measured along
+    pub(crate) fn traced_ray(&self, uv: (f64, f64), aspect_ratio: f64, lens_sample: (f64, f64), time: f64) -> Option<CameraRay> {
+        let (position, direction) = self.ray(uv, aspect_ratio, lens_sample)?;
+        let (depth_origin, depth_axis) = self.model.depth_axis(self, position, direction);
+        let (position, direction) = self.moved(position, direction, time);
+        Some(CameraRay {
+            ray: Ray::new(position, direction, time),
+            depth_axis: self.moved(depth_origin, depth_axis, time),
+        })
+    }
+    /// moves a point and a direction along with the camera, to where the [Camera::motion] puts it at the given time
+    fn moved(&self, point: Vector3, direction: Vector3, time: f64) -> (Vector3, Vector3) {
+        let Some(motion) = &self.motion else {
+            return (point, direction);
+        };
+        let transform = motion.at(time);
+        let center = self.motion_center.unwrap_or(self.position);
+        (transform.apply_point(point - center) + center, transform.apply_vector(direction))
     }
     /// Turns a vector from world space to cam space
     pub fn to_cam_space(&self, vec: Vector3) -> Vector3 {
@@ -66,13 +306,46 @@ impl Camera {
         self.to_world_space * vec
     }
 }
+/// A ray of a camera, for the renderer
+pub(crate) struct CameraRay {
+    pub ray: Ray,
+    /// The point with a depth of 0 and the direction in which the depth grows, see [CameraModel::depth_axis]
+    pub depth_axis: (Vector3, Vector3),
+}
+/// The shader builds the rays from the axes of camera space, the same way the built-in [models](model) do
 #[cfg(feature = "gpu")]
 impl GpuSerialize for Camera {
     fn serialize(&self) -> Vec<u8> {
+        let (right, up, forward) = Self::basis(self.direction, self.up, self.roll);
+        let fov_axis = match self.fov_axis {
+            FovAxis::Horizontal => 0u32,
+            FovAxis::Vertical => 1,
+            FovAxis::Diagonal => 2,
+        };
+        let (aperture_radius, focus_distance) = self.lens.as_ref().map_or((0.0, 1.0), |lens| (lens.aperture_radius(), lens.focus_distance));
+        // the kind, the blades and their rotation, and the size of the image, whose weights go to their own buffer
+        let (kind, blades, rotation, width, height) = match self.lens.as_ref().map(|lens| &lens.aperture) {
+            None | Some(Aperture::Disk) => (0u32, 0u32, 0.0, 0u32, 0u32),
+            Some(Aperture::Polygon { blades, rotation }) => (1, (*blades).max(3) as u32, *rotation, 0, 0),
+            Some(Aperture::Image(image)) => (2, 0, 0.0, image.width() as u32, image.height() as u32),
+        };
+        let (model, model_parameter) = model::gpu_model(&*self.model);
         self.position.serialize().into_iter()
-            .chain([0;4])
-            .chain(self.direction.serialize())
-            .chain(self.fov.serialize())
+            .chain(fov_axis.to_le_bytes())
+            .chain(right.serialize())
+            .chain((self.fov / 2.0).tan().serialize())
+            .chain(up.serialize())
+            .chain(aperture_radius.serialize())
+            .chain(forward.serialize())
+            .chain(focus_distance.serialize())
+            .chain(kind.to_le_bytes())
+            .chain(blades.to_le_bytes())
+            .chain(rotation.serialize())
+            .chain(width.to_le_bytes())
+            .chain(height.to_le_bytes())
+            .chain(model.to_le_bytes())
+            .chain(model_parameter.serialize())
+            .chain([0; 4])
             .collect()
     }
 }
@@ -83,28 +356,69 @@ mod tests {
     fn from_world_space() {
         let cam = Camera::new(Vector3::zeros(), Vector3::x(), 90f64.to_radians());
         assert_eq!(cam.to_cam_space(Vector3::x()), Vector3::z());
-        assert_eq!(cam.to_cam_space(Vector3::y()), Vector3::x());
+        assert_eq!(cam.to_cam_space(Vector3::y()), -Vector3::x());
         assert_eq!(cam.to_cam_space(Vector3::z()), Vector3::y());
     }
     #[test]
     fn from_cam_space() {
         let cam = Camera::new(Vector3::zeros(), Vector3::x(), 90f64.to_radians());
-        assert_eq!(cam.to_world_space(Vector3::x()), Vector3::y());
+        assert_eq!(cam.to_world_space(Vector3::x()), -Vector3::y());
         assert_eq!(cam.to_world_space(Vector3::y()), Vector3::z());
         assert_eq!(cam.to_world_space(Vector3::z()), Vector3::x());
     }
     #[test]
     fn from_cam_space_2() {
         let cam = Camera::new(Vector3::zeros(), Vector3::y(), 90f64.to_radians());
-        assert_eq!(cam.to_world_space(Vector3::x()), -Vector3::x());
+        assert_eq!(cam.to_world_space(Vector3::x()), Vector3::x());
         assert_eq!(cam.to_world_space(Vector3::y()), Vector3::z());
         assert_eq!(cam.to_world_space(Vector3::z()), Vector3::y());
     }
     #[test]
     fn from_world_space_2() {
         let cam = Camera::new(Vector3::zeros(), Vector3::y(), 90f64.to_radians());
-        assert_eq!(cam.to_cam_space(Vector3::x()), -Vector3::x());
+        assert_eq!(cam.to_cam_space(Vector3::x()), Vector3::x());
         assert_eq!(cam.to_cam_space(Vector3::y()), Vector3::z());
         assert_eq!(cam.to_cam_space(Vector3::z()), Vector3::y());
     }
-}
\ No newline at end of file
+    #[test]
+    fn looking_straight_down_keeps_a_frame() {
+        let mut cam = Camera::look_at(Vector3::new(0, 0, 2), Vector3::zeros(), Vector3::z(), 1.0);
+        let (right, up) = (cam.rotate_to_world_space(Vector3::x()), cam.rotate_to_world_space(Vector3::y()));
+        assert!((right.len() - 1.0).abs() < 1e-12 && (up.len() - 1.0).abs() < 1e-12);
+        assert!(right.dot(up).abs() < 1e-12 && right.z.abs() < 1e-12 && up.z.abs() < 1e-12);
+        // a quarter turn of roll moves the top of the image to the left
+        cam.set_up(Vector3::y());
+        cam.set_roll(90f64.to_radians());
+        assert!((cam.rotate_to_world_space(Vector3::y()) + Vector3::x()).len() < 1e-12);
+    }
+    #[test]
+    fn projection_is_rectilinear() {
+        let mut cam = Camera::new(Vector3::zeros(), Vector3::x(), 90f64.to_radians());
+        // the right edge is 45° off the middle, the top edge of a 2:1 image tan(y) = 0.5 off
+        let edge = cam.ray_direction((1.0, 0.5), 2.0);
+        assert!((edge - Vector3::new(1, -1, 0).norm()).len() < 1e-12);
+        let top = cam.ray_direction((0.5, 1.0), 2.0);
+        assert!((top.z / top.x - 0.5).abs() < 1e-12);
+        // points on a line stay on a line
+        let points = [0.1, 0.4, 0.9].map(|u| cam.ray_direction((u, u * 0.5 + 0.2), 1.5));
+        assert!(points[0].cross(points[1]).norm().dot(points[2]).abs() < 1e-12);
+        cam.fov_axis = FovAxis::Diagonal;
+        let corner = cam.ray_direction((1.0, 1.0), 2.0);
+        assert!((corner.dot(Vector3::x()) - 45f64.to_radians().cos()).abs() < 1e-12);
+    }
+    #[test]
+    fn lens_rays_meet_on_the_focus_plane() {
+        let cam = Camera::look_at(Vector3::new(0, 0, 1), Vector3::new(4, 0, 1), Vector3::z(), 1.0)
+            .with_lens(ThinLens::new(50.0, 1.0, 4.0));
+        assert!((cam.fov - 2.0 * (0.36f64).atan()).abs() < 1e-12);
+        let (pinhole_position, pinhole_direction) = Camera { lens: None, ..cam.clone() }.ray((0.7, 0.2), 1.5, (0.5, 0.5)).unwrap();
+        let target = pinhole_position + pinhole_direction * (4.0 / pinhole_direction.x);
+        for lens_sample in [(0.1, 0.9), (0.5, 0.5), (0.99, 0.3)] {
+            let (position, direction) = cam.ray((0.7, 0.2), 1.5, lens_sample).unwrap();
+            // the lens is the plane x = 0 through the camera, 2.5cm across
+            assert!(position.x.abs() < 1e-12 && (position - cam.position).len() <= 0.025 + 1e-12);
+            let hit = position + direction * ((4.0 - position.x) / direction.x);
+            assert!((hit - target).len() < 1e-12);
+        }
+    }
+}
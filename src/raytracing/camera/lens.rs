@@ -0,0 +1,282 @@
+//! A thin lens, which makes everything that isn't at the focus distance blurry.
+use crate::math::Vector3;
+use crate::raytracing::environment::sample_cdf;
+use std::f64::consts::PI;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+/// A lens with the parameters of a real camera, see [Camera::with_lens](crate::Camera::with_lens).
+///
+/// Scene units are taken to be meters, so the lens of a 50mm f/2 camera has an aperture of 12.5mm = 0.0125 units.
+///
+/// # Examples
+///
+/// ```
+/// use rtx::camera::lens::{Aperture, ThinLens};
+/// use rtx::Camera;
+///
+/// // a 35mm lens at f/1.4, focused 2 meters in front of the camera, with six blades
+/// let lens = ThinLens::new(35.0, 1.4, 2.0).with_aperture(Aperture::Polygon { blades: 6, rotation: 0.0 });
+/// let camera = Camera::new((0, 0, 1).into(), (1, 0, 0).into(), 1.0).with_lens(lens.clone());
+/// assert_eq!(camera.fov, lens.fov());
+/// ```
+#[derive(Clone, Debug, PartialEq)]
+pub struct ThinLens {
+    /// The focal length in mm
+    pub focal_length: f64,
+    /// The focal length divided by the diameter of the aperture, the smaller the blurrier. Greater than 0.
+    pub f_number: f64,
+    /// The distance from the camera to the plane that is in focus, in scene units
+    pub focus_distance: f64,
+    /// The width of the sensor in mm, 36 for full frame. Together with the focal length, it gives the horizontal fov.
+    pub sensor_width: f64,
+    /// The shape of the aperture, which is the shape of the blurred highlights
+    pub aperture: Aperture,
+}
+impl Default for ThinLens {
+    fn default() -> Self {
+        Self {
+            focal_length: 50.0,
+            f_number: 2.8,
+            focus_distance: 5.0,
+            sensor_width: 36.0,
+            aperture: Aperture::Disk,
+        }
+    }
+}
+impl ThinLens {
+    /// Creates a lens with a round aperture and a full frame sensor.
+    ///
+    /// # Arguments
+    ///
+    /// * `focal_length`: The focal length in mm
+    /// * `f_number`: The focal length divided by the diameter of the aperture
+    /// * `focus_distance`: The distance to the plane that is in focus, in scene units
+    ///
+    /// returns: ThinLens
+    ///
+    /// # Panics
+    ///
+    /// If the f-number isn't greater than 0.
+    pub fn new(focal_length: f64, f_number: f64, focus_distance: f64) -> Self {
+        assert!(f_number > 0.0, "the f-number of a lens has to be greater than 0");
+        Self { focal_length, f_number, focus_distance, ..Self::default() }
+    }
+    pub fn with_sensor_width(mut self, sensor_width: f64) -> Self {
+        self.sensor_width = sensor_width;
+        self
+    }
+    /// changes the shape of the aperture
+    ///
+    /// # Panics
+    ///
+    /// If the aperture is a polygon with less than 3 blades.
+    pub fn with_aperture(mut self, aperture: Aperture) -> Self {
+        if let Aperture::Polygon { blades, .. } = aperture {
+            assert!(blades >= 3, "a polygon aperture needs at least 3 blades");
+        }
+        self.aperture = aperture;
+        self
+    }
+    /// the horizontal fov in radians that the sensor sees through the lens
+    pub fn fov(&self) -> f64 {
+        2.0 * (self.sensor_width / (2.0 * self.focal_length)).atan()
+    }
+    /// the radius of the aperture in scene units
+    pub fn aperture_radius(&self) -> f64 {
+        self.focal_length / (2.0 * self.f_number) / 1000.0
+    }
+    /// A point on the aperture in camera space, with the lens at z = 0.
+    ///
+    /// # Arguments
+    ///
+    /// * `u`: Two numbers from 0 to 1, points are spread evenly over the aperture if they are
+    ///
+    /// returns: Vector3
+    pub fn sample(&self, u: (f64, f64)) -> Vector3 {
+        let (x, y) = self.aperture.sample(u);
+        Vector3::new(x, y, 0.0) * self.aperture_radius()
+    }
+}
+
+/// The shape of the opening of a lens, within the unit circle
+#[derive(Clone, Debug, Default, PartialEq)]
+pub enum Aperture {
+    /// A round opening
+    #[default]
+    Disk,
+    /// The opening between the blades of a diaphragm, a regular polygon with its corners on the unit circle
+    Polygon {
+        /// The number of blades and corners, at least 3
+        blades: usize,
+        /// The angle of the first corner in radians, counterclockwise from the right
+        rotation: f64,
+    },
+    /// An opening with the brightness of an image, for custom bokeh shapes
+    Image(Arc<ApertureImage>),
+}
+impl Aperture {
+    /// a point within the square around the unit circle, spread evenly over the shape
+    pub(crate) fn sample(&self, u: (f64, f64)) -> (f64, f64) {
+        match self {
+            Aperture::Disk => concentric_disk(u),
+            Aperture::Polygon { blades, rotation } => {
+                // every blade makes a triangle with the center, of which the one that `u.0` lands in gets the point
+                let blades = (*blades).max(3);
+                let scaled = u.0 * blades as f64;
+                let blade = (scaled as usize).min(blades - 1);
+                let corner = |index: usize| {
+                    let angle = rotation + 2.0 * PI * index as f64 / blades as f64;
+                    (angle.cos(), angle.sin())
+                };
+                let (start, end) = (corner(blade), corner(blade + 1));
+                // the square root spreads the points evenly from the tip of the triangle to its base
+                let distance = (scaled - blade as f64).sqrt();
+                (
+                    distance * (start.0 + (end.0 - start.0) * u.1),
+                    distance * (start.1 + (end.1 - start.1) * u.1),
+                )
+            }
+            Aperture::Image(image) => image.sample(u),
+        }
+    }
+}
+/// Maps the unit square to the unit disk, keeping neighbouring points close together (Shirley and Chiu 1997)
+fn concentric_disk(u: (f64, f64)) -> (f64, f64) {
+    let (x, y) = (u.0 * 2.0 - 1.0, u.1 * 2.0 - 1.0);
+    if x == 0.0 && y == 0.0 {
+        return (0.0, 0.0);
+    }
+    let (radius, angle) = if x.abs() > y.abs() {
+        (x, PI / 4.0 * (y / x))
+    } else {
+        (y, PI / 2.0 - PI / 4.0 * (x / y))
+    };
+    (radius * angle.cos(), radius * angle.sin())
+}
+
+/// A grayscale image that covers the square around the unit circle, bright where the aperture lets light through
+#[derive(Clone, Debug, PartialEq)]
+pub struct ApertureImage {
+    width: usize,
+    height: usize,
+    /// The file the image was loaded from
+    path: Option<PathBuf>,
+    /// The summed up brightness of the rows
+    row_cdf: Vec<f64>,
+    /// The summed up brightness of the pixels within each row
+    pixel_cdf: Vec<f64>,
+}
+impl ApertureImage {
+    /// Creates a new aperture image.
+    ///
+    /// # Arguments
+    ///
+    /// * `width`: The width of the image in pixels.
+    /// * `height`: The height of the image in pixels.
+    /// * `weights`: How much light gets through every pixel, row by row, starting at the top.
+    ///
+    /// returns: ApertureImage
+    ///
+    /// # Panics
+    ///
+    /// If the number of pixels doesn't match the size or no light gets through.
+    pub fn new(width: usize, height: usize, weights: Vec<f32>) -> Self {
+        assert_eq!(weights.len(), width * height, "expected {width}x{height} pixels");
+        let mut row_cdf = Vec::with_capacity(height);
+        let mut pixel_cdf = Vec::with_capacity(width * height);
+        let mut total = 0.0;
+        for row in weights.chunks_exact(width.max(1)) {
+            let mut row_total = 0.0;
+            for &weight in row {
+                row_total += weight.max(0.0) as f64;
+                pixel_cdf.push(row_total);
+            }
+            total += row_total;
+            row_cdf.push(total);
+        }
+        assert!(total > 0.0, "the aperture image has to let some light through");
+        Self { width, height, path: None, row_cdf, pixel_cdf }
+    }
+    /// Loads an image and uses its brightness. Requires the `images` feature
+    #[cfg(feature = "images")]
+    pub fn load(path: impl Into<PathBuf>) -> image::ImageResult<Self> {
+        use crate::raytracing::bsdf::luminance;
+        let path = path.into();
+        let image = image::open(&path)?.into_rgb32f();
+        let weights = image.pixels().map(|pixel| luminance(Vector3::new(pixel.0[0], pixel.0[1], pixel.0[2])) as f32).collect();
+        let mut aperture = Self::new(image.width() as usize, image.height() as usize, weights);
+        aperture.path = Some(path);
+        Ok(aperture)
+    }
+    pub fn width(&self) -> usize {
+        self.width
+    }
+    pub fn height(&self) -> usize {
+        self.height
+    }
+    /// the file the image was loaded from, if it was loaded with [ApertureImage::load]
+    pub fn path(&self) -> Option<&PathBuf> {
+        self.path.as_ref()
+    }
+    /// the summed up brightness of the rows
+    #[cfg(feature = "gpu")]
+    pub(crate) fn row_cdf(&self) -> &[f64] {
+        &self.row_cdf
+    }
+    /// the summed up brightness of the pixels within each row
+    #[cfg(feature = "gpu")]
+    pub(crate) fn pixel_cdf(&self) -> &[f64] {
+        &self.pixel_cdf
+    }
+    /// a point in the unit square, picked by the brightness of the pixels
+    fn sample(&self, u: (f64, f64)) -> (f64, f64) {
+        let (y, v_offset) = sample_cdf(&self.row_cdf, u.0);
+        let (x, u_offset) = sample_cdf(&self.pixel_cdf[y * self.width..(y + 1) * self.width], u.1);
+        // the top row is at the top of the aperture
+        (
+            (x as f64 + u_offset) / self.width as f64 * 2.0 - 1.0,
+            1.0 - (y as f64 + v_offset) / self.height as f64 * 2.0,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// the points that the apertures give for a grid of numbers
+    fn points(aperture: &Aperture) -> Vec<(f64, f64)> {
+        (0..32 * 32).map(|i| aperture.sample(((i % 32) as f64 / 32.0 + 0.01, (i / 32) as f64 / 32.0 + 0.01))).collect()
+    }
+    #[test]
+    fn apertures_stay_in_their_shape() {
+        let hexagon = Aperture::Polygon { blades: 6, rotation: 0.3 };
+        for (x, y) in points(&hexagon) {
+            // the sides of a hexagon with its corners on the unit circle are sqrt(3)/2 from the center
+            let angle = y.atan2(x) - 0.3;
+            let side_angle = angle.rem_euclid(PI / 3.0) - PI / 6.0;
+            assert!(x.hypot(y) * side_angle.cos() <= 3f64.sqrt() / 2.0 + 1e-12);
+        }
+        assert!(points(&Aperture::Disk).iter().all(|(x, y)| x.hypot(*y) <= 1.0 + 1e-12));
+        // only the right half of the image lets light through
+        let image = ApertureImage::new(4, 2, vec![0.0, 0.0, 1.0, 1.0, 0.0, 0.0, 1.0, 1.0]);
+        assert!(points(&Aperture::Image(Arc::new(image))).iter().all(|(x, y)| *x >= 0.0 && x.abs() <= 1.0 && y.abs() <= 1.0));
+    }
+    /// checks that the points are spread evenly, with `is_inner` telling whether a point is in the same shape at half the size
+    fn assert_even(aperture: Aperture, is_inner: impl Fn(f64, f64) -> bool) {
+        let points = points(&aperture);
+        // as many points in the left half as in the right one, and the upper half as in the lower one
+        assert_eq!(points.iter().filter(|(x, _)| *x < 0.0).count(), points.len() / 2);
+        assert_eq!(points.iter().filter(|(_, y)| *y < 0.0).count(), points.len() / 2);
+        // half the size is a quarter of the area
+        let inner = points.iter().filter(|(x, y)| is_inner(*x, *y)).count();
+        assert!((inner as f64 / points.len() as f64 - 0.25).abs() < 0.02, "{aperture:?} {inner}");
+    }
+    #[test]
+    fn points_cover_the_aperture_evenly() {
+        assert_even(Aperture::Disk, |x, y| x.hypot(y) < 0.5);
+        // an axis aligned square, with the sides 1/sqrt(2) from the center
+        assert_even(Aperture::Polygon { blades: 4, rotation: PI / 4.0 }, |x, y| x.abs().max(y.abs()) < 0.5 / 2f64.sqrt());
+    }
+}
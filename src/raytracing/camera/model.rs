@@ -0,0 +1,264 @@
+//! Projections that map points of the image to the rays the camera sees them through.
+use super::Camera;
+use crate::math::Vector3;
+use std::any::Any;
+use std::f64::consts::PI;
+use std::fmt::Debug;
+
+/// Maps a point of the image to a ray in world space, see [Camera::with_model].
+///
+/// Only the cpu renderer supports custom models, the gpu renderer uses a [Perspective] one instead.
+///
+/// # Examples
+///
+/// ```
+/// use rtx::camera::model::CameraModel;
+/// use rtx::math::Vector3;
+/// use rtx::Camera;
+///
+/// /// sees the whole image along the direction of the camera, but from a bit further to the right the more to the right
+/// #[derive(Debug)]
+/// struct Pushbroom;
+/// impl CameraModel for Pushbroom {
+///     fn ray(&self, camera: &Camera, uv: (f64, f64), _aspect_ratio: f64, _lens_sample: (f64, f64)) -> Option<(Vector3, Vector3)> {
+///         let direction = Vector3::new(0.0, uv.1 * 2.0 - 1.0, 1.0);
+///         Some((camera.to_world_space(Vector3::new(uv.0 * 2.0 - 1.0, 0.0, 0.0)), camera.rotate_to_world_space(direction).norm()))
+///     }
+/// }
+/// let camera = Camera::new(Vector3::zeros(), Vector3::x(), 1.0).with_model(Pushbroom);
+/// let (position, _) = camera.ray((1.0, 0.5), 1.0, (0.5, 0.5)).unwrap();
+/// assert!((position + Vector3::y()).len() < 1e-12);
+/// ```
+pub trait CameraModel: Any + Debug + Send + Sync {
+    /// The ray through a point of the image, in world space.
+    ///
+    /// # Arguments
+    ///
+    /// * `camera`: The camera with the position and orientation, see [Camera::to_world_space]
+    /// * `uv`: The point on the image, from (0, 0) in the bottom left corner to (1, 1) in the top right one
+    /// * `aspect_ratio`: The width of the image divided by its height
+    /// * `lens_sample`: Two numbers from 0 to 1 that pick the point on the lens, for models that have one
+    ///
+    /// returns: Option<(Vector3, Vector3)>
+    ///     The start of the ray and its normalized direction,
+    ///     [None] for points that the model doesn't see anything through, which stay black
+    fn ray(&self, camera: &Camera, uv: (f64, f64), aspect_ratio: f64, lens_sample: (f64, f64)) -> Option<(Vector3, Vector3)>;
+    /// The axis that the [depth](crate::Aov::Depth) of what a ray hits is measured along, in world space.
+    /// By default the ray itself, so the depth is the distance along the ray, which suits panoramas.
+    ///
+    /// # Arguments
+    ///
+    /// * `camera`: The camera with the position and orientation, see [Camera::to_world_space]
+    /// * `position`: The start of a ray from [CameraModel::ray]
+    /// * `direction`: The direction of that ray
+    ///
+    /// returns: (Vector3, Vector3)
+    ///     The point with a depth of 0 and the normalized direction in which the depth grows
+    fn depth_axis(&self, _camera: &Camera, position: Vector3, direction: Vector3) -> (Vector3, Vector3) {
+        (position, direction)
+    }
+}
+
+/// The rectilinear projection of a pinhole or a [thin lens](super::lens::ThinLens),
+/// with the [fov](Camera::fov), [fov axis](Camera::fov_axis) and [lens](Camera::lens) of the camera
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Perspective;
+impl CameraModel for Perspective {
+    fn ray(&self, camera: &Camera, uv: (f64, f64), aspect_ratio: f64, lens_sample: (f64, f64)) -> Option<(Vector3, Vector3)> {
+        let Some(lens) = &camera.lens else {
+            return Some((camera.position, camera.ray_direction(uv, aspect_ratio)));
+        };
+        let (width, height) = camera.film_size(aspect_ratio);
+        // all rays through the same point of the image meet on the plane in focus
+        let focus_point = Vector3::new((uv.0 * 2.0 - 1.0) * width, (uv.1 * 2.0 - 1.0) * height, 1.0) * lens.focus_distance;
+        let lens_point = lens.sample(lens_sample);
+        Some((camera.to_world_space(lens_point), camera.rotate_to_world_space(focus_point - lens_point).norm()))
+    }
+    /// along the viewing direction, so flat surfaces facing the camera have the same depth everywhere
+    fn depth_axis(&self, camera: &Camera, _position: Vector3, _direction: Vector3) -> (Vector3, Vector3) {
+        (camera.position, camera.rotate_to_world_space(Vector3::z()))
+    }
+}
+
+/// Parallel rays from a rectangle around the camera position, so things keep their size at any distance
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Orthographic {
+    /// The width of the rectangle in scene units, its height follows from the aspect ratio
+    pub width: f64,
+}
+impl CameraModel for Orthographic {
+    fn ray(&self, camera: &Camera, uv: (f64, f64), aspect_ratio: f64, _lens_sample: (f64, f64)) -> Option<(Vector3, Vector3)> {
+        let offset = Vector3::new(uv.0 - 0.5, (uv.1 - 0.5) / aspect_ratio, 0.0) * self.width;
+        Some((camera.to_world_space(offset), camera.rotate_to_world_space(Vector3::z())))
+    }
+    fn depth_axis(&self, camera: &Camera, _position: Vector3, _direction: Vector3) -> (Vector3, Vector3) {
+        (camera.position, camera.rotate_to_world_space(Vector3::z()))
+    }
+}
+
+/// How the angle from the center of a [Fisheye] image grows with the distance to it
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum FisheyeMapping {
+    /// The angle grows evenly with the distance, as dome projections expect
+    #[default]
+    Equidistant,
+    /// Equal areas of the image see equal solid angles, like most real fisheye lenses
+    Equisolid,
+}
+impl FisheyeMapping {
+    pub const ALL: [FisheyeMapping; 2] = [FisheyeMapping::Equidistant, FisheyeMapping::Equisolid];
+
+    /// the name in scene files
+    pub fn name(&self) -> &'static str {
+        match self {
+            FisheyeMapping::Equidistant => "equidistant",
+            FisheyeMapping::Equisolid => "equisolid",
+        }
+    }
+    /// finds the mapping with the given [FisheyeMapping::name]
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|mapping| mapping.name() == name)
+    }
+}
+
+/// A circular fisheye, the circle touches the shorter edges of the image and the corners outside of it stay black
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Fisheye {
+    /// The fov across the circle in radians, up to 2π
+    pub fov: f64,
+    pub mapping: FisheyeMapping,
+}
+impl Default for Fisheye {
+    fn default() -> Self {
+        Self { fov: PI, mapping: FisheyeMapping::default() }
+    }
+}
+impl CameraModel for Fisheye {
+    fn ray(&self, camera: &Camera, uv: (f64, f64), aspect_ratio: f64, _lens_sample: (f64, f64)) -> Option<(Vector3, Vector3)> {
+        // the point relative to the circle, which has a radius of 1
+        let (x, y) = (uv.0 * 2.0 - 1.0, uv.1 * 2.0 - 1.0);
+        let (x, y) = if aspect_ratio >= 1.0 { (x * aspect_ratio, y) } else { (x, y / aspect_ratio) };
+        let radius = x.hypot(y);
+        if radius > 1.0 {
+            return None;
+        }
+        let angle = match self.mapping {
+            FisheyeMapping::Equidistant => radius * self.fov / 2.0,
+            FisheyeMapping::Equisolid => 2.0 * (radius * (self.fov / 4.0).sin()).asin(),
+        };
+        let (sin, cos) = angle.sin_cos();
+        let (x, y) = if radius > 0.0 { (x / radius, y / radius) } else { (0.0, 0.0) };
+        Some((camera.position, camera.rotate_to_world_space(Vector3::new(x * sin, y * sin, cos)).norm()))
+    }
+}
+
+/// A 360° panorama with the longitude along the width and the latitude along the height (lat-long),
+/// for images with an aspect ratio of 2:1. The center of the image is in the direction of the camera.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Equirectangular;
+impl CameraModel for Equirectangular {
+    fn ray(&self, camera: &Camera, uv: (f64, f64), _aspect_ratio: f64, _lens_sample: (f64, f64)) -> Option<(Vector3, Vector3)> {
+        let longitude = (uv.0 - 0.5) * 2.0 * PI;
+        let latitude = (uv.1 - 0.5) * PI;
+        let direction = Vector3::new(latitude.cos() * longitude.sin(), latitude.sin(), latitude.cos() * longitude.cos());
+        Some((camera.position, camera.rotate_to_world_space(direction).norm()))
+    }
+}
+
+/// The six faces of a cube around the camera next to each other, for images with an aspect ratio of 6:1.
+///
+/// The faces look along +x, -x, +y, -y, +z and -z of camera space, so to the right, left, top, bottom, front and back.
+/// The top of the side faces is the top of the camera, the top face has the back at its top and the bottom face the front.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Cubemap;
+impl Cubemap {
+    /// the right, up and forward axes of the faces in camera space
+    const FACES: [[(f64, f64, f64); 3]; 6] = [
+        [(0.0, 0.0, -1.0), (0.0, 1.0, 0.0), (1.0, 0.0, 0.0)],
+        [(0.0, 0.0, 1.0), (0.0, 1.0, 0.0), (-1.0, 0.0, 0.0)],
+        [(1.0, 0.0, 0.0), (0.0, 0.0, -1.0), (0.0, 1.0, 0.0)],
+        [(1.0, 0.0, 0.0), (0.0, 0.0, 1.0), (0.0, -1.0, 0.0)],
+        [(1.0, 0.0, 0.0), (0.0, 1.0, 0.0), (0.0, 0.0, 1.0)],
+        [(-1.0, 0.0, 0.0), (0.0, 1.0, 0.0), (0.0, 0.0, -1.0)],
+    ];
+}
+impl CameraModel for Cubemap {
+    fn ray(&self, camera: &Camera, uv: (f64, f64), _aspect_ratio: f64, _lens_sample: (f64, f64)) -> Option<(Vector3, Vector3)> {
+        let scaled = uv.0 * 6.0;
+        let face = (scaled.max(0.0) as usize).min(5);
+        let [right, up, forward] = Self::FACES[face].map(|(x, y, z)| Vector3::new(x, y, z));
+        // every face is a 90° perspective view
+        let direction = right * ((scaled - face as f64) * 2.0 - 1.0) + up * (uv.1 * 2.0 - 1.0) + forward;
+        Some((camera.position, camera.rotate_to_world_space(direction).norm()))
+    }
+}
+
+/// The kind of a built-in model and its parameter for the shader, [Perspective] for custom ones
+#[cfg(feature = "gpu")]
+pub(crate) fn gpu_model(model: &dyn CameraModel) -> (u32, f64) {
+    let model: &dyn Any = model;
+    if let Some(orthographic) = model.downcast_ref::<Orthographic>() {
+        (1, orthographic.width)
+    } else if let Some(fisheye) = model.downcast_ref::<Fisheye>() {
+        let kind = match fisheye.mapping {
+            FisheyeMapping::Equidistant => 2,
+            FisheyeMapping::Equisolid => 3,
+        };
+        (kind, fisheye.fov)
+    } else if model.is::<Equirectangular>() {
+        (4, 0.0)
+    } else if model.is::<Cubemap>() {
+        (5, 0.0)
+    } else {
+        (0, 0.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn camera(model: impl CameraModel) -> Camera {
+        Camera::new(Vector3::new(1, 2, 3), Vector3::x(), 1.0).with_model(model)
+    }
+    #[test]
+    fn orthographic_rays_are_parallel() {
+        let camera = camera(Orthographic { width: 4.0 });
+        let (corner, direction) = camera.ray((1.0, 1.0), 2.0, (0.5, 0.5)).unwrap();
+        // 2 units to the right (-y) and 1 up
+        assert!((corner - Vector3::new(1, 0, 4)).len() < 1e-12);
+        assert_eq!(direction, Vector3::x());
+        assert_eq!(camera.ray((0.3, 0.1), 2.0, (0.5, 0.5)).unwrap().1, Vector3::x());
+    }
+    #[test]
+    fn fisheye_angles_follow_the_mapping() {
+        let angle = |mapping, uv| {
+            let (_, direction) = camera(Fisheye { fov: PI, mapping }).ray(uv, 2.0, (0.5, 0.5))?;
+            Some(direction.dot(Vector3::x()).acos())
+        };
+        // halfway to the top edge of the circle
+        assert!((angle(FisheyeMapping::Equidistant, (0.5, 0.75)).unwrap() - PI / 4.0).abs() < 1e-12);
+        assert!((angle(FisheyeMapping::Equisolid, (0.5, 0.75)).unwrap() - 2.0 * (0.5 * (PI / 4.0).sin()).asin()).abs() < 1e-12);
+        // the circle touches the top edge and the left side of a 2:1 image is outside of it
+        assert!((angle(FisheyeMapping::Equisolid, (0.5, 1.0)).unwrap() - PI / 2.0).abs() < 1e-12);
+        assert_eq!(angle(FisheyeMapping::Equidistant, (0.1, 0.5)), None);
+    }
+    #[test]
+    fn panoramas_see_every_direction() {
+        let equirectangular = camera(Equirectangular);
+        let direction = |uv| equirectangular.ray(uv, 2.0, (0.5, 0.5)).unwrap().1;
+        assert!((direction((0.5, 0.5)) - Vector3::x()).len() < 1e-12);
+        assert!((direction((0.75, 0.5)) + Vector3::y()).len() < 1e-12);
+        assert!((direction((0.0, 0.5)) + Vector3::x()).len() < 1e-12);
+        assert!((direction((0.3, 1.0)) - Vector3::z()).len() < 1e-12);
+        let cubemap = camera(Cubemap);
+        let direction = |uv| cubemap.ray(uv, 6.0, (0.5, 0.5)).unwrap().1;
+        let centers = [-Vector3::y(), Vector3::y(), Vector3::z(), -Vector3::z(), Vector3::x(), -Vector3::x()];
+        for (face, center) in centers.into_iter().enumerate() {
+            assert!((direction(((face as f64 + 0.5) / 6.0, 0.5)) - center).len() < 1e-12);
+        }
+        // neighbouring faces share their edges
+        assert!((direction((0.0, 0.7)) - direction((5.0 / 6.0 - 1e-12, 0.7))).len() < 1e-9);
+        assert!((direction((2.5 / 6.0, 0.0)) - direction((4.5 / 6.0, 1.0))).len() < 1e-9);
+    }
+}
@@ -0,0 +1,257 @@
+//! Stereo rigs that render an image for each eye, for headsets and 3D displays.
+use super::model::{CameraModel, Equirectangular};
+use super::Camera;
+use crate::math::Vector3;
+use std::any::Any;
+use std::sync::Arc;
+
+/// How the eyes of a [StereoRig] look at the convergence distance
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum StereoMode {
+    /// Both eyes look in the direction of the camera and their images are shifted,
+    /// so the planes in focus line up without the vertical parallax of toe-in
+    #[default]
+    Parallel,
+    /// Both eyes turn towards the point at the convergence distance in front of the camera
+    ToeIn,
+}
+
+/// How the images of the two eyes are packed into one
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum StereoLayout {
+    /// The left eye in the left half, the right eye in the right one
+    #[default]
+    SideBySide,
+    /// The left eye in the upper half, the right eye in the lower one
+    OverUnder,
+}
+impl StereoLayout {
+    /// The size of the packed image.
+    ///
+    /// # Arguments
+    ///
+    /// * `width`: The width of the image of one eye in pixels
+    /// * `height`: The height of the image of one eye in pixels
+    ///
+    /// returns: (usize, usize)
+    pub fn packed_size(&self, width: usize, height: usize) -> (usize, usize) {
+        match self {
+            StereoLayout::SideBySide => (width * 2, height),
+            StereoLayout::OverUnder => (width, height * 2),
+        }
+    }
+    /// the eye that sees a point of the packed image, and the point on its image, both from (0, 0) in the bottom left corner
+    pub(crate) fn split(&self, uv: (f64, f64)) -> (Eye, (f64, f64)) {
+        match self {
+            StereoLayout::SideBySide if uv.0 < 0.5 => (Eye::Left, (uv.0 * 2.0, uv.1)),
+            StereoLayout::SideBySide => (Eye::Right, (uv.0 * 2.0 - 1.0, uv.1)),
+            StereoLayout::OverUnder if uv.1 >= 0.5 => (Eye::Left, (uv.0, uv.1 * 2.0 - 1.0)),
+            StereoLayout::OverUnder => (Eye::Right, (uv.0, uv.1 * 2.0)),
+        }
+    }
+}
+
+/// One of the eyes of a [StereoRig]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Eye {
+    Left,
+    Right,
+}
+
+/// Two eyes to the left and right of a camera, rendered together with [Scene::render_stereo](crate::Scene::render_stereo).
+///
+/// With an [Equirectangular] camera, the rig renders omni-directional stereo (ODS):
+/// every ray starts on the circle that the eyes make when the head turns towards it,
+/// so all directions of the panorama get the right parallax. Towards the poles the eyes move together.
+///
+/// # Examples
+///
+/// ```
+/// use rtx::camera::stereo::{Eye, StereoLayout, StereoRig};
+/// use rtx::Camera;
+///
+/// let camera = Camera::look_at((0, 0, 1.7).into(), (5, 0, 1.7).into(), (0, 0, 1).into(), 90f64.to_radians());
+/// let rig = StereoRig::new(camera).with_convergence_distance(5.0).with_layout(StereoLayout::OverUnder);
+/// // the left eye is 32mm to the left of the camera
+/// assert!((rig.eye(Eye::Left).position.y - 0.032).abs() < 1e-12);
+/// assert_eq!(rig.layout.packed_size(64, 32), (64, 64));
+/// ```
+#[derive(Clone, Debug)]
+pub struct StereoRig {
+    /// The camera between the eyes
+    pub camera: Camera,
+    /// The distance between the eyes in scene units, 64mm by default
+    pub interpupillary_distance: f64,
+    /// The distance from the camera at which both eyes see the same point, so things there seem to be as far away as the screen.
+    /// Infinite by default, which keeps the eyes parallel.
+    pub convergence_distance: f64,
+    pub mode: StereoMode,
+    pub layout: StereoLayout,
+}
+impl StereoRig {
+    /// Creates a rig with the eyes 64mm apart and parallel, packed side by side.
+    pub fn new(camera: Camera) -> Self {
+        Self {
+            camera,
+            interpupillary_distance: 0.064,
+            convergence_distance: f64::INFINITY,
+            mode: StereoMode::default(),
+            layout: StereoLayout::default(),
+        }
+    }
+    pub fn with_interpupillary_distance(mut self, interpupillary_distance: f64) -> Self {
+        self.interpupillary_distance = interpupillary_distance;
+        self
+    }
+    pub fn with_convergence_distance(mut self, convergence_distance: f64) -> Self {
+        self.convergence_distance = convergence_distance;
+        self
+    }
+    pub fn with_mode(mut self, mode: StereoMode) -> Self {
+        self.mode = mode;
+        self
+    }
+    pub fn with_layout(mut self, layout: StereoLayout) -> Self {
+        self.layout = layout;
+        self
+    }
+    /// The camera of one eye. Its [model](Camera::model) is only supported by the cpu renderer,
+    /// unless the eyes are toed in or parallel without convergence.
+    pub fn eye(&self, eye: Eye) -> Camera {
+        let offset = match eye {
+            Eye::Left => -self.interpupillary_distance / 2.0,
+            Eye::Right => self.interpupillary_distance / 2.0,
+        };
+        let mut camera = self.camera.clone();
+        // the eyes move together, the whole rig turns around its camera
+        camera.motion_center = Some(self.camera.motion_center.unwrap_or(self.camera.position));
+        let model: &dyn Any = &*self.camera.model;
+        if model.is::<Equirectangular>() {
+            camera.model = Arc::new(OmniDirectional { offset, convergence_distance: self.convergence_distance });
+            return camera;
+        }
+        let forward = camera.rotate_to_world_space(Vector3::z());
+        camera.position = camera.to_world_space(Vector3::x() * offset);
+        if self.convergence_distance.is_finite() {
+            match self.mode {
+                StereoMode::Parallel => {
+                    camera.model = Arc::new(OffAxis {
+                        model: self.camera.model.clone(),
+                        offset,
+                        convergence_distance: self.convergence_distance,
+                    });
+                }
+                StereoMode::ToeIn => camera.set_direction(self.camera.position + forward * self.convergence_distance - camera.position),
+            }
+        }
+        camera
+    }
+}
+
+/// The model of an eye that is moved to the side of the camera,
+/// with its rays turned so they meet the ones of the camera at the convergence distance
+#[derive(Debug)]
+struct OffAxis {
+    model: Arc<dyn CameraModel>,
+    /// how far the eye is to the right of the camera
+    offset: f64,
+    convergence_distance: f64,
+}
+impl CameraModel for OffAxis {
+    fn ray(&self, camera: &Camera, uv: (f64, f64), aspect_ratio: f64, lens_sample: (f64, f64)) -> Option<(Vector3, Vector3)> {
+        let (position, direction) = self.model.ray(camera, uv, aspect_ratio, lens_sample)?;
+        let center = position - camera.rotate_to_world_space(Vector3::x()) * self.offset;
+        // the distance along the ray to the plane at the convergence distance
+        let distance = self.convergence_distance / direction.dot(camera.rotate_to_world_space(Vector3::z()));
+        if distance <= 0.0 {
+            return Some((position, direction));
+        }
+        Some((position, (center + direction * distance - position).norm()))
+    }
+    fn depth_axis(&self, camera: &Camera, position: Vector3, direction: Vector3) -> (Vector3, Vector3) {
+        self.model.depth_axis(camera, position, direction)
+    }
+}
+
+/// An [Equirectangular] eye that turns around the camera with the direction of the rays
+#[derive(Debug)]
+struct OmniDirectional {
+    /// how far the eye is to the right of the camera when it looks in the direction of the camera
+    offset: f64,
+    convergence_distance: f64,
+}
+impl CameraModel for OmniDirectional {
+    fn ray(&self, camera: &Camera, uv: (f64, f64), aspect_ratio: f64, lens_sample: (f64, f64)) -> Option<(Vector3, Vector3)> {
+        let (center, direction) = Equirectangular.ray(camera, uv, aspect_ratio, lens_sample)?;
+        // to the right of the direction, shrinking towards the poles so the eyes don't spin around them
+        let side = direction.cross(camera.rotate_to_world_space(Vector3::y()));
+        let position = center + side * self.offset;
+        if !self.convergence_distance.is_finite() {
+            return Some((position, direction));
+        }
+        Some((position, (center + direction * self.convergence_distance - position).norm()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::raytracing::motion::{Motion, Transform};
+    use std::f64::consts::PI;
+
+    fn camera() -> Camera {
+        Camera::look_at(Vector3::new(0, 0, 1), Vector3::new(1, 0, 1), Vector3::z(), 90f64.to_radians())
+    }
+    #[test]
+    fn eyes_converge_at_the_distance() {
+        for mode in [StereoMode::Parallel, StereoMode::ToeIn] {
+            let rig = StereoRig::new(camera()).with_convergence_distance(4.0).with_mode(mode);
+            let (left, right) = (rig.eye(Eye::Left), rig.eye(Eye::Right));
+            assert!((left.position - right.position - Vector3::y() * 0.064).len() < 1e-12);
+            // the middle of both images is the point in front of the camera
+            for eye in [left, right] {
+                let (position, direction) = eye.ray((0.5, 0.5), 1.5, (0.5, 0.5)).unwrap();
+                let hit = position + direction * ((4.0 - position.x) / direction.x);
+                assert!((hit - Vector3::new(4, 0, 1)).len() < 1e-12, "{mode:?}");
+            }
+        }
+        // parallel eyes keep the plane in focus free of vertical parallax, unlike toed in ones
+        let hits = |mode| {
+            let rig = StereoRig::new(camera()).with_convergence_distance(4.0).with_mode(mode);
+            [Eye::Left, Eye::Right].map(|eye| {
+                let (position, direction) = rig.eye(eye).ray((0.9, 0.9), 1.0, (0.5, 0.5)).unwrap();
+                position + direction * ((4.0 - position.x) / direction.x)
+            })
+        };
+        let [left, right] = hits(StereoMode::Parallel);
+        assert!((left - right).len() < 1e-12);
+        let [left, right] = hits(StereoMode::ToeIn);
+        assert!((left.z - right.z).abs() > 1e-3);
+    }
+    #[test]
+    fn omni_directional_eyes_circle_the_camera() {
+        let rig = StereoRig::new(camera().with_model(Equirectangular));
+        for uv in [(0.5, 0.5), (0.8, 0.5), (0.1, 0.6), (0.3, 0.25)] {
+            let (left, direction) = rig.eye(Eye::Left).ray(uv, 2.0, (0.5, 0.5)).unwrap();
+            let (right, right_direction) = rig.eye(Eye::Right).ray(uv, 2.0, (0.5, 0.5)).unwrap();
+            assert_eq!(direction, right_direction);
+            // the eyes are level and across from each other, at a right angle to the ray
+            let latitude = (uv.1 - 0.5) * std::f64::consts::PI;
+            assert!(((right - left).len() - 0.064 * latitude.cos()).abs() < 1e-12);
+            assert!((left + right - Vector3::new(0, 0, 2)).len() < 1e-12);
+            assert!((right - left).z.abs() < 1e-12 && (right - left).dot(direction).abs() < 1e-12);
+        }
+        // looking along the camera, the left eye is on its left
+        assert!(rig.eye(Eye::Left).ray((0.5, 0.5), 2.0, (0.5, 0.5)).unwrap().0.y > 0.0);
+    }
+    #[test]
+    fn eyes_turn_around_the_rig() {
+        // a quarter turn to the left while the shutter is open
+        let camera = Camera::new(Vector3::zeros(), Vector3::x(), 1.0)
+            .with_motion(Motion::linear(Transform::default(), Transform::rotation(Vector3::z(), PI / 2.0)));
+        let rig = StereoRig::new(camera);
+        let (position, direction) = rig.eye(Eye::Left).ray_at((0.5, 0.5), 1.0, (0.5, 0.5), 1.0).unwrap();
+        assert!((position - Vector3::new(-0.032, 0, 0)).len() < 1e-12);
+        assert!((direction - Vector3::y()).len() < 1e-12);
+    }
+}
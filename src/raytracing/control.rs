@@ -0,0 +1,87 @@
+//! Watching and stopping renders that are still running, see [Scene::render_with_control](crate::Scene::render_with_control).
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// Lets the caller stop a render early. Clones share the same cancellation, so one can be moved to another thread.
+///
+/// # Examples
+///
+/// ```
+/// use rtx::control::RenderControl;
+/// use rtx::Scene;
+/// use std::time::Duration;
+///
+/// let control = RenderControl::new().with_time_budget(Duration::from_secs(60));
+/// let canceller = control.clone();
+/// let (film, progress) = Scene::default().render_with_control(16, 8, &control, |progress| {
+///     if progress.fraction() >= 0.5 {
+///         canceller.cancel();
+///     }
+/// });
+/// assert!(progress.fraction() >= 0.5);
+/// assert_eq!(film.width(), 16);
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct RenderControl {
+    cancelled: Arc<AtomicBool>,
+    time_budget: Option<Duration>,
+}
+impl RenderControl {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    /// stops the render once it ran for the given time
+    pub fn with_time_budget(mut self, time_budget: Duration) -> Self {
+        self.time_budget = Some(time_budget);
+        self
+    }
+    pub fn time_budget(&self) -> Option<Duration> {
+        self.time_budget
+    }
+    /// Asks the render to stop. It finishes the pixels it is working on and returns what it has.
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+    /// whether a render that started at `start` has to stop
+    pub(crate) fn should_stop(&self, start: Instant) -> bool {
+        self.is_cancelled() || self.time_budget.is_some_and(|budget| start.elapsed() >= budget)
+    }
+}
+/// How far a render is
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Progress {
+    /// The tiles that were rendered completely, counted once per pass over the image
+    pub finished_tiles: usize,
+    pub total_tiles: usize,
+    /// The camera rays that were traced, summed over all pixels
+    pub finished_samples: usize,
+    /// The camera rays that [adaptive sampling](crate::Config::adaptive_sampling) found unnecessary, they count as done
+    pub skipped_samples: usize,
+    pub total_samples: usize,
+    /// The time since the render started
+    pub elapsed: Duration,
+}
+impl Progress {
+    /// the part of the samples that are done or skipped, from 0 to 1
+    pub fn fraction(&self) -> f64 {
+        match self.total_samples {
+            0 => 1.0,
+            total => (self.finished_samples + self.skipped_samples) as f64 / total as f64,
+        }
+    }
+    /// the part of the tiles that are done, from 0 to 1
+    pub fn tile_fraction(&self) -> f64 {
+        match self.total_tiles {
+            0 => 1.0,
+            total => self.finished_tiles as f64 / total as f64,
+        }
+    }
+    /// whether every sample was rendered or skipped, so the render wasn't stopped early
+    pub fn is_complete(&self) -> bool {
+        self.finished_samples + self.skipped_samples == self.total_samples
+    }
+}
@@ -0,0 +1,208 @@
+//! An edge-avoiding À-Trous wavelet filter (Dammertz et al. 2010) that removes the noise of renders with few rays per pixel.
+use crate::math::Vector3;
+use crate::Film;
+use std::thread;
+
+/// The weights of the B3 spline, applied in both directions
+const KERNEL: [f64; 5] = [1.0 / 16.0, 1.0 / 4.0, 3.0 / 8.0, 1.0 / 4.0, 1.0 / 16.0];
+
+/// The settings of the denoiser.
+///
+/// Every iteration blurs with a 5x5 kernel whose taps are twice as far apart as in the iteration before,
+/// but only across pixels whose colors, normals and albedos are similar.
+/// The albedo is divided out before filtering, so textures stay sharp.
+///
+/// # Examples
+///
+/// ```
+/// use rtx::denoise::Denoiser;
+/// use rtx::{Aov, Config, Scene};
+///
+/// // the normal and albedo buffers guide the filter
+/// let mut scene = Scene::default();
+/// scene.config = Config::default().with_rays_per_pixel(2).with_aovs(vec![Aov::Normal, Aov::Albedo]);
+/// let film = scene.render(16, 8);
+/// let denoised = film.denoised(&Denoiser::default());
+/// // or let the render do it
+/// scene.config = scene.config.with_denoiser(Some(Denoiser::default()));
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Denoiser {
+    /// How often the filter is applied, the radius of the filter is `2^(iterations + 1)` pixels
+    pub iterations: usize,
+    /// How different colors can be before they stop blurring into each other, gets halved every iteration
+    pub color_sigma: f64,
+    /// How different normals can be before they stop blurring into each other
+    pub normal_sigma: f64,
+    /// How different albedos can be before they stop blurring into each other
+    pub albedo_sigma: f64,
+}
+impl Default for Denoiser {
+    fn default() -> Self {
+        Self {
+            iterations: 5,
+            color_sigma: 0.6,
+            normal_sigma: 0.3,
+            albedo_sigma: 0.1,
+        }
+    }
+}
+impl Denoiser {
+    /// Denoises the colors of a film.
+    ///
+    /// # Arguments
+    ///
+    /// * `color`: The noisy film
+    /// * `normal`: The normals of the first hits, like [Aov::Normal](crate::Aov::Normal)
+    /// * `albedo`: The albedos of the first hits, like [Aov::Albedo](crate::Aov::Albedo)
+    ///
+    /// returns: Film
+    ///     The denoised colors, with the alpha and AOVs of `color`.
+    ///     Without guides, the filter can only tell edges apart by their color and blurs more of them.
+    ///
+    /// # Panics
+    ///
+    /// If the guides don't have the size of the film.
+    pub fn denoise(&self, color: &Film, normal: Option<&Film>, albedo: Option<&Film>) -> Film {
+        let (width, height) = (color.width(), color.height());
+        for guide in [normal, albedo].into_iter().flatten() {
+            assert_eq!((guide.width(), guide.height()), (width, height), "the guides of the denoiser need the size of the film");
+        }
+        let guide = |film: Option<&Film>| (0..width * height)
+            .map(|i| film.map_or(Vector3::zeros(), |film| film.get(i % width, i / width)))
+            .collect::<Vec<_>>();
+        let normals = guide(normal);
+        let albedos = guide(albedo);
+        // the light that arrives at the surfaces, without their texture
+        let mut irradiance = (0..width * height)
+            .map(|i| demodulate(color.get(i % width, i / width), albedos[i]))
+            .collect::<Vec<_>>();
+        for iteration in 0..self.iterations {
+            let guides = Guides { width, height, normals: &normals, albedos: &albedos, irradiance: &irradiance };
+            irradiance = self.iteration(&guides, 1 << iteration, self.color_sigma / (1 << iteration) as f64);
+        }
+        let mut denoised = color.clone();
+        for (i, irradiance) in irradiance.into_iter().enumerate() {
+            denoised.set(i % width, i / width, remodulate(irradiance, albedos[i]));
+        }
+        denoised
+    }
+    /// one pass of the filter, with the taps `step` pixels apart
+    fn iteration(&self, guides: &Guides, step: usize, color_sigma: f64) -> Vec<Vector3> {
+        let threads = thread::available_parallelism().map_or(1, |threads| threads.get());
+        let rows_per_thread = guides.height.div_ceil(threads).max(1);
+        thread::scope(|scope| {
+            (0..guides.height).step_by(rows_per_thread)
+                .map(|start| scope.spawn(move || {
+                    (start..(start + rows_per_thread).min(guides.height))
+                        .flat_map(|y| (0..guides.width).map(move |x| (x, y)))
+                        .map(|(x, y)| self.filter_pixel(guides, x, y, step, color_sigma))
+                        .collect::<Vec<_>>()
+                }))
+                .collect::<Vec<_>>().into_iter()
+                .flat_map(|thread| thread.join().unwrap())
+                .collect()
+        })
+    }
+    fn filter_pixel(&self, guides: &Guides, x: usize, y: usize, step: usize, color_sigma: f64) -> Vector3 {
+        let center = y * guides.width + x;
+        let center_color = compress(guides.irradiance[center]);
+        let mut sum = Vector3::zeros();
+        let mut total_weight = 0.0;
+        for (dy, ky) in KERNEL.iter().enumerate() {
+            for (dx, kx) in KERNEL.iter().enumerate() {
+                let (Some(qx), Some(qy)) = (offset(x, dx, step, guides.width), offset(y, dy, step, guides.height)) else {
+                    continue;
+                };
+                let q = qy * guides.width + qx;
+                let weight = kx * ky
+                    * edge_stop(center_color - compress(guides.irradiance[q]), color_sigma)
+                    * edge_stop(guides.normals[center] - guides.normals[q], self.normal_sigma)
+                    * edge_stop(guides.albedos[center] - guides.albedos[q], self.albedo_sigma);
+                sum += guides.irradiance[q] * weight;
+                total_weight += weight;
+            }
+        }
+        // the center always has a weight, so this is never 0
+        sum / total_weight
+    }
+}
+impl Film {
+    /// Denoises the film, guided by its [Aov::Normal](crate::Aov::Normal) and [Aov::Albedo](crate::Aov::Albedo) buffers if it has them.
+    /// See [Denoiser::denoise]
+    pub fn denoised(&self, denoiser: &Denoiser) -> Film {
+        denoiser.denoise(self, self.aov(crate::Aov::Normal), self.aov(crate::Aov::Albedo))
+    }
+}
+/// the buffers that one iteration reads
+struct Guides<'a> {
+    width: usize,
+    height: usize,
+    normals: &'a [Vector3],
+    albedos: &'a [Vector3],
+    irradiance: &'a [Vector3],
+}
+/// the coordinate of a tap of the kernel, if it's inside the image
+fn offset(center: usize, tap: usize, step: usize, size: usize) -> Option<usize> {
+    (center + tap * step).checked_sub(2 * step).filter(|&coordinate| coordinate < size)
+}
+fn edge_stop(difference: Vector3, sigma: f64) -> f64 {
+    (-difference.dot(difference) / (sigma * sigma)).exp()
+}
+/// brings bright colors closer together, so the color sigma works for any brightness
+fn compress(color: Vector3) -> Vector3 {
+    Vector3::new(color.x / (1.0 + color.x), color.y / (1.0 + color.y), color.z / (1.0 + color.z))
+}
+/// divides the albedo out of a color, where there is one
+fn demodulate(color: Vector3, albedo: Vector3) -> Vector3 {
+    let divide = |color: f64, albedo: f64| if albedo > 1e-3 { color / albedo } else { color };
+    Vector3::new(divide(color.x, albedo.x), divide(color.y, albedo.y), divide(color.z, albedo.z))
+}
+fn remodulate(irradiance: Vector3, albedo: Vector3) -> Vector3 {
+    let multiply = |irradiance: f64, albedo: f64| if albedo > 1e-3 { irradiance * albedo } else { irradiance };
+    Vector3::new(multiply(irradiance.x, albedo.x), multiply(irradiance.y, albedo.y), multiply(irradiance.z, albedo.z))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// a film with noise around 0.5 on the left and 0.1 on the right half, whose normals differ at the edge
+    fn noisy_halves() -> (Film, Film) {
+        let (mut color, mut normal) = (Film::new(32, 16), Film::new(32, 16));
+        fastrand::seed(7);
+        for y in 0..16 {
+            for x in 0..32 {
+                let (mean, side) = if x < 16 { (0.5, Vector3::x()) } else { (0.1, Vector3::z()) };
+                color.set(x, y, Vector3::ones() * mean * (0.5 + fastrand::f64()));
+                normal.set(x, y, side);
+            }
+        }
+        (color, normal)
+    }
+    fn variance(film: &Film, xs: std::ops::Range<usize>) -> f64 {
+        let values = xs.flat_map(|x| (0..film.height()).map(move |y| (x, y))).map(|(x, y)| film.get(x, y).x).collect::<Vec<_>>();
+        let mean = values.iter().sum::<f64>() / values.len() as f64;
+        values.iter().map(|value| (value - mean).powi(2)).sum::<f64>() / values.len() as f64
+    }
+    #[test]
+    fn removes_noise_but_keeps_edges() {
+        let (color, normal) = noisy_halves();
+        let denoised = Denoiser::default().denoise(&color, Some(&normal), None);
+        assert!(variance(&denoised, 0..16) < variance(&color, 0..16) / 10.0);
+        // the pixels next to the edge don't take on the brightness of the other side
+        let (left, right) = (denoised.get(15, 8).x, denoised.get(16, 8).x);
+        assert!((left - 0.5).abs() < 0.05 && (right - 0.1).abs() < 0.02, "{left} {right}");
+    }
+    #[test]
+    fn constant_films_stay_the_same() {
+        let mut film = Film::new(8, 8);
+        for y in 0..8 {
+            for x in 0..8 {
+                film.set(x, y, Vector3::new(3.0, 0.5, 0.0));
+            }
+        }
+        let denoised = Denoiser::default().denoise(&film, None, Some(&film));
+        assert!((0..64).all(|i| (denoised.get(i % 8, i / 8) - Vector3::new(3.0, 0.5, 0.0)).len() < 1e-6));
+    }
+}
@@ -0,0 +1,307 @@
+//! The light that comes from far away, seen by every ray that doesn't hit an object.
+//!
+//! Directions are mapped to the environment with `z` pointing up.
+pub mod sky;
+
+use crate::math::Vector3;
+use crate::raytracing::bsdf::luminance;
+use sky::Sky;
+use std::f64::consts::PI;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+/// What rays see when they don't hit any object.
+///
+/// # Examples
+///
+/// ```
+/// use rtx::environment::Environment;
+/// use rtx::math::Vector3;
+/// use rtx::Scene;
+///
+/// let mut scene = Scene::default();
+/// scene.environment = Environment::Gradient { bottom: Vector3::ones() * 0.1, top: Vector3::new(0.4, 0.6, 1.0) };
+/// assert_eq!(scene.environment.radiance(Vector3::z()), Vector3::new(0.4, 0.6, 1.0));
+/// ```
+#[derive(Clone, Debug)]
+pub enum Environment {
+    /// The same color in every direction
+    Color(Vector3),
+    /// Blends linearly from `bottom` (straight down) to `top` (straight up)
+    Gradient {
+        bottom: Vector3,
+        top: Vector3,
+    },
+    /// An equirectangular image, importance sampled by its luminance
+    Map(Arc<EnvironmentMap>),
+    /// An analytic daylight sky, whose sun is sampled explicitly
+    Sky(Box<Sky>),
+}
+impl Default for Environment {
+    /// a black environment, that doesn't emit any light
+    fn default() -> Self {
+        Self::Color(Vector3::zeros())
+    }
+}
+/// A direction that was sampled from an [Environment]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) struct EnvironmentSample {
+    pub direction: Vector3,
+    pub radiance: Vector3,
+    /// The probability density of sampling the direction, in solid angle
+    pub pdf: f64,
+}
+impl Environment {
+    /// Calculates the light coming from a direction.
+    ///
+    /// # Arguments
+    ///
+    /// * `direction`: The normalized direction the light comes from, pointing away from the scene.
+    ///
+    /// returns: Vector3
+    pub fn radiance(&self, direction: Vector3) -> Vector3 {
+        match self {
+            Environment::Color(color) => *color,
+            Environment::Gradient { bottom, top } => {
+                let t = (direction.z + 1.0) / 2.0;
+                *bottom * (1.0 - t) + *top * t
+            }
+            Environment::Map(map) => map.radiance(direction),
+            Environment::Sky(sky) => sky.radiance(direction),
+        }
+    }
+    /// whether no light comes from the environment at all
+    pub(crate) fn is_black(&self) -> bool {
+        match self {
+            Environment::Color(color) => *color == Vector3::zeros(),
+            Environment::Gradient { bottom, top } => *bottom == Vector3::zeros() && *top == Vector3::zeros(),
+            Environment::Map(map) => map.total_weight() <= 0.0 || map.intensity == 0.0,
+            Environment::Sky(sky) => sky.intensity == 0.0,
+        }
+    }
+    /// Picks a direction, proportional to the brightness of the environment.
+    /// Only maps and the sun of skies are sampled, everything else is smooth enough to be found by bsdf sampling.
+    pub(crate) fn sample(&self, u: (f64, f64)) -> Option<EnvironmentSample> {
+        match self {
+            Environment::Map(map) => map.sample(u),
+            Environment::Sky(sky) => sky.sample_sun(u)
+                .map(|(direction, radiance, pdf)| EnvironmentSample { direction, radiance, pdf }),
+            _ => None,
+        }
+    }
+    /// returns the probability density of [Environment::sample] returning the direction, in solid angle
+    pub(crate) fn pdf(&self, direction: Vector3) -> f64 {
+        match self {
+            Environment::Map(map) => map.pdf(direction),
+            Environment::Sky(sky) => sky.sun_pdf(direction),
+            _ => 0.0,
+        }
+    }
+}
+
+/// An equirectangular (latitude/longitude) image of the surroundings.
+///
+/// The top row is straight up (`+z`), the center column looks along `+x`.
+/// Construction builds the luminance CDF that is used for importance sampling.
+#[derive(Clone, Debug)]
+pub struct EnvironmentMap {
+    width: usize,
+    height: usize,
+    /// The linear colors, row by row
+    pixels: Vec<[f32; 3]>,
+    /// The factor all colors are multiplied with
+    pub intensity: f64,
+    /// The file the map was loaded from
+    path: Option<PathBuf>,
+    /// The summed up sampling weights of the rows
+    row_cdf: Vec<f64>,
+    /// The summed up sampling weights of the pixels within each row
+    pixel_cdf: Vec<f64>,
+}
+impl EnvironmentMap {
+    /// Creates a new environment map.
+    ///
+    /// # Arguments
+    ///
+    /// * `width`: The width of the image in pixels.
+    /// * `height`: The height of the image in pixels.
+    /// * `pixels`: The linear colors, row by row, starting at the top.
+    ///
+    /// returns: EnvironmentMap
+    ///
+    /// # Panics
+    ///
+    /// If the number of pixels doesn't match the size or the image is empty.
+    pub fn new(width: usize, height: usize, pixels: Vec<[f32; 3]>) -> Self {
+        assert!(width > 0 && height > 0, "environment maps can't be empty");
+        assert_eq!(pixels.len(), width * height, "expected {width}x{height} pixels");
+        let mut row_cdf = Vec::with_capacity(height);
+        let mut pixel_cdf = Vec::with_capacity(width * height);
+        let mut total = 0.0;
+        for (y, row) in pixels.chunks_exact(width).enumerate() {
+            // rows near the poles cover a smaller solid angle
+            let sin_theta = (PI * (y as f64 + 0.5) / height as f64).sin();
+            let mut row_total = 0.0;
+            for pixel in row {
+                row_total += luminance(to_vector(*pixel)).max(0.0) * sin_theta;
+                pixel_cdf.push(row_total);
+            }
+            total += row_total;
+            row_cdf.push(total);
+        }
+        Self { width, height, pixels, intensity: 1.0, path: None, row_cdf, pixel_cdf }
+    }
+    /// Loads an equirectangular image, e.g. a Radiance `.hdr` or OpenEXR file. Requires the `images` feature
+    #[cfg(feature = "images")]
+    pub fn load(path: impl Into<PathBuf>) -> image::ImageResult<Self> {
+        let path = path.into();
+        let image = image::open(&path)?.into_rgb32f();
+        let mut map = Self::new(image.width() as usize, image.height() as usize, image.pixels().map(|pixel| pixel.0).collect());
+        map.path = Some(path);
+        Ok(map)
+    }
+    /// multiplies all colors of the map by `intensity`
+    pub fn with_intensity(mut self, intensity: f64) -> Self {
+        self.intensity = intensity;
+        self
+    }
+    pub fn width(&self) -> usize {
+        self.width
+    }
+    pub fn height(&self) -> usize {
+        self.height
+    }
+    /// the linear colors, row by row, starting at the top
+    pub fn pixels(&self) -> &[[f32; 3]] {
+        &self.pixels
+    }
+    /// the file the map was loaded from, if it was loaded with [EnvironmentMap::load]
+    pub fn path(&self) -> Option<&PathBuf> {
+        self.path.as_ref()
+    }
+    /// returns the light coming from a direction
+    pub fn radiance(&self, direction: Vector3) -> Vector3 {
+        let (x, y) = self.pixel_at(direction_to_uv(direction));
+        to_vector(self.pixels[y * self.width + x]) * self.intensity
+    }
+    /// the summed up sampling weights of all pixels
+    pub(crate) fn total_weight(&self) -> f64 {
+        self.row_cdf.last().copied().unwrap_or(0.0)
+    }
+    /// the summed up sampling weights of the rows
+    #[cfg(feature = "gpu")]
+    pub(crate) fn row_cdf(&self) -> &[f64] {
+        &self.row_cdf
+    }
+    /// the summed up sampling weights of the pixels within each row
+    #[cfg(feature = "gpu")]
+    pub(crate) fn pixel_cdf(&self) -> &[f64] {
+        &self.pixel_cdf
+    }
+    fn pixel_at(&self, (u, v): (f64, f64)) -> (usize, usize) {
+        (
+            ((u * self.width as f64) as usize).min(self.width - 1),
+            ((v * self.height as f64) as usize).min(self.height - 1),
+        )
+    }
+    fn weight(&self, x: usize, y: usize) -> f64 {
+        let index = y * self.width + x;
+        if x == 0 { self.pixel_cdf[index] } else { self.pixel_cdf[index] - self.pixel_cdf[index - 1] }
+    }
+    fn sample(&self, u: (f64, f64)) -> Option<EnvironmentSample> {
+        let total = self.total_weight();
+        if total <= 0.0 {
+            return None;
+        }
+        let (y, v_offset) = sample_cdf(&self.row_cdf, u.0);
+        let (x, u_offset) = sample_cdf(&self.pixel_cdf[y * self.width..(y + 1) * self.width], u.1);
+        let uv = ((x as f64 + u_offset) / self.width as f64, (y as f64 + v_offset) / self.height as f64);
+        let direction = uv_to_direction(uv);
+        let pdf = self.pdf_at(x, y, uv.1);
+        if pdf <= 0.0 {
+            return None;
+        }
+        Some(EnvironmentSample { direction, radiance: to_vector(self.pixels[y * self.width + x]) * self.intensity, pdf })
+    }
+    fn pdf(&self, direction: Vector3) -> f64 {
+        let uv = direction_to_uv(direction);
+        let (x, y) = self.pixel_at(uv);
+        self.pdf_at(x, y, uv.1)
+    }
+    /// the pdf in solid angle of a point within a pixel
+    fn pdf_at(&self, x: usize, y: usize, v: f64) -> f64 {
+        let total = self.total_weight();
+        let sin_theta = (PI * v).sin();
+        if total <= 0.0 || sin_theta <= 0.0 {
+            return 0.0;
+        }
+        // constant within the pixel in image space, then converted by the area the pixel covers on the sphere
+        let pdf_uv = self.weight(x, y) / total * (self.width * self.height) as f64;
+        pdf_uv / (2.0 * PI * PI * sin_theta)
+    }
+}
+/// Picks an entry of a summed up list of weights.
+///
+/// returns: (usize, f64)
+///     The index and where within the entry `u` landed, from 0 to 1
+pub(crate) fn sample_cdf(cdf: &[f64], u: f64) -> (usize, f64) {
+    let total = cdf[cdf.len() - 1];
+    let target = u * total;
+    let index = cdf.partition_point(|&sum| sum <= target).min(cdf.len() - 1);
+    let start = if index == 0 { 0.0 } else { cdf[index - 1] };
+    let weight = cdf[index] - start;
+    let offset = if weight > 0.0 { ((target - start) / weight).clamp(0.0, 1.0) } else { 0.5 };
+    (index, offset)
+}
+/// maps a direction to the position in an equirectangular image, both coordinates from 0 to 1
+pub(crate) fn direction_to_uv(direction: Vector3) -> (f64, f64) {
+    let u = 0.5 + direction.y.atan2(direction.x) / (2.0 * PI);
+    let v = direction.z.clamp(-1.0, 1.0).acos() / PI;
+    (u, v)
+}
+/// the inverse of [direction_to_uv]
+pub(crate) fn uv_to_direction((u, v): (f64, f64)) -> Vector3 {
+    let phi = (u - 0.5) * 2.0 * PI;
+    let theta = v * PI;
+    Vector3::new(theta.sin() * phi.cos(), theta.sin() * phi.sin(), theta.cos())
+}
+fn to_vector(pixel: [f32; 3]) -> Vector3 {
+    Vector3::new(pixel[0], pixel[1], pixel[2])
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// a dark map with one bright pixel
+    fn sun_map() -> EnvironmentMap {
+        let mut pixels = vec![[0.1f32; 3]; 32 * 16];
+        pixels[4 * 32 + 20] = [1000.0; 3];
+        EnvironmentMap::new(32, 16, pixels)
+    }
+    #[test]
+    fn sampling_matches_pdf() {
+        let map = sun_map();
+        let mut hit_sun = 0;
+        let mut rng = fastrand::Rng::with_seed(11);
+        for _ in 0..1000 {
+            let sample = map.sample((rng.f64(), rng.f64())).unwrap();
+            assert!((sample.pdf - map.pdf(sample.direction)).abs() < 1e-6 * sample.pdf);
+            assert!((sample.direction.len() - 1.0).abs() < 1e-12);
+            if sample.radiance.x > 1.0 {
+                hit_sun += 1;
+            }
+        }
+        assert!(hit_sun > 900, "the sun was only sampled {hit_sun} times");
+    }
+    #[test]
+    fn pdf_integrates_to_one() {
+        let map = sun_map();
+        // midpoint rule over the sphere, with a few cells per pixel so the bright pixel doesn't dominate the error
+        let (columns, rows) = (32 * 8, 16 * 8);
+        let integral = (0..rows)
+            .flat_map(|row| (0..columns).map(move |column| ((column as f64 + 0.5) / columns as f64, (row as f64 + 0.5) / rows as f64)))
+            .map(|(u, v)| map.pdf(uv_to_direction((u, v))) * (v * PI).sin())
+            .sum::<f64>() * 2.0 * PI * PI / (columns * rows) as f64;
+        assert!((integral - 1.0).abs() < 0.01, "{integral}");
+    }
+}
@@ -0,0 +1,253 @@
+use crate::math::Vector3;
+use crate::raytracing::bsdf::orthonormal_basis;
+use std::f64::consts::PI;
+
+/// Converts the luminance of the model (in kcd/m²) to the units of the renderer
+const LUMINANCE_SCALE: f64 = 0.05;
+/// The illuminance of the sun outside of the atmosphere, in klx
+const EXTRATERRESTRIAL_SUN: f64 = 128.0;
+/// Half the apparent diameter of the sun, in radians
+pub const SUN_ANGULAR_RADIUS: f64 = 0.004625;
+
+/// An analytic daylight sky (Preetham et al. 1999) with a sun disk.
+///
+/// Directions use `z` as up, `x` as east and `y` as north. Below the horizon a diffuse ground is seen.
+/// The coefficients of the model are calculated once in [Sky::new], so the CPU and the GPU evaluate exactly the same formulas.
+///
+/// # Examples
+///
+/// ```
+/// use rtx::environment::{sky::Sky, Environment};
+/// use rtx::math::Vector3;
+/// use rtx::Scene;
+///
+/// // the afternoon sun in Berlin in June
+/// let sun = Sky::sun_direction_at(52.5, 172, 16.0);
+/// let mut scene = Scene::default();
+/// scene.environment = Environment::Sky(Box::new(Sky::new(sun, 3.0, Vector3::ones() * 0.2)));
+/// ```
+#[derive(Clone, Debug, PartialEq)]
+pub struct Sky {
+    /// The direction towards the sun, as it was given
+    sun_direction: Vector3,
+    /// The normalized direction towards the sun
+    sun: Vector3,
+    turbidity: f64,
+    ground_albedo: Vector3,
+    /// The factor the sky and the sun are multiplied with
+    pub intensity: f64,
+    /// The distributions of the luminance and the two chromaticities of the sky (the Perez coefficients A to E)
+    perez: [Vector3; 5],
+    /// The luminance and the chromaticities at the zenith, divided by the distribution at the zenith
+    zenith: Vector3,
+    /// The radiance of the sun disk
+    sun_radiance: Vector3,
+    /// The radiance of the ground
+    ground_radiance: Vector3,
+}
+impl Sky {
+    /// Creates a new sky.
+    ///
+    /// # Arguments
+    ///
+    /// * `sun_direction`: The direction towards the sun, doesn't have to be normalized.
+    /// * `turbidity`: How hazy the air is, from 2 (very clear) to 10 (hazy).
+    /// * `ground_albedo`: The color of the ground below the horizon.
+    ///
+    /// returns: Sky
+    pub fn new(sun_direction: Vector3, turbidity: f64, ground_albedo: Vector3) -> Self {
+        let sun = sun_direction.norm();
+        let t = turbidity;
+        let perez = [
+            Vector3::new(0.1787 * t - 1.4630, -0.0193 * t - 0.2592, -0.0167 * t - 0.2608),
+            Vector3::new(-0.3554 * t + 0.4275, -0.0665 * t + 0.0008, -0.0950 * t + 0.0092),
+            Vector3::new(-0.0227 * t + 5.3251, -0.0004 * t + 0.2125, -0.0079 * t + 0.2102),
+            Vector3::new(0.1206 * t - 2.5771, -0.0641 * t - 0.8989, -0.0441 * t - 1.6537),
+            Vector3::new(-0.0670 * t + 0.3703, -0.0033 * t + 0.0452, -0.0109 * t + 0.0529),
+        ];
+        // the model only holds for a sun above the horizon
+        let theta_sun = sun.z.clamp(0.0, 1.0).acos().min(PI / 2.0 - 1e-3);
+        let chi = (4.0 / 9.0 - t / 120.0) * (PI - 2.0 * theta_sun);
+        let zenith_luminance = (4.0453 * t - 4.9710) * chi.tan() - 0.2155 * t + 2.4192;
+        let (t2, th, th2, th3) = (t * t, theta_sun, theta_sun * theta_sun, theta_sun.powi(3));
+        let zenith_x = t2 * (0.00166 * th3 - 0.00375 * th2 + 0.00209 * th)
+            + t * (-0.02903 * th3 + 0.06377 * th2 - 0.03202 * th + 0.00394)
+            + (0.11693 * th3 - 0.21196 * th2 + 0.06052 * th + 0.25886);
+        let zenith_y = t2 * (0.00275 * th3 - 0.00610 * th2 + 0.00317 * th)
+            + t * (-0.04214 * th3 + 0.08970 * th2 - 0.04153 * th + 0.00516)
+            + (0.15346 * th3 - 0.26756 * th2 + 0.06670 * th + 0.26688);
+        let zenith = Vector3::new(zenith_luminance, zenith_x, zenith_y) / perez_distribution(&perez, 1.0, theta_sun.cos());
+
+        let mut sky = Self {
+            sun_direction,
+            sun,
+            turbidity,
+            ground_albedo,
+            intensity: 1.0,
+            perez,
+            zenith,
+            sun_radiance: Vector3::zeros(),
+            ground_radiance: Vector3::zeros(),
+        };
+        let sun_irradiance = sun_transmittance(sun.z, turbidity) * (EXTRATERRESTRIAL_SUN * LUMINANCE_SCALE);
+        sky.sun_radiance = sun_irradiance / sun_solid_angle();
+        // the ground is lit by the sun and (roughly) by a uniform sky as bright as the zenith
+        sky.ground_radiance = ground_albedo * (sky.sky_radiance(Vector3::z()) + sun_irradiance * sun.z.max(0.0) / PI);
+        sky
+    }
+    /// multiplies the light of the sky and the sun by `intensity`
+    pub fn with_intensity(mut self, intensity: f64) -> Self {
+        self.intensity = intensity;
+        self
+    }
+    /// Calculates the direction towards the sun.
+    ///
+    /// # Arguments
+    ///
+    /// * `latitude`: The latitude of the place in degrees, positive in the north.
+    /// * `day_of_year`: The day of the year, from 1 to 365.
+    /// * `solar_time`: The local solar time in hours, 12 is noon.
+    ///
+    /// returns: Vector3
+    ///     The normalized direction, with `x` pointing east, `y` north and `z` up.
+    pub fn sun_direction_at(latitude: f64, day_of_year: u32, solar_time: f64) -> Vector3 {
+        let latitude = latitude.to_radians();
+        let declination = 23.44f64.to_radians() * (2.0 * PI * (284.0 + day_of_year as f64) / 365.0).sin();
+        let hour_angle = (15.0 * (solar_time - 12.0)).to_radians();
+        Vector3::new(
+            -declination.cos() * hour_angle.sin(),
+            latitude.cos() * declination.sin() - latitude.sin() * declination.cos() * hour_angle.cos(),
+            latitude.sin() * declination.sin() + latitude.cos() * declination.cos() * hour_angle.cos(),
+        ).norm()
+    }
+    /// the direction towards the sun, as it was given to [Sky::new]
+    pub fn sun_direction(&self) -> Vector3 {
+        self.sun_direction
+    }
+    pub fn turbidity(&self) -> f64 {
+        self.turbidity
+    }
+    pub fn ground_albedo(&self) -> Vector3 {
+        self.ground_albedo
+    }
+    /// returns the light coming from a direction, the sun disk included
+    pub fn radiance(&self, direction: Vector3) -> Vector3 {
+        let radiance = if direction.z < 0.0 {
+            self.ground_radiance
+        } else if self.sun_radiance != Vector3::zeros() && direction.dot(self.sun) >= SUN_ANGULAR_RADIUS.cos() {
+            self.sky_radiance(direction) + self.sun_radiance
+        } else {
+            self.sky_radiance(direction)
+        };
+        radiance * self.intensity
+    }
+    /// Picks a direction within the sun disk.
+    ///
+    /// returns: Option<(Vector3, Vector3, f64)>
+    ///     The direction, the radiance from it and the pdf in solid angle. [None] if the sun has set.
+    pub(crate) fn sample_sun(&self, u: (f64, f64)) -> Option<(Vector3, Vector3, f64)> {
+        if self.sun_radiance == Vector3::zeros() {
+            return None;
+        }
+        let cos_max = SUN_ANGULAR_RADIUS.cos();
+        let cos_theta = 1.0 - u.0 * (1.0 - cos_max);
+        let sin_theta = (1.0 - cos_theta * cos_theta).max(0.0).sqrt();
+        let phi = 2.0 * PI * u.1;
+        let (tangent, bitangent) = orthonormal_basis(self.sun);
+        let direction = tangent * (sin_theta * phi.cos()) + bitangent * (sin_theta * phi.sin()) + self.sun * cos_theta;
+        Some((direction, self.radiance(direction), 1.0 / sun_solid_angle()))
+    }
+    /// returns the probability density of [Sky::sample_sun] returning the direction, in solid angle
+    pub(crate) fn sun_pdf(&self, direction: Vector3) -> f64 {
+        if self.sun_radiance == Vector3::zeros() || direction.dot(self.sun) < SUN_ANGULAR_RADIUS.cos() {
+            return 0.0;
+        }
+        1.0 / sun_solid_angle()
+    }
+    /// the radiance of the sky above the horizon, without the sun
+    fn sky_radiance(&self, direction: Vector3) -> Vector3 {
+        let cos_gamma = direction.dot(self.sun).clamp(-1.0, 1.0);
+        let yxy = self.zenith * perez_distribution(&self.perez, direction.z, cos_gamma);
+        yxy_to_rgb(yxy) * LUMINANCE_SCALE
+    }
+    /// The coefficients for the shader
+    ///
+    /// returns: ([Vector3; 5], Vector3, Vector3, Vector3)
+    ///     The Perez coefficients, the zenith values, the radiance of the sun and of the ground
+    #[cfg(feature = "gpu")]
+    pub(crate) fn coefficients(&self) -> ([Vector3; 5], Vector3, Vector3, Vector3) {
+        (self.perez, self.zenith, self.sun_radiance, self.ground_radiance)
+    }
+}
+/// the solid angle covered by the sun disk
+pub(crate) fn sun_solid_angle() -> f64 {
+    2.0 * PI * (1.0 - SUN_ANGULAR_RADIUS.cos())
+}
+/// The Perez distribution of the luminance and the chromaticities, relative to the zenith.
+///
+/// # Arguments
+///
+/// * `cos_theta`: The cosine between the direction and the zenith.
+/// * `cos_gamma`: The cosine between the direction and the sun.
+fn perez_distribution(perez: &[Vector3; 5], cos_theta: f64, cos_gamma: f64) -> Vector3 {
+    let [a, b, c, d, e] = *perez;
+    let gamma = cos_gamma.clamp(-1.0, 1.0).acos();
+    let cos_theta = cos_theta.max(1e-3);
+    let exp = |v: Vector3| Vector3::new(v.x.exp(), v.y.exp(), v.z.exp());
+    (Vector3::ones() + a * exp(b / cos_theta)) * (Vector3::ones() + c * exp(d * gamma) + e * (cos_gamma * cos_gamma))
+}
+/// converts a luminance and two chromaticities to linear sRGB
+fn yxy_to_rgb(yxy: Vector3) -> Vector3 {
+    let (luminance, x, y) = (yxy.x, yxy.y, yxy.z);
+    if y <= 0.0 {
+        return Vector3::zeros();
+    }
+    let (cx, cy, cz) = (x / y * luminance, luminance, (1.0 - x - y) / y * luminance);
+    Vector3::new(
+        3.2406 * cx - 1.5372 * cy - 0.4986 * cz,
+        -0.9689 * cx + 1.8758 * cy + 0.0415 * cz,
+        0.0557 * cx - 0.2040 * cy + 1.0570 * cz,
+    ).max(Vector3::zeros())
+}
+/// The fraction of the sunlight that makes it through the atmosphere, for red, green and blue.
+///
+/// Uses the Rayleigh and aerosol optical depths of Preetham et al. at 680, 550 and 440 nm.
+fn sun_transmittance(cos_zenith: f64, turbidity: f64) -> Vector3 {
+    if cos_zenith <= 0.0 {
+        return Vector3::zeros();
+    }
+    // the relative optical air mass (Kasten and Young)
+    let zenith_degrees = cos_zenith.acos().to_degrees();
+    let air_mass = 1.0 / (cos_zenith + 0.50572 * (96.07995 - zenith_degrees).powf(-1.6364));
+    let beta = 0.04608 * turbidity - 0.04586;
+    let depth = |wavelength: f64| 0.008735 * wavelength.powf(-4.08) + beta * wavelength.powf(-1.3);
+    Vector3::new(
+        (-air_mass * depth(0.68)).exp(),
+        (-air_mass * depth(0.55)).exp(),
+        (-air_mass * depth(0.44)).exp(),
+    )
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sky_is_blue_and_brightest_near_the_sun() {
+        let sky = Sky::new(Vector3::new(1.0, 0.0, 0.5), 3.0, Vector3::ones() * 0.3);
+        let zenith = sky.radiance(Vector3::z());
+        assert!(zenith.z > zenith.x, "{zenith}");
+        let near_sun = sky.radiance(Vector3::new(1.0, 0.0, 0.6).norm());
+        let away = sky.radiance(Vector3::new(-1.0, 0.0, 0.6).norm());
+        assert!(near_sun.y > away.y);
+        assert!(sky.radiance(sky.sun).y > 1000.0 * near_sun.y);
+    }
+    #[test]
+    fn sun_rises_in_the_east() {
+        let morning = Sky::sun_direction_at(48.0, 80, 7.0);
+        let noon = Sky::sun_direction_at(48.0, 80, 12.0);
+        assert!(morning.x > 0.5 && morning.z > 0.0);
+        // around the equinox, the noon sun stands at 90° minus the latitude, in the south
+        assert!((noon.z.asin().to_degrees() - 42.0).abs() < 1.0, "{noon}");
+        assert!(noon.y < 0.0);
+    }
+}
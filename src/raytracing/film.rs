@@ -0,0 +1,266 @@
+//! The result of a render: linear floating point colors, without any clipping.
+use crate::math::Vector3;
+use crate::raytracing::aov::Aov;
+#[cfg(feature = "images")]
+use image::{ImageBuffer, ImageResult, Rgb, Rgba};
+#[cfg(feature = "images")]
+use std::path::Path;
+
+/// An image of linear RGBA colors with 32 bit floats per channel.
+///
+/// The rows are stored from top to bottom, so `(0, 0)` is the top left pixel.
+/// Colors can be brighter than 1, they are only clipped when converting to 8 bits with [Film::to_rgb8].
+///
+/// # Examples
+///
+/// ```
+/// use rtx::Film;
+/// use rtx::math::Vector3;
+///
+/// let mut film = Film::new(2, 1);
+/// film.set(1, 0, Vector3::new(4.0, 0.5, 0.0));
+/// assert_eq!(film.get(1, 0), Vector3::new(4.0, 0.5, 0.0));
+/// ```
+#[derive(Clone, Debug, PartialEq)]
+pub struct Film {
+    width: usize,
+    height: usize,
+    /// The pixels, row by row
+    pixels: Vec<[f32; 4]>,
+    /// Extra buffers of the same size, see [Config::aovs](crate::Config::aovs)
+    aovs: Vec<(Aov, Film)>,
+}
+impl Film {
+    /// creates a new black film with an alpha of 1
+    pub fn new(width: usize, height: usize) -> Self {
+        Self { width, height, pixels: vec![[0.0, 0.0, 0.0, 1.0]; width * height], aovs: Vec::new() }
+    }
+    /// Creates a film from rows of colors.
+    ///
+    /// # Arguments
+    ///
+    /// * `rows`: The colors, indexed with `rows[y][x]`, from top to bottom.
+    ///
+    /// returns: Film
+    ///
+    /// # Panics
+    ///
+    /// If the rows have different lengths.
+    pub fn from_rows(rows: Vec<Vec<Vector3>>) -> Self {
+        let width = rows.first().map_or(0, Vec::len);
+        let height = rows.len();
+        assert!(rows.iter().all(|row| row.len() == width), "all rows of a film need the same length");
+        let pixels = rows.into_iter()
+            .flatten()
+            .map(|color| [color.x as f32, color.y as f32, color.z as f32, 1.0])
+            .collect();
+        Self { width, height, pixels, aovs: Vec::new() }
+    }
+    pub fn width(&self) -> usize {
+        self.width
+    }
+    pub fn height(&self) -> usize {
+        self.height
+    }
+    /// the pixels as RGBA, row by row from the top
+    pub fn pixels(&self) -> &[[f32; 4]] {
+        &self.pixels
+    }
+    /// the pixels as RGBA, row by row from the top
+    pub fn pixels_mut(&mut self) -> &mut [[f32; 4]] {
+        &mut self.pixels
+    }
+    /// returns the color of a pixel
+    pub fn get(&self, x: usize, y: usize) -> Vector3 {
+        let [r, g, b, _] = self.pixels[self.index(x, y)];
+        Vector3::new(r, g, b)
+    }
+    /// sets the color of a pixel and keeps its alpha
+    pub fn set(&mut self, x: usize, y: usize, color: Vector3) {
+        let index = self.index(x, y);
+        let alpha = self.pixels[index][3];
+        self.pixels[index] = [color.x as f32, color.y as f32, color.z as f32, alpha];
+    }
+    /// returns the buffer of an AOV, if it was rendered
+    pub fn aov(&self, aov: Aov) -> Option<&Film> {
+        self.aovs.iter().find(|(kind, _)| *kind == aov).map(|(_, film)| film)
+    }
+    /// all AOVs of the film, in the order they were added
+    pub fn aovs(&self) -> impl Iterator<Item = (Aov, &Film)> {
+        self.aovs.iter().map(|(aov, film)| (*aov, film))
+    }
+    /// Adds the buffer of an AOV, replacing an earlier one of the same kind.
+    ///
+    /// # Panics
+    ///
+    /// If the buffer doesn't have the size of the film.
+    pub fn set_aov(&mut self, aov: Aov, mut film: Film) {
+        assert_eq!((film.width, film.height), (self.width, self.height), "the buffer of {aov:?} has a different size than the film");
+        film.aovs.clear();
+        match self.aovs.iter_mut().find(|(kind, _)| *kind == aov) {
+            Some((_, existing)) => *existing = film,
+            None => self.aovs.push((aov, film)),
+        }
+    }
+    /// Colors the first channel of the film from dark blue for 0 over red to yellow for its largest value,
+    /// to see at a glance where a single channel like [Aov::SampleCount] is high.
+    pub fn heatmap(&self) -> Film {
+        let max = self.pixels.iter()
+            .map(|pixel| pixel[0])
+            .filter(|value| value.is_finite())
+            .fold(0f32, f32::max);
+        let mut heatmap = Film::new(self.width, self.height);
+        for (pixel, heat) in self.pixels.iter().zip(&mut heatmap.pixels) {
+            let t = if max > 0.0 { (pixel[0] / max).clamp(0.0, 1.0) } else { 0.0 };
+            let color = heatmap_color(t as f64);
+            *heat = [color.x as f32, color.y as f32, color.z as f32, 1.0];
+        }
+        heatmap
+    }
+    fn index(&self, x: usize, y: usize) -> usize {
+        assert!(x < self.width && y < self.height, "pixel ({x}, {y}) is outside of the {}x{} film", self.width, self.height);
+        y * self.width + x
+    }
+    /// Converts the film to 8 bit sRGB. Colors are clipped to `[0, 1]` before they are encoded.
+    /// Requires the `images` feature
+    #[cfg(feature = "images")]
+    pub fn to_rgb8(&self) -> ImageBuffer<Rgb<u8>, Vec<u8>> {
+        ImageBuffer::from_fn(self.width as u32, self.height as u32, |x, y| {
+            let [r, g, b, _] = self.pixels[y as usize * self.width + x as usize];
+            Rgb([encode_srgb8(r), encode_srgb8(g), encode_srgb8(b)])
+        })
+    }
+    /// Copies the film into a floating point image buffer. Requires the `images` feature
+    #[cfg(feature = "images")]
+    pub fn to_rgba32f(&self) -> ImageBuffer<Rgba<f32>, Vec<f32>> {
+        ImageBuffer::from_raw(self.width as u32, self.height as u32, self.pixels.iter().flatten().copied().collect())
+            .expect("the film has exactly width * height pixels")
+    }
+    /// Saves the film as an OpenEXR file with 32 bit floats, keeping all of its data. Requires the `images` feature
+    #[cfg(feature = "images")]
+    pub fn save_exr(&self, path: impl AsRef<Path>) -> ImageResult<()> {
+        self.to_rgba32f().save_with_format(path, image::ImageFormat::OpenExr)
+    }
+    /// Saves the film as a Radiance `.hdr` file. The format has no alpha and a shared exponent per pixel.
+    /// Requires the `images` feature
+    #[cfg(feature = "images")]
+    pub fn save_hdr(&self, path: impl AsRef<Path>) -> ImageResult<()> {
+        let rgb: ImageBuffer<Rgb<f32>, Vec<f32>> = ImageBuffer::from_raw(
+            self.width as u32,
+            self.height as u32,
+            self.pixels.iter().flat_map(|&[r, g, b, _]| [r, g, b]).collect(),
+        ).expect("the film has exactly width * height pixels");
+        rgb.save_with_format(path, image::ImageFormat::Hdr)
+    }
+    /// Saves the film and all of its AOVs as layers of one OpenEXR file.
+    ///
+    /// The color is the layer `color` with the channels `R`, `G`, `B` and `A`,
+    /// the AOVs are named after [Aov::name] and have the channels of [Aov::channels].
+    /// Requires the `images` feature
+    #[cfg(feature = "images")]
+    pub fn save_layered_exr(&self, path: impl AsRef<Path>) -> ImageResult<()> {
+        use exr::prelude::*;
+        let size = Vec2(self.width, self.height);
+        let layer = |name: &str, film: &Film, channels: &[&str]| Layer::new(
+            size,
+            LayerAttributes::named(name),
+            Encoding::SMALL_LOSSLESS,
+            AnyChannels::sort(channels.iter().enumerate()
+                .map(|(i, channel)| AnyChannel::new(*channel, FlatSamples::F32(film.pixels.iter().map(|pixel| pixel[i]).collect())))
+                .collect()),
+        );
+        let layers = std::iter::once(layer("color", self, &["R", "G", "B", "A"]))
+            .chain(self.aovs.iter().map(|(aov, film)| layer(aov.name(), film, aov.channels())))
+            .collect::<Vec<_>>();
+        Image::from_layers(ImageAttributes::new(IntegerBounds::from_dimensions(size)), layers)
+            .write()
+            .to_file(path)
+            .map_err(|error| match error {
+                Error::Io(error) => image::ImageError::IoError(error),
+                error => image::ImageError::Encoding(image::error::EncodingError::new(image::ImageFormat::OpenExr.into(), error)),
+            })
+    }
+}
+/// The linear colors of [Film::heatmap], evenly spaced from 0 to 1
+const HEATMAP_COLORS: [[f64; 3]; 5] = [
+    [0.0, 0.0, 0.05],
+    [0.05, 0.0, 0.4],
+    [0.6, 0.0, 0.2],
+    [1.0, 0.35, 0.0],
+    [1.0, 1.0, 0.3],
+];
+/// interpolates between the heatmap colors
+fn heatmap_color(t: f64) -> Vector3 {
+    let position = t * (HEATMAP_COLORS.len() - 1) as f64;
+    let index = (position as usize).min(HEATMAP_COLORS.len() - 2);
+    let (low, high) = (Vector3::from(HEATMAP_COLORS[index]), Vector3::from(HEATMAP_COLORS[index + 1]));
+    low + (high - low) * (position - index as f64)
+}
+/// Encodes a linear color channel with the sRGB transfer function.
+///
+/// Values outside of `[0, 1]` (and NaN) are clipped first, so bright colors saturate instead of wrapping around.
+pub fn encode_srgb8(linear: f32) -> u8 {
+    let linear = if linear.is_nan() { 0.0 } else { linear.clamp(0.0, 1.0) };
+    let encoded = if linear <= 0.0031308 {
+        linear * 12.92
+    } else {
+        1.055 * linear.powf(1.0 / 2.4) - 0.055
+    };
+    (encoded * 255.0 + 0.5) as u8
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn srgb_encoding_saturates() {
+        assert_eq!(encode_srgb8(0.0), 0);
+        assert_eq!(encode_srgb8(1.0), 255);
+        assert_eq!(encode_srgb8(1.2), 255);
+        assert_eq!(encode_srgb8(300.0), 255);
+        assert_eq!(encode_srgb8(-1.0), 0);
+        assert_eq!(encode_srgb8(f32::NAN), 0);
+        // middle gray
+        assert_eq!(encode_srgb8(0.2159), 128);
+    }
+    #[test]
+    fn heatmaps_scale_to_the_largest_value() {
+        let film = Film::from_rows(vec![vec![Vector3::zeros(), Vector3::ones() * 8.0, Vector3::ones() * 32.0]]);
+        let heatmap = film.heatmap();
+        for (x, color) in [(0, 0), (1, 1), (2, 4)] {
+            assert!((heatmap.get(x, 0) - Vector3::from(HEATMAP_COLORS[color])).len() < 1e-6);
+        }
+    }
+    #[cfg(feature = "images")]
+    #[test]
+    fn exr_keeps_bright_colors() {
+        let mut film = Film::new(3, 2);
+        film.set(2, 1, Vector3::new(1000.0, 0.25, 1e-3));
+        let path = std::env::temp_dir().join(format!("rtx-film-{}.exr", std::process::id()));
+        film.save_exr(&path).unwrap();
+        let loaded = image::open(&path).unwrap().into_rgba32f();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(loaded.dimensions(), (3, 2));
+        assert_eq!(loaded.get_pixel(2, 1).0, [1000.0, 0.25, 1e-3, 1.0]);
+    }
+    #[cfg(feature = "images")]
+    #[test]
+    fn layered_exr_has_a_layer_per_aov() {
+        let mut film = Film::new(2, 2);
+        let mut depth = Film::new(2, 2);
+        depth.set(1, 0, Vector3::ones() * 7.5);
+        film.set_aov(Aov::Depth, depth);
+        film.set_aov(Aov::Normal, Film::new(2, 2));
+        let path = std::env::temp_dir().join(format!("rtx-film-layers-{}.exr", std::process::id()));
+        film.save_layered_exr(&path).unwrap();
+        let loaded = exr::prelude::read_all_flat_layers_from_file(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        let names = loaded.layer_data.iter()
+            .map(|layer| layer.attributes.layer_name.as_ref().unwrap().to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(names, ["color", "depth", "normal"]);
+        let depth = &loaded.layer_data[1].channel_data.list[0];
+        assert_eq!(depth.name.to_string(), "Z");
+        assert_eq!(depth.sample_data.value_by_flat_index(1).to_f32(), 7.5);
+    }
+}
@@ -1,8 +1,26 @@
 const PI = acos(0.0) * 2.0;
+// how far rays are moved off a surface after hitting it, so they don't hit the same spot again
+const RAY_OFFSET = 1e-4;
+// the same constants as in `bsdf.rs`
+const MIN_ALPHA = 1e-3;
+const DIELECTRIC_F0 = 0.04;
+// the axes of camera space in world space and the lens, see `Camera::ray`
 struct Camera {
     pos: vec3<f32>,
-    dir: vec3<f32>,
-    fov: f32,
+    fov_axis: u32, // 0 horizontal, 1 vertical, 2 diagonal
+    right: vec3<f32>,
+    tan_half_fov: f32,
+    up: vec3<f32>,
+    aperture_radius: f32, // 0 without a lens
+    forward: vec3<f32>,
+    focus_distance: f32,
+    aperture_kind: u32, // 0 disk, 1 polygon, 2 image
+    blades: u32,
+    rotation: f32,
+    aperture_width: u32,
+    aperture_height: u32,
+    model: u32, // 0 perspective, 1 orthographic, 2 equidistant fisheye, 3 equisolid fisheye, 4 equirectangular, 5 cubemap
+    model_parameter: f32, // the width of orthographic cameras, the fov of fisheyes
 }
 struct Object {
     base_color: vec3<f32>,
@@ -10,8 +28,51 @@ struct Object {
     emission_color: vec3<f32>,
     object_id: u32, // placed here for easier alignement
     object_index: u32,
+    auxiliary_offset: u32,
+    ior: f32, // 0 for opaque objects
+    metallic: f32,
+    absorption: vec3<f32>,
     //vec3<f32> requires a 16 bit alignement, that's why those above are where they are.
 }
+// the same as `Light` in `light.rs`, serialized by its `GpuSerialize` implementation
+struct Light {
+    position: vec3<f32>,
+    kind: u32, // 0: unused, 1: point, 2: spot, 3: directional
+    direction: vec3<f32>,
+    cos_inner: f32,
+    radiant: vec3<f32>, // color * intensity
+    cos_outer: f32,
+    cos_cone: f32, // the cosine of half the angular diameter of directional lights
+}
+// the same as `Environment` in `environment.rs`
+struct Environment {
+    kind: u32, // 0: color, 1: gradient, 2: map, 3: sky
+    width: u32,
+    height: u32,
+    intensity: f32,
+    color: vec3<f32>, // the color, or the bottom of the gradient
+    top: vec3<f32>,
+    // the sky, the same as `Sky` in `sky.rs`
+    sun: vec3<f32>,
+    cos_sun_radius: f32,
+    sun_radiance: vec3<f32>,
+    sun_pdf: f32,
+    ground_radiance: vec3<f32>,
+    zenith: vec3<f32>,
+    perez: array<vec3<f32>, 5>,
+}
+// the same as `ToneMapping` in `tone_mapping.rs`
+struct ToneMapping {
+    kind: u32, // 0: none, 1: clamp, 2: reinhard, 3: extended reinhard, 4: aces, 5: agx
+    exposure: f32, // the factor, not the stops
+    white: f32,
+    encode_srgb: u32, // whether the target doesn't do the sRGB transfer function itself
+}
+// which frame is rendered, see `State::render`
+struct Frame {
+    index: u32, // counts all frames, so every frame gets other random numbers
+    accumulated: u32, // the frames in the accumulation texture, 0 starts it over
+}
 struct BoundingBox {
     has_box: bool,
     min: vec3<f32>,
@@ -20,9 +81,6 @@ struct BoundingBox {
 struct Config {
     rays_per_pixel: u32,
     max_bounces: u32,
-    focal_length: f32,
-    focal_offset: f32,
-    non_focal_offset: f32,
 }
 
 @group(0)
@@ -40,6 +98,40 @@ var<uniform> config: Config;
 @binding(3)
 var<storage, read> objects: array<Object>;
 
+@group(0)
+@binding(4)
+var<storage, read> auxiliary: array<vec4<f32>>;
+
+@group(0)
+@binding(5)
+var<storage, read> lights: array<Light>;
+
+@group(0)
+@binding(6)
+var<uniform> environment: Environment;
+@group(0)
+@binding(7)
+var environment_map: texture_2d<f32>;
+// the summed up sampling weights of the map: first the rows, then the pixels within each row
+@group(0)
+@binding(8)
+var<storage, read> environment_cdf: array<f32>;
+@group(0)
+@binding(9)
+var<uniform> tone_mapping: ToneMapping;
+@group(0)
+@binding(10)
+var<uniform> frame: Frame;
+// the average of the frames before this one, the other texture of the pair gets the new average.
+// The bits of 32 bit floats, since not every backend can render to float textures of that size
+@group(0)
+@binding(11)
+var accumulation: texture_2d<u32>;
+// the summed up brightness of the aperture image: first the rows, then the pixels within each row
+@group(0)
+@binding(12)
+var<storage, read> aperture_cdf: array<f32>;
+
 struct VertexOutput {
     @builtin(position) clip_position: vec4<f32>,
     @location(0) uv: vec2<f32>,
@@ -56,80 +148,291 @@ fn vs_main(@builtin(vertex_index) vertex_index: u32) -> VertexOutput {
     var position: vec2<f32> = positions[vertex_index];
     var output: VertexOutput;
     output.clip_position = vec4<f32>(position, 0.0, 1.0);
-    // Map clip space [-1, 1] to UV space [0, 1]:
-    output.uv = position * vec2<f32>(0.5, -0.5); // + vec2<f32>(0.5, 0.5);
+    // Map clip space [-1, 1] to UV space [0, 1], with (0, 0) in the bottom left corner like on the CPU
+    output.uv = position * 0.5 + 0.5;
     return output;
 }
 
 @fragment
 fn fs_main(input: VertexOutput) -> @location(0) vec4<f32> {
-    initRngSeed(input.uv);
-    let angle = input.uv * vec2<f32>(camera.fov, camera.fov / aspect_ratio);
-    let cam_space_dir: vec3<f32> = vec3<f32>(sin(angle), cos(angle.x) * cos(angle.y));
-
-    let cam_space_forward: vec3<f32> = normalize(camera.dir);
-    let cam_space_right: vec3<f32> = cross(cam_space_forward, vec3<f32>(0.0, 0.0, 1.0));
-    let cam_space_up: vec3<f32> = cross(cam_space_forward, cam_space_right);
-    let to_cam_space_mat: mat3x3<f32> = mat3x3<f32>(cam_space_right, cam_space_up, cam_space_forward);
-
-    let ray_dir = inverse3x3(to_cam_space_mat) * cam_space_dir;
-
-    let ray = Ray(camera.pos, ray_dir, vec3<f32>(1.0, 1.0, 1.0), vec3<f32>(0.0, 0.0, 0.0));
+    return vec4(apply_tone_mapping(render_pixel(input.uv, pixel_size(input.uv), vec2<u32>(input.clip_position.xy))), 1.0);
+}
+// the size of a pixel in UV space
+fn pixel_size(uv: vec2<f32>) -> vec2<f32> {
+    return abs(vec2(dpdx(uv).x, dpdy(uv).y));
+}
+struct AccumulationOutput {
+    @location(0) color: vec4<f32>,
+    @location(1) average: vec4<u32>,
+}
+// averages the new frame with the ones before, writes the average to the accumulation and tone maps it for the target
+@fragment
+fn fs_accumulate(input: VertexOutput) -> AccumulationOutput {
+    let color = render_pixel(input.uv, pixel_size(input.uv), vec2<u32>(input.clip_position.xy));
+    var average = color;
+    if frame.accumulated > 0u {
+        let previous = bitcast<vec3<f32>>(textureLoad(accumulation, vec2<i32>(input.clip_position.xy), 0).rgb);
+        average = previous + (color - previous) / f32(frame.accumulated + 1u);
+    }
+    return AccumulationOutput(vec4(apply_tone_mapping(average), 1.0), bitcast<vec4<u32>>(vec4(average, 1.0)));
+}
+// the average linear color of the rays through random points of a pixel, `pixel` is its integer position on the target
+fn render_pixel(uv: vec2<f32>, pixel_size: vec2<f32>, pixel: vec2<u32>) -> vec3<f32> {
+    initRngSeed(pixel, frame.index);
     var color: vec3<f32> = vec3(0.0, 0.0, 0.0);
-    let target_point = ray.position + ray.direction * config.focal_length;
     for (var i: u32 = 0u; i < config.rays_per_pixel; i++) {
-        var current_ray = ray;
-        let ray_target = target_point + random_direction() * config.focal_offset;
-        let ray_origin = ray.position + random_direction() * config.non_focal_offset;
-        current_ray.position = ray_origin;
-        current_ray.direction = normalize(ray_target - ray_origin);
-        color += trace_ray(current_ray);
-    }
-    color /= f32(config.rays_per_pixel);
-
-    return vec4(color, 1.0);
+        let offset = vec2(random_float(), random_float()) - 0.5;
+        let ray = camera_ray(uv + offset * pixel_size);
+        // points of the image without a ray stay black
+        if any(ray.light_color != vec3(0.0)) {
+            color += trace_ray(ray);
+        }
+    }
+    return color / f32(config.rays_per_pixel);
+}
+// the same as `Camera::film_size`
+fn film_size() -> vec2<f32> {
+    let extent = camera.tan_half_fov;
+    switch camera.fov_axis {
+        case 1u: {
+            return vec2(extent * aspect_ratio, extent);
+        }
+        case 2u: {
+            return extent * vec2(aspect_ratio, 1.0) / length(vec2(aspect_ratio, 1.0));
+        }
+        default: {
+            return vec2(extent, extent / aspect_ratio);
+        }
+    }
+}
+// the same as `Camera::ray` with the built-in models, rays without any light_color for points that the model doesn't see anything through
+fn camera_ray(uv: vec2<f32>) -> Ray {
+    // the lens is always sampled, so the random numbers don't depend on it
+    let lens_point = sample_aperture(vec2(random_float(), random_float())) * camera.aperture_radius;
+    switch camera.model {
+        case 1u: {
+            return orthographic_ray(uv);
+        }
+        case 2u, 3u: {
+            return fisheye_ray(uv);
+        }
+        case 4u: {
+            return equirectangular_ray(uv);
+        }
+        case 5u: {
+            return cubemap_ray(uv);
+        }
+        default: {
+            return perspective_ray(uv, lens_point);
+        }
+    }
+}
+// a ray from the camera position in a direction of camera space
+fn camera_space_ray(direction: vec3<f32>) -> Ray {
+    let world_direction = normalize(camera.right * direction.x + camera.up * direction.y + camera.forward * direction.z);
+    return Ray(camera.pos, world_direction, vec3<f32>(1.0, 1.0, 1.0), vec3<f32>(0.0, 0.0, 0.0));
+}
+// the same as `Orthographic::ray`
+fn orthographic_ray(uv: vec2<f32>) -> Ray {
+    let offset = vec2(uv.x - 0.5, (uv.y - 0.5) / aspect_ratio) * camera.model_parameter;
+    let position = camera.pos + camera.right * offset.x + camera.up * offset.y;
+    return Ray(position, camera.forward, vec3<f32>(1.0, 1.0, 1.0), vec3<f32>(0.0, 0.0, 0.0));
+}
+// the same as `Fisheye::ray`
+fn fisheye_ray(uv: vec2<f32>) -> Ray {
+    var point = uv * 2.0 - 1.0;
+    if aspect_ratio >= 1.0 {
+        point.x *= aspect_ratio;
+    } else {
+        point.y /= aspect_ratio;
+    }
+    let radius = length(point);
+    if radius > 1.0 {
+        return Ray(camera.pos, camera.forward, vec3<f32>(0.0, 0.0, 0.0), vec3<f32>(0.0, 0.0, 0.0));
+    }
+    var angle = radius * camera.model_parameter / 2.0;
+    if camera.model == 3u {
+        angle = 2.0 * asin(radius * sin(camera.model_parameter / 4.0));
+    }
+    var direction = vec2(0.0);
+    if radius > 0.0 {
+        direction = point / radius;
+    }
+    return camera_space_ray(vec3(direction * sin(angle), cos(angle)));
+}
+// the same as `Equirectangular::ray`
+fn equirectangular_ray(uv: vec2<f32>) -> Ray {
+    let longitude = (uv.x - 0.5) * 2.0 * PI;
+    let latitude = (uv.y - 0.5) * PI;
+    return camera_space_ray(vec3(cos(latitude) * sin(longitude), sin(latitude), cos(latitude) * cos(longitude)));
+}
+// the same as `Cubemap::ray`
+fn cubemap_ray(uv: vec2<f32>) -> Ray {
+    let scaled = uv.x * 6.0;
+    let face = u32(clamp(floor(scaled), 0.0, 5.0));
+    let point = vec2((scaled - f32(face)) * 2.0 - 1.0, uv.y * 2.0 - 1.0);
+    var direction: vec3<f32>;
+    switch face {
+        case 0u: {
+            direction = vec3(1.0, point.y, -point.x);
+        }
+        case 1u: {
+            direction = vec3(-1.0, point.y, point.x);
+        }
+        case 2u: {
+            direction = vec3(point.x, 1.0, -point.y);
+        }
+        case 3u: {
+            direction = vec3(point.x, -1.0, point.y);
+        }
+        case 4u: {
+            direction = vec3(point, 1.0);
+        }
+        default: {
+            direction = vec3(-point.x, point.y, -1.0);
+        }
+    }
+    return camera_space_ray(direction);
+}
+// the same as `Perspective::ray`
+fn perspective_ray(uv: vec2<f32>, lens_point: vec2<f32>) -> Ray {
+    let offset = (uv * 2.0 - 1.0) * film_size();
+    if camera.aperture_radius <= 0.0 {
+        let direction = normalize(camera.right * offset.x + camera.up * offset.y + camera.forward);
+        return Ray(camera.pos, direction, vec3<f32>(1.0, 1.0, 1.0), vec3<f32>(0.0, 0.0, 0.0));
+    }
+    // all rays through the same point of the image meet on the plane in focus
+    let direction = vec3(offset, 1.0) * camera.focus_distance - vec3(lens_point, 0.0);
+    let position = camera.pos + camera.right * lens_point.x + camera.up * lens_point.y;
+    let world_direction = normalize(camera.right * direction.x + camera.up * direction.y + camera.forward * direction.z);
+    return Ray(position, world_direction, vec3<f32>(1.0, 1.0, 1.0), vec3<f32>(0.0, 0.0, 0.0));
+}
+// a point in the square around the unit circle, the same as `Aperture::sample`
+fn sample_aperture(u: vec2<f32>) -> vec2<f32> {
+    switch camera.aperture_kind {
+        case 1u: {
+            let blades = f32(camera.blades);
+            let scaled = u.x * blades;
+            let blade = min(floor(scaled), blades - 1.0);
+            let start_angle = camera.rotation + 2.0 * PI * blade / blades;
+            let end_angle = camera.rotation + 2.0 * PI * (blade + 1.0) / blades;
+            let start = vec2(cos(start_angle), sin(start_angle));
+            let end = vec2(cos(end_angle), sin(end_angle));
+            return sqrt(scaled - blade) * (start + (end - start) * u.y);
+        }
+        case 2u: {
+            let row = sample_aperture_cdf(0u, camera.aperture_height, u.x);
+            let column = sample_aperture_cdf(camera.aperture_height + u32(row.x) * camera.aperture_width, camera.aperture_width, u.y);
+            let position = vec2((column.x + column.y) / f32(camera.aperture_width), (row.x + row.y) / f32(camera.aperture_height));
+            return vec2(position.x * 2.0 - 1.0, 1.0 - position.y * 2.0);
+        }
+        default: {
+            // the concentric mapping
+            let square = u * 2.0 - 1.0;
+            if all(square == vec2(0.0)) {
+                return vec2(0.0);
+            }
+            if abs(square.x) > abs(square.y) {
+                let angle = PI / 4.0 * (square.y / square.x);
+                return square.x * vec2(cos(angle), sin(angle));
+            }
+            let angle = PI / 2.0 - PI / 4.0 * (square.x / square.y);
+            return square.y * vec2(cos(angle), sin(angle));
+        }
+    }
+}
+// the same as `sample_environment_cdf`, for `aperture_cdf`
+fn sample_aperture_cdf(start: u32, count: u32, u: f32) -> vec2<f32> {
+    let target_sum = u * aperture_cdf[start + count - 1u];
+    var low = 0u;
+    var high = count - 1u;
+    while (low < high) {
+        let middle = (low + high) / 2u;
+        if (aperture_cdf[start + middle] <= target_sum) {
+            low = middle + 1u;
+        } else {
+            high = middle;
+        }
+    }
+    var previous = 0.0;
+    if (low > 0u) {
+        previous = aperture_cdf[start + low - 1u];
+    }
+    let weight = aperture_cdf[start + low] - previous;
+    var offset = 0.5;
+    if (weight > 0.0) {
+        offset = clamp((target_sum - previous) / weight, 0.0, 1.0);
+    }
+    return vec2<f32>(f32(low), offset);
+}
+// tone mapping, the same as in `tone_mapping.rs`
+fn apply_tone_mapping(linear: vec3<f32>) -> vec3<f32> {
+    if tone_mapping.kind == 0u {
+        return linear;
+    }
+    let color = max(linear * tone_mapping.exposure, vec3(0.0));
+    var mapped = color;
+    switch tone_mapping.kind {
+        case 2u: {
+            mapped = color / (1.0 + color);
+        }
+        case 3u: {
+            mapped = color * (1.0 + color / (tone_mapping.white * tone_mapping.white)) / (1.0 + color);
+        }
+        case 4u: {
+            mapped = aces(color);
+        }
+        case 5u: {
+            mapped = agx(color);
+        }
+        default: {}
+    }
+    mapped = clamp(mapped, vec3(0.0), vec3(1.0));
+    if tone_mapping.encode_srgb != 0u {
+        return select(1.055 * pow(mapped, vec3(1.0 / 2.4)) - 0.055, mapped * 12.92, mapped <= vec3(0.0031308));
+    }
+    return mapped;
+}
+// the matrices are written row by row, so they multiply from the right
+fn aces(color: vec3<f32>) -> vec3<f32> {
+    let input = mat3x3<f32>(
+        vec3(0.59719, 0.35458, 0.04823),
+        vec3(0.07600, 0.90834, 0.01566),
+        vec3(0.02840, 0.13383, 0.83777),
+    );
+    let output = mat3x3<f32>(
+        vec3(1.60475, -0.53108, -0.07367),
+        vec3(-0.10208, 1.10813, -0.00605),
+        vec3(-0.00327, -0.07276, 1.07602),
+    );
+    let v = color * input;
+    let fitted = (v * (v + 0.0245786) - 0.000090537) / (v * (0.983729 * v + 0.4329510) + 0.238081);
+    return fitted * output;
+}
+const AGX_MIN_EV = -12.47393;
+const AGX_MAX_EV = 4.026069;
+fn agx(color: vec3<f32>) -> vec3<f32> {
+    let inset = mat3x3<f32>(
+        vec3(0.842479062253094, 0.0784335999999992, 0.0792237451477643),
+        vec3(0.0423282422610123, 0.878468636469772, 0.0791661274605434),
+        vec3(0.0423756549057051, 0.0784336, 0.879142973793104),
+    );
+    let outset = mat3x3<f32>(
+        vec3(1.19687900512017, -0.0980208811401368, -0.0990297440797205),
+        vec3(-0.0528968517574562, 1.15190312990417, -0.0989611768448433),
+        vec3(-0.0529716355144438, -0.0980434501171241, 1.15107367264116),
+    );
+    let x = (clamp(log2(max(color * inset, vec3(1e-10))), vec3(AGX_MIN_EV), vec3(AGX_MAX_EV)) - AGX_MIN_EV) / (AGX_MAX_EV - AGX_MIN_EV);
+    let x2 = x * x;
+    let x4 = x2 * x2;
+    let encoded = 15.5 * x4 * x2 - 40.14 * x4 * x + 31.96 * x4 - 6.868 * x2 * x + 0.4298 * x2 + 0.1191 * x - 0.00232;
+    return pow(max(encoded * outset, vec3(0.0)), vec3(2.2));
 }
 struct DistanceInfo {
     did_hit: bool,
     distance: f32,
 }
 
-// rng
-var<private> rng_seed: u32 = 0u;
-fn initRngSeed(pixel: vec2<f32>) {
-    // Compute a seed from pixel coordinates and a frame constant using a simple hash.
-    // Given two floats in [0,1), scale them to the full 32-bit range,
-    // then mix their bits using a simple hash function.
-    // Scale the floats to 32-bit unsigned integer range.
-    // (We subtract a tiny epsilon to avoid exactly 1.0.)
-    let ai: u32 = u32(clamp(pixel.x + 0.5, 0.0, 0.999999) * 4294967296.0);
-    let bi: u32 = u32(clamp(pixel.y + 0.5, 0.0, 0.999999) * 4294967296.0);
-
-    // Combine the two values into a 64-bit value, then mix down to 32 bits.
-    // Since WGSL doesn’t have 64-bit integers yet, we can instead combine them via bitwise mixing.
-    // One simple approach is to use XOR and a multiplication with a large odd constant.
-    var h: u32 = ai ^ (bi * 0x85ebca6bu);
-    h = (h ^ (h >> 16u)) * 0x85ebca6bu;
-    rng_seed = jenkinsHash(u32(h ^ (h >> 13u)));
-}
-fn jenkinsHash(input: u32) -> u32 {
-  var x = input;
-  x += x << 10u;
-  x ^= x >> 6u;
-  x += x << 3u;
-  x ^= x >> 11u;
-  x += x << 15u;
-  return x;
-}
-fn random_int() -> u32 {
-    let newSeed = rng_seed * 747796405u + 2891336453u;
-    rng_seed = newSeed;
-    let word = ((newSeed >> ((newSeed >> 28u) + 4u)) ^ newSeed) * 277803737u;
-    return (word >> 22u) ^ word;
-}
-fn random_float() -> f32 {
-    return f32(random_int()) / f32(0xffffffffu);
-}
 fn random_direction() -> vec3<f32> {
     let z: f32 = random_float() * 2.0 - 1.0;
     let theta = random_float() * 2.0 * PI;
@@ -150,32 +453,407 @@ struct Ray {
 }
 fn trace_ray(ray_: Ray) -> vec3<f32> {
     var ray = ray_;
+    // the pdf of the bsdf sample the ray comes from, negative if it wasn't sampled from a bsdf
+    var bsdf_pdf = -1.0;
     for (var i: u32 = 0u; i < config.max_bounces; i++) {
         let hit_info = closest_object(ray);
         if (!hit_info.did_hit) {
+            // the environment is weighted against sampling it directly
+            var weight = 1.0;
+            if (bsdf_pdf >= 0.0) {
+                weight = power_heuristic(bsdf_pdf, environment_pdf(ray.direction));
+            }
+            ray.actual_color += environment_radiance(ray.direction) * ray.light_color * weight;
             break;
         }
         ray.position += ray.direction * hit_info.distance;
         ray.actual_color += hit_info.object.emission_color * ray.light_color;
-        ray.light_color *= max(hit_info.object.base_color, vec3<f32>(0.0, 0.0, 0.0));
+        let object = hit_info.object;
+        let normal = calculate_normal(ray.position, object.object_id, object.object_index, object.auxiliary_offset);
+        if (object.ior > 0.0) {
+            ray.light_color *= max(object.base_color, vec3<f32>(0.0, 0.0, 0.0));
+            let entering = dot(ray.direction, normal) < 0.0;
+            if (!entering) {
+                // the ray travelled through the medium (Beer–Lambert)
+                ray.light_color *= exp(-object.absorption * hit_info.distance);
+            }
+            let facing_normal = select(-normal, normal, entering);
+            let eta = select(object.ior, 1.0 / object.ior, entering);
+            ray.direction = refract_or_reflect(ray.direction, facing_normal, eta, object.roughness);
+            bsdf_pdf = -1.0;
+        } else {
+            // scatter back to the side the ray came from
+            let facing_normal = select(normal, -normal, dot(ray.direction, normal) > 0.0);
+            for (var l: u32 = 0u; l < arrayLength(&lights); l++) {
+                ray.actual_color += ray.light_color * punctual_light(lights[l], object, ray.position, -ray.direction, facing_normal);
+            }
+            ray.actual_color += ray.light_color * sample_environment_light(object, ray.position, -ray.direction, facing_normal);
+            let sample = sample_microfacet(object, -ray.direction, facing_normal);
+            if (!sample.valid) {
+                break;
+            }
+            ray.direction = sample.direction;
+            ray.light_color *= sample.weight;
+            bsdf_pdf = sample.pdf;
+        }
         if (all(ray.light_color == vec3<f32>(0.0, 0.0, 0.0))) {
             break;
         }
-        let object = hit_info.object;
-        let normal = calculate_normal(ray.position, object.object_id, object.object_index);
-        ray.direction = random_bounce(ray.direction, normal, object.roughness);
+        ray.position += select(-normal, normal, dot(ray.direction, normal) > 0.0) * RAY_OFFSET;
     }
     return ray.actual_color;
 }
-fn random_bounce(ray_dir: vec3<f32>, surface_normal: vec3<f32>, surface_roughness: f32) -> vec3<f32> {
+fn power_heuristic(pdf: f32, other_pdf: f32) -> f32 {
+    let a = pdf * pdf;
+    let b = other_pdf * other_pdf;
+    if (a + b <= 0.0) {
+        return 0.0;
+    }
+    return a / (a + b);
+}
+// the environment, the same as `Environment` in `environment.rs`
+fn direction_to_uv(direction: vec3<f32>) -> vec2<f32> {
+    return vec2<f32>(0.5 + atan2(direction.y, direction.x) / (2.0 * PI), acos(clamp(direction.z, -1.0, 1.0)) / PI);
+}
+fn uv_to_direction(uv: vec2<f32>) -> vec3<f32> {
+    let phi = (uv.x - 0.5) * 2.0 * PI;
+    let theta = uv.y * PI;
+    return vec3<f32>(sin(theta) * cos(phi), sin(theta) * sin(phi), cos(theta));
+}
+fn environment_pixel(uv: vec2<f32>) -> vec2<u32> {
+    return min(vec2<u32>(uv * vec2<f32>(f32(environment.width), f32(environment.height))), vec2<u32>(environment.width - 1u, environment.height - 1u));
+}
+fn environment_radiance(direction: vec3<f32>) -> vec3<f32> {
+    switch (environment.kind) {
+        case 1u: {
+            return mix(environment.color, environment.top, (direction.z + 1.0) / 2.0);
+        }
+        case 2u: {
+            let pixel = environment_pixel(direction_to_uv(direction));
+            return textureLoad(environment_map, pixel, 0).rgb * environment.intensity;
+        }
+        case 3u: {
+            return sky_radiance(direction) * environment.intensity;
+        }
+        default: {
+            return environment.color;
+        }
+    }
+}
+// the same as `LUMINANCE_SCALE` in `sky.rs`
+const SKY_LUMINANCE_SCALE = 0.05;
+fn sky_radiance(direction: vec3<f32>) -> vec3<f32> {
+    if (direction.z < 0.0) {
+        return environment.ground_radiance;
+    }
+    let cos_gamma = clamp(dot(direction, environment.sun), -1.0, 1.0);
+    // the Perez distribution of the luminance and the chromaticities
+    let gamma = acos(cos_gamma);
+    let cos_theta = max(direction.z, 1e-3);
+    let p = environment.perez;
+    let distribution = (1.0 + p[0] * exp(p[1] / cos_theta)) * (1.0 + p[2] * exp(p[3] * gamma) + p[4] * cos_gamma * cos_gamma);
+    let yxy = environment.zenith * distribution;
+    var radiance = vec3<f32>(0.0);
+    if (yxy.z > 0.0) {
+        let xyz = vec3<f32>(yxy.y / yxy.z * yxy.x, yxy.x, (1.0 - yxy.y - yxy.z) / yxy.z * yxy.x);
+        radiance = max(vec3<f32>(
+            3.2406 * xyz.x - 1.5372 * xyz.y - 0.4986 * xyz.z,
+            -0.9689 * xyz.x + 1.8758 * xyz.y + 0.0415 * xyz.z,
+            0.0557 * xyz.x - 0.2040 * xyz.y + 1.0570 * xyz.z,
+        ), vec3<f32>(0.0)) * SKY_LUMINANCE_SCALE;
+    }
+    if (cos_gamma >= environment.cos_sun_radius) {
+        radiance += environment.sun_radiance;
+    }
+    return radiance;
+}
+fn environment_total_weight() -> f32 {
+    return environment_cdf[environment.height - 1u];
+}
+// the weight of a pixel of the map
+fn environment_weight(pixel: vec2<u32>) -> f32 {
+    let index = environment.height + pixel.y * environment.width + pixel.x;
+    if (pixel.x == 0u) {
+        return environment_cdf[index];
+    }
+    return environment_cdf[index] - environment_cdf[index - 1u];
+}
+fn environment_pdf_at(pixel: vec2<u32>, v: f32) -> f32 {
+    let total = environment_total_weight();
+    let sin_theta = sin(PI * v);
+    if (environment.kind != 2u || total <= 0.0 || sin_theta <= 0.0) {
+        return 0.0;
+    }
+    let pdf_uv = environment_weight(pixel) / total * f32(environment.width * environment.height);
+    return pdf_uv / (2.0 * PI * PI * sin_theta);
+}
+fn environment_pdf(direction: vec3<f32>) -> f32 {
+    if (environment.kind == 3u) {
+        return sun_pdf(direction);
+    }
+    if (environment.kind != 2u) {
+        return 0.0;
+    }
+    let uv = direction_to_uv(direction);
+    return environment_pdf_at(environment_pixel(uv), uv.y);
+}
+fn sun_pdf(direction: vec3<f32>) -> f32 {
+    if (all(environment.sun_radiance == vec3<f32>(0.0)) || dot(direction, environment.sun) < environment.cos_sun_radius) {
+        return 0.0;
+    }
+    return environment.sun_pdf;
+}
+// picks an entry of the summed up weights in `environment_cdf[start..start + count]`, returns the index and where within the entry `u` landed
+fn sample_environment_cdf(start: u32, count: u32, u: f32) -> vec2<f32> {
+    let total = environment_cdf[start + count - 1u];
+    let target_sum = u * total;
+    // binary search for the first entry that is bigger than the target
+    var low = 0u;
+    var high = count - 1u;
+    while (low < high) {
+        let middle = (low + high) / 2u;
+        if (environment_cdf[start + middle] <= target_sum) {
+            low = middle + 1u;
+        } else {
+            high = middle;
+        }
+    }
+    var previous = 0.0;
+    if (low > 0u) {
+        previous = environment_cdf[start + low - 1u];
+    }
+    let weight = environment_cdf[start + low] - previous;
+    var offset = 0.5;
+    if (weight > 0.0) {
+        offset = clamp((target_sum - previous) / weight, 0.0, 1.0);
+    }
+    return vec2<f32>(f32(low), offset);
+}
+// next-event estimation for the environment map, weighted with multiple importance sampling
+fn sample_environment_light(object: Object, position: vec3<f32>, outgoing: vec3<f32>, normal: vec3<f32>) -> vec3<f32> {
+    var direction: vec3<f32>;
+    var pdf: f32;
+    if (environment.kind == 3u) {
+        // uniformly within the sun disk
+        if (all(environment.sun_radiance == vec3<f32>(0.0))) {
+            return vec3<f32>(0.0);
+        }
+        let cos_theta = 1.0 - random_float() * (1.0 - environment.cos_sun_radius);
+        let sin_theta = sqrt(max(1.0 - cos_theta * cos_theta, 0.0));
+        let phi = 2.0 * PI * random_float();
+        let basis = orthonormal_basis(environment.sun);
+        direction = normalize(basis[0] * (sin_theta * cos(phi)) + basis[1] * (sin_theta * sin(phi)) + environment.sun * cos_theta);
+        pdf = environment.sun_pdf;
+    } else {
+        if (environment.kind != 2u || environment_total_weight() <= 0.0) {
+            return vec3<f32>(0.0);
+        }
+        let row = sample_environment_cdf(0u, environment.height, random_float());
+        let y = u32(row.x);
+        let column = sample_environment_cdf(environment.height + y * environment.width, environment.width, random_float());
+        let x = u32(column.x);
+        let uv = vec2<f32>((column.x + column.y) / f32(environment.width), (row.x + row.y) / f32(environment.height));
+        direction = uv_to_direction(uv);
+        pdf = environment_pdf_at(vec2<u32>(x, y), uv.y);
+    }
+    let cos_surface = dot(direction, normal);
+    if (pdf <= 0.0 || cos_surface <= 0.0) {
+        return vec3<f32>(0.0);
+    }
+    let shadow = closest_object(Ray(position + normal * RAY_OFFSET, direction, vec3<f32>(0.0), vec3<f32>(0.0)));
+    if (shadow.did_hit) {
+        return vec3<f32>(0.0);
+    }
+    let radiance = environment_radiance(direction);
+    let weight = power_heuristic(pdf, pdf_microfacet(object, outgoing, direction, normal));
+    return eval_microfacet(object, outgoing, direction, normal) * radiance * (cos_surface * weight / pdf);
+}
+// how much of the light of a punctual light is reflected towards `outgoing`, the same as `Scene::punctual_light`
+fn punctual_light(light: Light, object: Object, position: vec3<f32>, outgoing: vec3<f32>, normal: vec3<f32>) -> vec3<f32> {
+    var direction: vec3<f32>;
+    var distance: f32;
+    var irradiance: vec3<f32>;
+    switch (light.kind) {
+        case 1u, 2u: {
+            let to_light = light.position - position;
+            distance = length(to_light);
+            direction = to_light / distance;
+            irradiance = light.radiant / (distance * distance);
+            if (light.kind == 2u) {
+                irradiance *= spot_falloff(dot(-direction, light.direction), light.cos_inner, light.cos_outer);
+            }
+        }
+        case 3u: {
+            // uniformly within the cone of directions the light comes from
+            let to_light = -light.direction;
+            let cos_theta = 1.0 - random_float() * (1.0 - light.cos_cone);
+            let sin_theta = sqrt(max(1.0 - cos_theta * cos_theta, 0.0));
+            let phi = 2.0 * PI * random_float();
+            let basis = orthonormal_basis(to_light);
+            direction = basis[0] * (sin_theta * cos(phi)) + basis[1] * (sin_theta * sin(phi)) + to_light * cos_theta;
+            distance = -1.0;
+            irradiance = light.radiant;
+        }
+        default: {
+            return vec3<f32>(0.0);
+        }
+    }
+    let cos_surface = dot(direction, normal);
+    if (cos_surface <= 0.0 || all(irradiance == vec3<f32>(0.0))) {
+        return vec3<f32>(0.0);
+    }
+    // shadow ray, a negative distance means that anything blocks it
+    let shadow = closest_object(Ray(position + normal * RAY_OFFSET, direction, vec3<f32>(0.0), vec3<f32>(0.0)));
+    if (shadow.did_hit && (distance < 0.0 || shadow.distance < distance - distance * 1e-4 - RAY_OFFSET * 2.0)) {
+        return vec3<f32>(0.0);
+    }
+    return eval_microfacet(object, outgoing, direction, normal) * irradiance * cos_surface;
+}
+fn spot_falloff(cos_angle: f32, cos_inner: f32, cos_outer: f32) -> f32 {
+    if (cos_inner <= cos_outer) {
+        return select(0.0, 1.0, cos_angle >= cos_outer);
+    }
+    return smoothstep(cos_outer, cos_inner, cos_angle);
+}
+// builds two vectors that form an orthonormal basis with the normal (Duff et al. 2017)
+fn orthonormal_basis(normal: vec3<f32>) -> array<vec3<f32>, 2> {
+    let sign = select(-1.0, 1.0, normal.z >= 0.0);
+    let a = -1.0 / (sign + normal.z);
+    let b = normal.x * normal.y * a;
+    return array<vec3<f32>, 2>(
+        vec3<f32>(1.0 + sign * normal.x * normal.x * a, sign * b, -sign * normal.x),
+        vec3<f32>(b, sign + normal.y * normal.y * a, -normal.y),
+    );
+}
+// the microfacet bsdf, the same as `Microfacet` in `bsdf.rs`
+struct BsdfSample {
+    valid: bool,
+    direction: vec3<f32>,
+    weight: vec3<f32>,
+    pdf: f32,
+}
+fn luminance(color: vec3<f32>) -> f32 {
+    return dot(color, vec3<f32>(0.2126, 0.7152, 0.0722));
+}
+fn schlick(f0: vec3<f32>, cos: f32) -> vec3<f32> {
+    return f0 + (1.0 - f0) * pow(clamp(1.0 - cos, 0.0, 1.0), 5.0);
+}
+fn microfacet_alpha(object: Object) -> f32 {
+    return max(object.roughness * object.roughness, MIN_ALPHA);
+}
+fn microfacet_f0(object: Object) -> vec3<f32> {
+    return mix(vec3<f32>(DIELECTRIC_F0), object.base_color, object.metallic);
+}
+fn specular_probability(object: Object, cos_outgoing: f32) -> f32 {
+    let specular = luminance(schlick(microfacet_f0(object), cos_outgoing));
+    let diffuse = luminance(object.base_color * (1.0 - object.metallic));
+    if (specular + diffuse <= 0.0) {
+        return 0.5;
+    }
+    return specular / (specular + diffuse);
+}
+fn ggx_distribution(alpha: f32, cos_half: f32) -> f32 {
+    let alpha2 = alpha * alpha;
+    let denominator = (alpha2 - 1.0) * cos_half * cos_half + 1.0;
+    return alpha2 / (PI * denominator * denominator);
+}
+fn smith_masking(alpha: f32, cos: f32) -> f32 {
+    let alpha2 = alpha * alpha;
+    return 2.0 * cos / (cos + sqrt(alpha2 + (1.0 - alpha2) * cos * cos));
+}
+fn eval_microfacet(object: Object, outgoing: vec3<f32>, incoming: vec3<f32>, normal: vec3<f32>) -> vec3<f32> {
+    let cos_outgoing = dot(outgoing, normal);
+    let cos_incoming = dot(incoming, normal);
+    if (cos_outgoing <= 0.0 || cos_incoming <= 0.0) {
+        return vec3<f32>(0.0);
+    }
+    let alpha = microfacet_alpha(object);
+    let half = normalize(outgoing + incoming);
+    let fresnel = schlick(microfacet_f0(object), dot(outgoing, half));
+    let specular = fresnel * (ggx_distribution(alpha, dot(half, normal)) * smith_masking(alpha, cos_outgoing) * smith_masking(alpha, cos_incoming)
+        / (4.0 * cos_outgoing * cos_incoming));
+    let diffuse = object.base_color * (1.0 - object.metallic) * (1.0 - fresnel) / PI;
+    return specular + diffuse;
+}
+fn pdf_microfacet(object: Object, outgoing: vec3<f32>, incoming: vec3<f32>, normal: vec3<f32>) -> f32 {
+    let cos_outgoing = dot(outgoing, normal);
+    let cos_incoming = dot(incoming, normal);
+    if (cos_outgoing <= 0.0 || cos_incoming <= 0.0) {
+        return 0.0;
+    }
+    let half = normalize(outgoing + incoming);
+    let probability = specular_probability(object, cos_outgoing);
+    let specular = ggx_distribution(microfacet_alpha(object), dot(half, normal)) * dot(half, normal) / (4.0 * dot(outgoing, half));
+    let diffuse = cos_incoming / PI;
+    return probability * specular + (1.0 - probability) * diffuse;
+}
+fn sample_microfacet(object: Object, outgoing: vec3<f32>, normal: vec3<f32>) -> BsdfSample {
+    let invalid = BsdfSample(false, vec3<f32>(0.0), vec3<f32>(0.0), 0.0);
+    let cos_outgoing = dot(outgoing, normal);
+    if (cos_outgoing <= 0.0) {
+        return invalid;
+    }
+    let basis = orthonormal_basis(normal);
+    let tangent = basis[0];
+    let bitangent = basis[1];
+
+    let probability = specular_probability(object, cos_outgoing);
+    let u = vec2<f32>(random_float(), random_float());
+    let phi = 2.0 * PI * u.y;
+    var incoming: vec3<f32>;
+    if (u.x < probability) {
+        // sample the half vector proportional to D(h) * cos(h)
+        let u0 = u.x / probability;
+        let alpha = microfacet_alpha(object);
+        let alpha2 = alpha * alpha;
+        let cos_theta = sqrt((1.0 - u0) / (1.0 + (alpha2 - 1.0) * u0));
+        let sin_theta = sqrt(max(1.0 - cos_theta * cos_theta, 0.0));
+        let half = tangent * (sin_theta * cos(phi)) + bitangent * (sin_theta * sin(phi)) + normal * cos_theta;
+        incoming = half * 2.0 * dot(outgoing, half) - outgoing;
+    } else {
+        // cosine weighted hemisphere
+        let u0 = (u.x - probability) / (1.0 - probability);
+        let radius = sqrt(u0);
+        incoming = tangent * (radius * cos(phi)) + bitangent * (radius * sin(phi)) + normal * sqrt(max(1.0 - u0, 0.0));
+    }
+    let cos_incoming = dot(incoming, normal);
+    if (cos_incoming <= 0.0) {
+        return invalid;
+    }
+    let pdf = pdf_microfacet(object, outgoing, incoming, normal);
+    if (pdf <= 0.0) {
+        return invalid;
+    }
+    return BsdfSample(true, incoming, eval_microfacet(object, outgoing, incoming, normal) * cos_incoming / pdf, pdf);
+}
+// picks randomly between reflecting and refracting, weighted by the Fresnel equations
+fn refract_or_reflect(ray_dir: vec3<f32>, facing_normal: vec3<f32>, eta: f32, surface_roughness: f32) -> vec3<f32> {
+    let cos_incident = -dot(ray_dir, facing_normal);
+    let sin2_transmitted = eta * eta * (1.0 - cos_incident * cos_incident);
+    let reflection_dir = ray_dir + facing_normal * 2.0 * cos_incident;
+    // total internal reflection
+    if (sin2_transmitted >= 1.0) {
+        return scatter(reflection_dir, facing_normal, surface_roughness);
+    }
+    let cos_transmitted = sqrt(1.0 - sin2_transmitted);
+    let perpendicular = (eta * cos_incident - cos_transmitted) / (eta * cos_incident + cos_transmitted);
+    let parallel = (cos_incident - eta * cos_transmitted) / (cos_incident + eta * cos_transmitted);
+    let reflectance = (perpendicular * perpendicular + parallel * parallel) / 2.0;
+    if (random_float() < reflectance) {
+        return scatter(reflection_dir, facing_normal, surface_roughness);
+    }
+    let refraction_dir = ray_dir * eta + facing_normal * (eta * cos_incident - cos_transmitted);
+    return scatter(refraction_dir, -facing_normal, surface_roughness);
+}
+// blends the ideal direction with a random one and keeps it on the side of the hemisphere normal
+fn scatter(ideal_dir: vec3<f32>, hemisphere_normal: vec3<f32>, surface_roughness: f32) -> vec3<f32> {
     let random_dir = random_direction();
-    let reflection_dir = ray_dir - surface_normal * 2 * dot(ray_dir, surface_normal);
-    let random_to_reflection = reflection_dir - random_dir;
-    let reflection_mult = 1.0 - surface_roughness;
-    var final_direction = random_dir + random_to_reflection * reflection_mult;
+    let random_to_ideal = ideal_dir - random_dir;
+    let ideal_mult = 1.0 - surface_roughness;
+    var final_direction = random_dir + random_to_ideal * ideal_mult;
 
     final_direction = normalize(final_direction);
-    if (dot(final_direction, surface_normal) < 0.0) {
+    if (dot(final_direction, hemisphere_normal) < 0.0) {
         return -final_direction;
     } else {
         return final_direction;
@@ -186,16 +864,16 @@ struct RayHitInfo {
     object: Object,
     distance: f32,
 }
-const NULL_OBJECT: Object = Object(vec3<f32>(0.0, 0.0, 0.0), 0.0, vec3<f32>(0.0, 0.0, 0.0), 0, 0);
+const NULL_OBJECT: Object = Object(vec3<f32>(0.0, 0.0, 0.0), 0.0, vec3<f32>(0.0, 0.0, 0.0), 0, 0, 0, 0.0, 0.0, vec3<f32>(0.0, 0.0, 0.0));
 fn closest_object(ray: Ray) -> RayHitInfo {
     var res: RayHitInfo = RayHitInfo(false, NULL_OBJECT, -1.0);
     for (var i: u32 = 0u; i < arrayLength(&objects); i++) {
         let current = objects[i];
-        let bounding_box = bounding_box(current.object_id, current.object_index);
+        let bounding_box = bounding_box(current.object_id, current.object_index, current.auxiliary_offset);
         if (!bounding_box_intersection(ray, bounding_box)) {
             continue;
         }
-        let distance_result = calculate_distance(ray.position, ray.direction, current.object_id, current.object_index);
+        let distance_result = calculate_distance(ray.position, ray.direction, current.object_id, current.object_index, current.auxiliary_offset);
         if (!distance_result.did_hit) {
             continue;
         }
@@ -222,47 +900,3 @@ fn bounding_box_intersection(ray: Ray, box: BoundingBox) -> bool {
     let tmax = min(min(max(t0s.x, t1s.x), max(t0s.y, t1s.y)), max(t0s.z, t1s.z));
     return tmax >= max(tmin, 0.0);
 }
-
-// thx ChatGPT
-// Function to compute the inverse of a 3x3 matrix.
-fn inverse3x3(m: mat3x3<f32>) -> mat3x3<f32> {
-    // Since m is column-major, we extract elements as follows:
-    let a = m[0][0]; // first column, first row
-    let d = m[0][1]; // first column, second row
-    let g = m[0][2]; // first column, third row
-
-    let b = m[1][0]; // second column, first row
-    let e = m[1][1]; // second column, second row
-    let h = m[1][2]; // second column, third row
-
-    let c = m[2][0]; // third column, first row
-    let f = m[2][1]; // third column, second row
-    let i = m[2][2]; // third column, third row
-
-    // Compute the determinant:
-    // det = a*(e*i - f*h) - b*(d*i - f*g) + c*(d*h - e*g)
-    let det = a * (e * i - f * h) - b * (d * i - f * g) + c * (d * h - e * g);
-
-    // For robustness, you might want to check if 'det' is near zero.
-    // Here we assume det is non-zero.
-
-    // Compute the components of the adjugate matrix.
-    let A = e * i - f * h;
-    let B = f * g - d * i;
-    let C = d * h - e * g;
-
-    let D = c * h - b * i;
-    let E = a * i - c * g;
-    let F = b * g - a * h;
-
-    let G = b * f - c * e;
-    let H = c * d - a * f;
-    let I = a * e - b * d;
-
-    // Construct the inverse as (1/det) * adjugate.
-    return (1/det) * mat3x3<f32>(
-        vec3<f32>(A, D, G), // first column of the inverse
-        vec3<f32>(B, E, H), // second column
-        vec3<f32>(C, F, I)  // third column
-    );
-}
\ No newline at end of file
@@ -3,11 +3,22 @@ mod buffer;
 use crate::raytracing::gpu::gpu_state::buffer::FrequentlyChangedBuffer;
 use crate::raytracing::gpu::object::Object;
 use crate::raytracing::gpu::GpuSerialize;
+use crate::raytracing::environment::sky::{sun_solid_angle, SUN_ANGULAR_RADIUS};
+use crate::raytracing::environment::Environment;
+use crate::raytracing::light::Light;
+use crate::raytracing::camera::lens::Aperture;
+use crate::raytracing::tone_mapping::{ToneMapOperator, ToneMapping};
+use crate::math::Vector3;
 use crate::{Camera, Config};
 use std::borrow::Cow;
 use std::collections::HashMap;
 
-const BASE_SHADER: &str = include_str!("base_shader.wgsl");
+const BASE_SHADER: &str = concat!(include_str!("base_shader.wgsl"), include_str!("rng.wgsl"));
+/// The format of the accumulation textures, linear colors that can get brighter than 1.
+/// 32 bit floats, because the change a new frame makes to the running average shrinks with every frame.
+/// Half floats would round it away after about a thousand frames and the image would stop converging.
+/// They are stored as their bits in unsigned integers, since not every backend can render to 32 bit float textures.
+const ACCUMULATION_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba32Uint;
 struct ShapeInfo<'a> {
     buffer: FrequentlyChangedBuffer<'a>,
     distance_function: String,
@@ -21,29 +32,83 @@ struct ShapeInfo<'a> {
 pub(super) struct State<'a> {
     device: wgpu::Device,
     pipeline: wgpu::RenderPipeline,
+    /// Like [State::pipeline], but also averages the frame with the accumulation
+    accumulation_pipeline: wgpu::RenderPipeline,
     targets: Vec<Option<wgpu::ColorTargetState>>,
     object_data: FrequentlyChangedBuffer<'a>,
     objects: HashMap<String, ShapeInfo<'a>>,
     cam_buffer: FrequentlyChangedBuffer<'a>,
     aspect_ratio_buffer: FrequentlyChangedBuffer<'a>,
     config_buffer: FrequentlyChangedBuffer<'a>,
+    /// The [GpuShape::auxiliary_data](crate::raytracing::gpu::object::GpuShape::auxiliary_data) of all objects
+    auxiliary_buffer: FrequentlyChangedBuffer<'a>,
+    /// The number of `vec4<f32>` entries in the auxiliary buffer
+    auxiliary_len: usize,
+    /// The punctual lights, after an unused entry
+    light_buffer: FrequentlyChangedBuffer<'a>,
+    /// The kind, size and colors of the environment
+    environment_buffer: FrequentlyChangedBuffer<'a>,
+    /// The pixels of the environment map, a single pixel for the other environments
+    environment_texture: wgpu::Texture,
+    /// Pixels that still have to be written to [State::environment_texture]
+    environment_pixels: Option<Vec<u8>>,
+    /// The summed up sampling weights of the environment map: first the rows, then the pixels within each row
+    environment_cdf: FrequentlyChangedBuffer<'a>,
+    /// The summed up weights of the aperture image of the camera
+    aperture_cdf: FrequentlyChangedBuffer<'a>,
+    /// The operator, exposure and whether the shader has to apply the sRGB transfer function
+    tone_mapping_buffer: FrequentlyChangedBuffer<'a>,
+    /// The index of the frame and how many frames are in the accumulation
+    frame_buffer: FrequentlyChangedBuffer<'a>,
+    /// Counts all rendered frames, for the seed of the random numbers
+    frame_index: u32,
+    /// The average of the frames so far and the texture that gets the next average, swapped after every frame
+    accumulation: [wgpu::Texture; 2],
+    /// The frames in `accumulation[0]`
+    accumulated_frames: u32,
 }
 impl<'a> State<'a> {
     pub fn new(device: &wgpu::Device, targets: Vec<Option<wgpu::ColorTargetState>>, camera: &Camera, config: Config) -> Self {
         let cam_buffer = FrequentlyChangedBuffer::new_init(device, Some("raytracing camera buffer"), camera.serialize());
+        let aperture_cdf = FrequentlyChangedBuffer::new_init(device, Some("raytracing aperture cdf"), serialize_aperture_cdf(camera));
         let aspect_ratio_buffer = FrequentlyChangedBuffer::new_init(device, Some("raytracing aspect ratio buffer"), 0f32.to_le_bytes().to_vec());
-        let pipeline = Self::create_pipeline(device, &targets, &HashMap::new());
+        let (pipeline, accumulation_pipeline) = Self::create_pipelines(device, &targets, &HashMap::new());
         let object_data = FrequentlyChangedBuffer::new(device, Some("raytracing object data"));
         let config_buffer = FrequentlyChangedBuffer::new_init(device, Some("raytracing config buffer"), config.serialize());
+        // bindings can't be empty, so the buffer starts with one unused entry
+        let auxiliary_buffer = FrequentlyChangedBuffer::new_init(device, Some("raytracing auxiliary buffer"), vec![0; 16]);
+        // same for the lights, a kind of 0 marks the entry as unused
+        let light_buffer = FrequentlyChangedBuffer::new_init(device, Some("raytracing light buffer"), vec![0; 64]);
+        let (environment, environment_pixels, environment_cdf) = serialize_environment(&Environment::default());
+        let environment_buffer = FrequentlyChangedBuffer::new_init(device, Some("raytracing environment buffer"), environment);
+        let environment_cdf = FrequentlyChangedBuffer::new_init(device, Some("raytracing environment cdf"), environment_cdf);
+        let environment_texture = Self::create_environment_texture(device, 1, 1);
+        let tone_mapping_buffer = FrequentlyChangedBuffer::new_init(device, Some("raytracing tone mapping buffer"), serialize_tone_mapping(None, false));
+        let frame_buffer = FrequentlyChangedBuffer::new_init(device, Some("raytracing frame buffer"), vec![0; 8]);
+        let accumulation = [Self::create_accumulation_texture(device, 1, 1), Self::create_accumulation_texture(device, 1, 1)];
         let device = device.clone();
         Self {
             device,
             targets,
             pipeline,
+            accumulation_pipeline,
             cam_buffer,
             aspect_ratio_buffer,
             object_data,
             config_buffer,
+            auxiliary_buffer,
+            auxiliary_len: 1,
+            light_buffer,
+            environment_buffer,
+            environment_texture,
+            environment_pixels: Some(environment_pixels),
+            environment_cdf,
+            aperture_cdf,
+            tone_mapping_buffer,
+            frame_buffer,
+            frame_index: 0,
+            accumulation,
+            accumulated_frames: 0,
             objects: HashMap::new(),
         }
     }
@@ -51,7 +116,7 @@ impl<'a> State<'a> {
         &self.device
     }
     pub fn add_object(&mut self, object: Object) {
-        let shape = object.shape.lock().unwrap();
+        let shape = &object.shape;
         let r#type = shape.object_type();
         let info = match self.objects.get_mut(&r#type) {
             Some(info) => info,
@@ -72,32 +137,115 @@ impl<'a> State<'a> {
         info.buffer.append(shape.serialize());
         info.count += 1;
         let type_id = info.shape_id;
-        self.object_data.append(object.gpu_serialize(type_id as u32, object_index as u32));
-        self.pipeline = Self::create_pipeline(&self.device, &self.targets, &self.objects);
+        let auxiliary_data = shape.auxiliary_data();
+        assert_eq!(auxiliary_data.len() % 16, 0, "auxiliary data of {} isn't a multiple of 16 bytes", r#type);
+        let auxiliary_offset = self.auxiliary_len;
+        self.auxiliary_len += auxiliary_data.len() / 16;
+        self.auxiliary_buffer.append(auxiliary_data);
+        self.object_data.append(object.gpu_serialize(type_id as u32, object_index as u32, auxiliary_offset as u32));
+        (self.pipeline, self.accumulation_pipeline) = Self::create_pipelines(&self.device, &self.targets, &self.objects);
+        self.reset_accumulation();
     }
+    pub fn add_light(&mut self, light: Light) {
+        self.light_buffer.append(light.serialize());
+        self.reset_accumulation();
+    }
+    pub fn set_camera(&mut self, camera: &Camera) {
+        self.cam_buffer.set_data(camera.serialize());
+        self.aperture_cdf.set_data(serialize_aperture_cdf(camera));
+        self.reset_accumulation();
+    }
+    pub fn set_environment(&mut self, environment: &Environment) {
+        let (data, pixels, cdf) = serialize_environment(environment);
+        let (width, height) = match environment {
+            Environment::Map(map) => (map.width() as u32, map.height() as u32),
+            _ => (1, 1),
+        };
+        self.environment_buffer.set_data(data);
+        self.environment_cdf.set_data(cdf);
+        self.environment_texture = Self::create_environment_texture(&self.device, width, height);
+        self.environment_pixels = Some(pixels);
+        self.reset_accumulation();
+    }
+    /// Sets the tone mapping that is applied to the colors before they are written to the target.
+    /// The sRGB transfer function is applied too, unless the target has an sRGB format that does it already.
+    pub fn set_tone_mapping(&mut self, tone_mapping: Option<ToneMapping>) {
+        let encode_srgb = self.targets.first()
+            .and_then(Option::as_ref)
+            .is_some_and(|target| !target.format.is_srgb());
+        self.tone_mapping_buffer.set_data(serialize_tone_mapping(tone_mapping.as_ref(), encode_srgb));
+    }
+    /// the next [State::render_accumulated] starts a new average
+    pub fn reset_accumulation(&mut self) {
+        self.accumulated_frames = 0;
+    }
+    /// the frames that were averaged since the last reset
+    pub fn accumulated_frames(&self) -> u32 {
+        self.accumulated_frames
+    }
+    /// renders a frame on its own, with new random numbers every time
     pub fn render(&mut self, aspect_ratio: f32, queue: &wgpu::Queue, view: &wgpu::TextureView) {
-        queue.write_buffer(&self.aspect_ratio_buffer.get_updated_buffer(queue), 0, &aspect_ratio.to_le_bytes());
+        self.draw(aspect_ratio, queue, view, false);
+    }
+    /// Renders a frame, averages it with the frames since the last reset and writes the average to the view.
+    /// The accumulation starts over if the size changes.
+    pub fn render_accumulated(&mut self, width: u32, height: u32, queue: &wgpu::Queue, view: &wgpu::TextureView) {
+        let size = self.accumulation[0].size();
+        if (size.width, size.height) != (width, height) {
+            self.accumulation = [
+                Self::create_accumulation_texture(&self.device, width, height),
+                Self::create_accumulation_texture(&self.device, width, height),
+            ];
+            self.reset_accumulation();
+        }
+        self.draw(width as f32 / height as f32, queue, view, true);
+        self.accumulation.swap(0, 1);
+        self.accumulated_frames += 1;
+    }
+    /// renders a frame into the view, and the new average into `accumulation[1]` if `accumulate` is set
+    fn draw(&mut self, aspect_ratio: f32, queue: &wgpu::Queue, view: &wgpu::TextureView, accumulate: bool) {
+        if let Some(pixels) = self.environment_pixels.take() {
+            queue.write_texture(
+                self.environment_texture.as_image_copy(),
+                &pixels,
+                wgpu::TexelCopyBufferLayout {
+                    offset: 0,
+                    bytes_per_row: Some(self.environment_texture.width() * 16),
+                    rows_per_image: None,
+                },
+                self.environment_texture.size(),
+            );
+        }
+        queue.write_buffer(self.aspect_ratio_buffer.get_updated_buffer(queue), 0, &aspect_ratio.to_le_bytes());
+        self.frame_buffer.set_data(self.frame_index.to_le_bytes().into_iter().chain(self.accumulated_frames.to_le_bytes()).collect());
+        self.frame_index = self.frame_index.wrapping_add(1);
         let mut encoder = self.device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
             label: Some("raytracing render pass encoder"),
         });
         let bind_group0 = self.create_bind_group_for_builtins(queue);
         let bind_group1 = self.create_bind_group_for_objects(queue);
+        let average_view = self.accumulation[1].create_view(&wgpu::TextureViewDescriptor::default());
+        let attachment = |view| Some(wgpu::RenderPassColorAttachment {
+            view,
+            resolve_target: None,
+            ops: wgpu::Operations {
+                load: wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT),
+                store: wgpu::StoreOp::Store,
+            },
+        });
+        let (pipeline, color_attachments) = match accumulate {
+            true => (&self.accumulation_pipeline, vec![attachment(view), attachment(&average_view)]),
+            false => (&self.pipeline, vec![attachment(view)]),
+        };
         {
             let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
                 label: Some("raytracing render pass"),
-                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
-                    view,
-                    resolve_target: None,
-                    ops: wgpu::Operations {
-                        load: wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT),
-                        store: wgpu::StoreOp::Store,
-                    },
-                })],
+                color_attachments: &color_attachments,
                 depth_stencil_attachment: None,
                 timestamp_writes: None,
                 occlusion_query_set: None,
             });
-            render_pass.set_pipeline(&self.pipeline);
+            render_pass.set_pipeline(pipeline);
             render_pass.set_bind_group(0, &bind_group0, &[]);
             render_pass.set_bind_group(1, &bind_group1, &[]);
             render_pass.draw(0..3, 0..1);
@@ -106,27 +254,67 @@ impl<'a> State<'a> {
     }
 }
 impl State<'_> {
-    fn create_pipeline(device: &wgpu::Device, targets: &Vec<Option<wgpu::ColorTargetState>>, objects: &HashMap<String, ShapeInfo>) -> wgpu::RenderPipeline {
+    fn create_environment_texture(device: &wgpu::Device, width: u32, height: u32) -> wgpu::Texture {
+        device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("raytracing environment texture"),
+            size: wgpu::Extent3d { width, height, depth_or_array_layers: 1 },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu::TextureFormat::Rgba32Float,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+            view_formats: &[],
+        })
+    }
+    fn create_accumulation_texture(device: &wgpu::Device, width: u32, height: u32) -> wgpu::Texture {
+        device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("raytracing accumulation texture"),
+            size: wgpu::Extent3d { width, height, depth_or_array_layers: 1 },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: ACCUMULATION_FORMAT,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::RENDER_ATTACHMENT,
+            view_formats: &[],
+        })
+    }
+    /// Creates the pipeline that renders single frames and the one that also writes the average of the frames,
+    /// to the first target and an accumulation texture.
+    fn create_pipelines(device: &wgpu::Device, targets: &[Option<wgpu::ColorTargetState>], objects: &HashMap<String, ShapeInfo>) -> (wgpu::RenderPipeline, wgpu::RenderPipeline) {
         let layout = Self::create_pipeline_layout(device, objects.len());
         let shader: String = Self::create_shader(objects);
         let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
             label: Some("raytracing shader"),
             source: wgpu::ShaderSource::Wgsl(Cow::Borrowed(&*shader)),
         });
+        let accumulation_targets = [
+            targets.first().cloned().flatten(),
+            Some(wgpu::ColorTargetState {
+                format: ACCUMULATION_FORMAT,
+                blend: None,
+                write_mask: wgpu::ColorWrites::ALL,
+            }),
+        ];
+        (
+            Self::create_pipeline(device, &layout, &shader, "fs_main", targets),
+            Self::create_pipeline(device, &layout, &shader, "fs_accumulate", &accumulation_targets),
+        )
+    }
+    fn create_pipeline(device: &wgpu::Device, layout: &wgpu::PipelineLayout, shader: &wgpu::ShaderModule, fragment_entry_point: &str, targets: &[Option<wgpu::ColorTargetState>]) -> wgpu::RenderPipeline {
         device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
             label: Some("raytracing render pipeline"),
-            layout: Some(&layout),
+            layout: Some(layout),
             vertex: wgpu::VertexState {
-                module: &shader,
+                module: shader,
                 entry_point: Some("vs_main"),
                 compilation_options: Default::default(),
                 buffers: &[],
             },
             fragment: Some(wgpu::FragmentState {
-                module: &shader,
-                entry_point: Some("fs_main"),
+                module: shader,
+                entry_point: Some(fragment_entry_point),
                 compilation_options: Default::default(),
-                targets: &**targets,
+                targets,
             }),
             primitive: wgpu::PrimitiveState::default(),
             depth_stencil: None,
@@ -183,6 +371,87 @@ impl State<'_> {
                     min_binding_size: None,
                 },
                 count: None,
+            }, wgpu::BindGroupLayoutEntry {
+                binding: 4,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Storage { read_only: true },
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            }, wgpu::BindGroupLayoutEntry {
+                binding: 5,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Storage { read_only: true },
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            }, wgpu::BindGroupLayoutEntry {
+                binding: 6,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            }, wgpu::BindGroupLayoutEntry {
+                binding: 7,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Texture {
+                    sample_type: wgpu::TextureSampleType::Float { filterable: false },
+                    view_dimension: wgpu::TextureViewDimension::D2,
+                    multisampled: false,
+                },
+                count: None,
+            }, wgpu::BindGroupLayoutEntry {
+                binding: 8,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Storage { read_only: true },
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            }, wgpu::BindGroupLayoutEntry {
+                binding: 9,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            }, wgpu::BindGroupLayoutEntry {
+                binding: 10,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            }, wgpu::BindGroupLayoutEntry {
+                binding: 11,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Texture {
+                    sample_type: wgpu::TextureSampleType::Uint,
+                    view_dimension: wgpu::TextureViewDimension::D2,
+                    multisampled: false,
+                },
+                count: None,
+            }, wgpu::BindGroupLayoutEntry {
+                binding: 12,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Storage { read_only: true },
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
             }
             ],
         })
@@ -220,15 +489,15 @@ impl State<'_> {
                     format!("@group(1)
 @binding({i})
 var<storage, read> shape_{i}: array<Shape{i}>;"),
-                    format!("fn distance_shape_{i}(ray_position: vec3<f32>, ray_direction: vec3<f32>, index: u32) -> DistanceInfo {{
+                    format!("fn distance_shape_{i}(ray_position: vec3<f32>, ray_direction: vec3<f32>, index: u32, auxiliary_offset: u32) -> DistanceInfo {{
     let current = shape_{i}[index];
     {}
 }}", info.distance_function),
-                    format!("fn normal_shape_{i}(world_position: vec3<f32>, index: u32) -> vec3<f32> {{
+                    format!("fn normal_shape_{i}(world_position: vec3<f32>, index: u32, auxiliary_offset: u32) -> vec3<f32> {{
     let current = shape_{i}[index];
     {}
 }}", info.normal_function),
-                    format!("fn bounding_box_shape_{i}(index: u32) -> BoundingBox {{
+                    format!("fn bounding_box_shape_{i}(index: u32, auxiliary_offset: u32) -> BoundingBox {{
     let current = shape_{i}[index];
     {}
 }}", info.bounding_box_function)
@@ -242,39 +511,39 @@ var<storage, read> shape_{i}: array<Shape{i}>;"),
                  a.4 + &b.4)
             });
         let dst_func = format!(
-            "fn calculate_distance(ray_pos: vec3<f32>, ray_dir: vec3<f32>, object_id: u32, object_index: u32) -> DistanceInfo {{\
+            "fn calculate_distance(ray_pos: vec3<f32>, ray_dir: vec3<f32>, object_id: u32, object_index: u32, auxiliary_offset: u32) -> DistanceInfo {{\
                 switch (object_id) {{
                     {cases}
                     default: {{return DistanceInfo(false, 0.0);}}
                 }}
             }}",
             cases=objects.values()
-                .map(|i| format!("case {i}u: {{return distance_shape_{i}(ray_pos, ray_dir, object_index);}}", i=i.shape_id))
+                .map(|i| format!("case {i}u: {{return distance_shape_{i}(ray_pos, ray_dir, object_index, auxiliary_offset);}}", i=i.shape_id))
                 .collect::<Vec<_>>()
                 .join("\n")
         );
 
         let normal_func = format!(
-            "fn calculate_normal(ray_pos: vec3<f32>, object_id: u32, object_index: u32) -> vec3<f32> {{\
+            "fn calculate_normal(ray_pos: vec3<f32>, object_id: u32, object_index: u32, auxiliary_offset: u32) -> vec3<f32> {{\
                 switch (object_id) {{
                     {}
                     default: {{return vec3<f32>(1.0, 0.0, 0.0);}}
                 }}
             }}",
             objects.values()
-                .map(|i| format!("case {i}u: {{return normal_shape_{i}(ray_pos, object_index);}}", i=i.shape_id))
+                .map(|i| format!("case {i}u: {{return normal_shape_{i}(ray_pos, object_index, auxiliary_offset);}}", i=i.shape_id))
                 .collect::<Vec<_>>()
                 .join("\n")
         );
         let bounding_box_func = format!(
-            "fn bounding_box(shape_id: u32, object_index: u32) -> BoundingBox {{
+            "fn bounding_box(shape_id: u32, object_index: u32, auxiliary_offset: u32) -> BoundingBox {{
     switch (shape_id) {{
         {}
         default: {{return BoundingBox(false, vec3<f32>(0.0, 0.0, 0.0), vec3<f32>(0.0, 0.0, 0.0));}}
     }}
 }}",
             objects.values()
-                .map(|i| format!("case {i}u: {{return bounding_box_shape_{i}(object_index);}}", i=i.shape_id))
+                .map(|i| format!("case {i}u: {{return bounding_box_shape_{i}(object_index, auxiliary_offset);}}", i=i.shape_id))
                 .collect::<Vec<_>>()
                 .join("\n")
         );
@@ -324,6 +593,8 @@ impl State<'_> {
                 }
             };
         }
+        let environment_view = self.environment_texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let accumulation_view = self.accumulation[0].create_view(&wgpu::TextureViewDescriptor::default());
         self.device.create_bind_group(&wgpu::BindGroupDescriptor {
             label: Some("raytracing builtin bind group"),
             layout: &Self::creat_builtin_bind_group_layout(&self.device),
@@ -332,6 +603,21 @@ impl State<'_> {
                 entry!(1, aspect_ratio_buffer),
                 entry!(2, config_buffer),
                 entry!(3, object_data),
+                entry!(4, auxiliary_buffer),
+                entry!(5, light_buffer),
+                entry!(6, environment_buffer),
+                wgpu::BindGroupEntry {
+                    binding: 7,
+                    resource: wgpu::BindingResource::TextureView(&environment_view),
+                },
+                entry!(8, environment_cdf),
+                entry!(9, tone_mapping_buffer),
+                entry!(10, frame_buffer),
+                wgpu::BindGroupEntry {
+                    binding: 11,
+                    resource: wgpu::BindingResource::TextureView(&accumulation_view),
+                },
+                entry!(12, aperture_cdf),
             ]
         })
     }
@@ -351,9 +637,194 @@ impl State<'_> {
         self.device.create_bind_group(&wgpu::BindGroupDescriptor {
             label: Some("raytracing objects bind group"),
             layout: &Self::create_bind_group_layout_for_objects(&self.device, self.objects.len()),
-            entries: &*self.objects.values_mut()
+            entries: &self.objects.values_mut()
                 .map(|info| entry!(info.shape_id as u32, &mut info.buffer))
                 .collect::<Vec<_>>()
         })
     }
-}
\ No newline at end of file
+}
+/// Serializes an environment for the shader.
+///
+/// returns: (Vec<u8>, Vec<u8>, Vec<u8>)
+///     The uniform with the kind, size and colors, the pixels of the texture and the summed up sampling weights
+fn serialize_environment(environment: &Environment) -> (Vec<u8>, Vec<u8>, Vec<u8>) {
+    // kind, width, height, intensity, two colors
+    let (kind, width, height, intensity, first, second) = match environment {
+        Environment::Color(color) => (0u32, 1u32, 1u32, 1.0, *color, *color),
+        Environment::Gradient { bottom, top } => (1, 1, 1, 1.0, *bottom, *top),
+        Environment::Map(map) => (2, map.width() as u32, map.height() as u32, map.intensity, Vector3::zeros(), Vector3::zeros()),
+        Environment::Sky(sky) => (3, 1, 1, sky.intensity, Vector3::zeros(), Vector3::zeros()),
+    };
+    // the coefficients of the sky model, see `Sky`
+    let (perez, zenith, sun_radiance, ground_radiance, sun) = match environment {
+        Environment::Sky(sky) => {
+            let (perez, zenith, sun_radiance, ground_radiance) = sky.coefficients();
+            (perez, zenith, sun_radiance, ground_radiance, sky.sun_direction().norm())
+        }
+        _ => ([Vector3::zeros(); 5], Vector3::zeros(), Vector3::zeros(), Vector3::zeros(), Vector3::z()),
+    };
+    let data = kind.to_le_bytes().into_iter()
+        .chain(width.to_le_bytes())
+        .chain(height.to_le_bytes())
+        .chain(intensity.serialize())
+        .chain(first.serialize())
+        .chain([0; 4]) // alignment
+        .chain(second.serialize())
+        .chain([0; 4]) // alignment
+        .chain(sun.serialize())
+        .chain(SUN_ANGULAR_RADIUS.cos().serialize())
+        .chain(sun_radiance.serialize())
+        .chain((1.0 / sun_solid_angle()).serialize())
+        .chain(ground_radiance.serialize())
+        .chain([0; 4])
+        .chain(zenith.serialize())
+        .chain([0; 4])
+        .chain(perez.iter().flat_map(|coefficient| coefficient.serialize().into_iter().chain([0; 4])))
+        .collect();
+    match environment {
+        Environment::Map(map) => {
+            let pixels = map.pixels().iter()
+                .flat_map(|pixel| [pixel[0], pixel[1], pixel[2], 1.0])
+                .flat_map(f32::to_le_bytes)
+                .collect();
+            let cdf = map.row_cdf().iter()
+                .chain(map.pixel_cdf())
+                .flat_map(|sum| sum.serialize())
+                .collect();
+            (data, pixels, cdf)
+        }
+        _ => (data, [0f32, 0.0, 0.0, 1.0].into_iter().flat_map(f32::to_le_bytes).collect(), vec![0; 4]),
+    }
+}
+/// The summed up weights of the rows of the aperture image of the camera, then of the pixels within each row.
+/// Bindings can't be empty, so other apertures get a single unused entry.
+fn serialize_aperture_cdf(camera: &Camera) -> Vec<u8> {
+    match camera.lens.as_ref().map(|lens| &lens.aperture) {
+        Some(Aperture::Image(image)) => image.row_cdf().iter()
+            .chain(image.pixel_cdf())
+            .flat_map(|sum| sum.serialize())
+            .collect(),
+        _ => vec![0; 4],
+    }
+}
+/// Serializes the tone mapping for the shader.
+///
+/// returns: Vec<u8>
+///     16 bytes: the operator (0 for none), the exposure as a factor, the white point and whether to encode sRGB
+fn serialize_tone_mapping(tone_mapping: Option<&ToneMapping>, encode_srgb: bool) -> Vec<u8> {
+    let (operator, exposure, white) = match tone_mapping {
+        None => (0u32, 0.0, 1.0),
+        Some(tone_mapping) => {
+            let (operator, white) = match tone_mapping.operator {
+                ToneMapOperator::Clamp => (1, 1.0),
+                ToneMapOperator::Reinhard => (2, 1.0),
+                ToneMapOperator::ExtendedReinhard { white } => (3, white),
+                ToneMapOperator::Aces => (4, 1.0),
+                ToneMapOperator::Agx => (5, 1.0),
+            };
+            (operator, tone_mapping.exposure, white)
+        }
+    };
+    operator.to_le_bytes().into_iter()
+        .chain(2f64.powf(exposure).serialize())
+        .chain(white.serialize())
+        .chain((encode_srgb as u32).to_le_bytes())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    /// the random numbers of the shader, kept apart so they can run on their own
+    const RNG_SHADER: &str = include_str!("rng.wgsl");
+    use std::future::Future;
+    use std::pin::pin;
+    use std::task::{Context, Poll, Waker};
+
+    /// the futures of the native backends are ready right away, so polling them is enough
+    fn block_on<F: Future>(future: F) -> F::Output {
+        let mut future = pin!(future);
+        let mut context = Context::from_waker(Waker::noop());
+        loop {
+            if let Poll::Ready(output) = future.as_mut().poll(&mut context) {
+                return output;
+            }
+        }
+    }
+
+    /// runs `initRngSeed` for every pixel of a `size` × `size` target and returns the seeds row by row
+    fn pixel_seeds(size: u32, frame_index: u32) -> Option<Vec<u32>> {
+        let instance = wgpu::Instance::default();
+        let adapter = block_on(instance.request_adapter(&wgpu::RequestAdapterOptions::default()))?;
+        let (device, queue) = block_on(adapter.request_device(&wgpu::DeviceDescriptor::default(), None)).ok()?;
+        let shader = format!("{RNG_SHADER}
+@group(0) @binding(0) var<storage, read_write> seeds: array<u32>;
+@compute @workgroup_size(8, 8)
+fn main(@builtin(global_invocation_id) pixel: vec3<u32>) {{
+    initRngSeed(pixel.xy, {frame_index}u);
+    seeds[pixel.y * {size}u + pixel.x] = rng_seed;
+}}");
+        let module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("rng test shader"),
+            source: wgpu::ShaderSource::Wgsl(shader.into()),
+        });
+        let pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            label: Some("rng test pipeline"),
+            layout: None,
+            module: &module,
+            entry_point: Some("main"),
+            compilation_options: Default::default(),
+            cache: None,
+        });
+        let byte_count = u64::from(size * size) * 4;
+        let storage = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("rng test seeds"),
+            size: byte_count,
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_SRC,
+            mapped_at_creation: false,
+        });
+        let readback = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("rng test readback"),
+            size: byte_count,
+            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+            mapped_at_creation: false,
+        });
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("rng test bind group"),
+            layout: &pipeline.get_bind_group_layout(0),
+            entries: &[wgpu::BindGroupEntry { binding: 0, resource: storage.as_entire_binding() }],
+        });
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor::default());
+        {
+            let mut pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor::default());
+            pass.set_pipeline(&pipeline);
+            pass.set_bind_group(0, &bind_group, &[]);
+            pass.dispatch_workgroups(size / 8, size / 8, 1);
+        }
+        encoder.copy_buffer_to_buffer(&storage, 0, &readback, 0, byte_count);
+        queue.submit([encoder.finish()]);
+        readback.slice(..).map_async(wgpu::MapMode::Read, |_| {});
+        device.poll(wgpu::Maintain::Wait);
+        let seeds = readback.slice(..).get_mapped_range()
+            .chunks_exact(4)
+            .map(|bytes| u32::from_le_bytes(bytes.try_into().unwrap()))
+            .collect();
+        Some(seeds)
+    }
+
+    #[test]
+    fn neighbouring_pixels_get_other_seeds() {
+        let size = 64;
+        let Some(seeds) = pixel_seeds(size, 7) else {
+            eprintln!("no adapter that can run compute shaders, skipping");
+            return;
+        };
+        let seed = |x: u32, y: u32| seeds[(y * size + x) as usize];
+        // every quadrant of the target, the uv coordinates of the old seed all clamped to one value in the top right
+        for (x, y) in (0..size - 1).flat_map(|y| (0..size - 1).map(move |x| (x, y))) {
+            assert_ne!(seed(x, y), seed(x + 1, y), "pixel ({x}, {y}) and its right neighbour");
+            assert_ne!(seed(x, y), seed(x, y + 1), "pixel ({x}, {y}) and its upper neighbour");
+        }
+        let next_frame = pixel_seeds(size, 8).unwrap();
+        assert!(seeds.iter().zip(&next_frame).all(|(a, b)| a != b), "every pixel gets another seed in the next frame");
+    }
+}
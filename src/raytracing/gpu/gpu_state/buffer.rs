@@ -71,7 +71,7 @@ impl<'a> FrequentlyChangedBuffer<'a> {
         match self.state {
             BufferChangeState::Unchanged => {}
             BufferChangeState::Changed => {
-                self.buffer = Self::create_init_buffer(&self.device, self.label, &*self.data)
+                self.buffer = Self::create_init_buffer(&self.device, self.label, &self.data)
             }
             BufferChangeState::ChangedOnly => {
                 queue.write_buffer(&self.buffer, 0, &self.data);
@@ -88,13 +88,13 @@ impl<'a> FrequentlyChangedBuffer<'a> {
         self.state = BufferChangeState::Unchanged;
         &self.buffer
     }
-    pub fn append(&mut self, data: impl IntoIterator<Item = u8>) -> () {
+    pub fn append(&mut self, data: impl IntoIterator<Item = u8>) {
         self.data.extend(data);
         if self.state == BufferChangeState::Unchanged {
             self.state = BufferChangeState::Appended;
         }
     }
-    pub fn set_data(&mut self, data: Vec<u8>) -> () {
+    pub fn set_data(&mut self, data: Vec<u8>) {
         if self.data.len() == data.len() && !self.state.got_resized() {
             self.state = BufferChangeState::ChangedOnly;
         } else {
@@ -102,15 +102,4 @@ impl<'a> FrequentlyChangedBuffer<'a> {
         }
         self.data = data;
     }
-    pub fn change_data(&mut self, data: Vec<u8>, start_index: usize) {
-        if data.len() + start_index > self.data.len() {
-            self.data.resize(data.len() + start_index, 0);
-            self.state = BufferChangeState::Changed;
-        } else if !self.state.got_resized() {
-            self.state = BufferChangeState::ChangedOnly;
-        } else {
-            self.state = BufferChangeState::Changed;
-        }
-        self.data[start_index..start_index + data.len()].copy_from_slice(data.as_slice());
-    }
 }
\ No newline at end of file
@@ -1,12 +1,13 @@
 use crate::math::Vector3;
-use std::sync::{Arc, Mutex};
+use crate::object::Dielectric;
+use std::sync::Arc;
 use crate::raytracing::gpu::GpuSerialize;
 
 /// An object that can be raytraced/raymarched
 #[derive(Clone)]
 pub struct Object {
     /// The shape of the Object
-    pub(crate) shape: Arc<Mutex<dyn GpuShape + Send + Sync>>,
+    pub(crate) shape: Arc<dyn GpuShape + Send + Sync>,
     /// The material of the Object
     pub material: Material,
 }
@@ -20,7 +21,7 @@ impl Object {
     ///
     /// returns: Object
     pub fn new<T: GpuShape + Send + Sync + 'static>(shape: T, material: Material) -> Self {
-        let shape = Arc::new(Mutex::new(shape));
+        let shape = Arc::new(shape);
         Self { shape, material }
     }
     // pub(crate) fn to_gpu_object(&self, object_id: u64) -> GPUSendableObject {
@@ -31,13 +32,18 @@ impl Object {
     //         object_id,
     //     }
     // }
-    pub(crate) fn gpu_serialize(&self, object_id: u32, object_index: u32) -> Vec<u8> {
+    pub(crate) fn gpu_serialize(&self, object_id: u32, object_index: u32, auxiliary_offset: u32) -> Vec<u8> {
         self.material.base_color.serialize().into_iter()
             .chain(self.material.roughness.serialize())
             .chain(self.material.emission_color.serialize())
             .chain(object_id.to_le_bytes())
             .chain(object_index.to_le_bytes())
-            .chain([0;4 * 3]) // alignment
+            .chain(auxiliary_offset.to_le_bytes())
+            // an index of refraction of 0 marks opaque objects
+            .chain(self.material.dielectric.map_or(0.0, |d| d.ior).serialize())
+            .chain(self.material.metallic.serialize())
+            .chain(self.material.dielectric.map_or(Vector3::zeros(), |d| d.absorption).serialize())
+            .chain([0;4]) // alignment
             .collect::<Vec<_>>()
     }
 }
@@ -50,6 +56,7 @@ pub trait GpuShape: GpuSerialize {
     /// generates the wgsl code for generating the distance.
     ///
     /// function takes two `vec3<f32>`s. 1st one is the position, 2nd one is the direction in world space.
+    /// The data of the object is available as `current`, the start of its [GpuShape::auxiliary_data] as `auxiliary_offset`.
     /// The function should return a DistanceInfo object which is constructed via 1 bool and 1 f32.
     /// The boolean dictates, whether the object was actually hit or not and the f32 dictates the distance.
     /// If the Object isn't hit (boolean  is false) the distance is ignored.
@@ -61,6 +68,14 @@ pub trait GpuShape: GpuSerialize {
     fn normal_calculation_code(&self) -> String;
     fn object_type(&self) -> String;
     fn bounding_box_code(&self) -> String;
+    /// Additional data of variable size that doesn't fit into the struct of the shape (e.g. the triangles of a mesh).
+    ///
+    /// The data of all objects is put into one storage buffer `auxiliary: array<vec4<f32>>`,
+    /// so it has to be a multiple of 16 bytes long. Integers can be stored by bit-casting them.
+    /// The generated functions get the index of the first entry of this object as `auxiliary_offset: u32`.
+    fn auxiliary_data(&self) -> Vec<u8> {
+        Vec::new()
+    }
 }
 /// represents the material of an [Object]
 #[derive(Clone, Debug)]
@@ -69,16 +84,41 @@ pub struct Material {
     pub base_color: Vector3,
     /// The color of emissions on the object.
     pub emission_color: Vector3,
-    /// How rough the material is.
-    /// 0 means rays are only reflected, 1 means rays bounce randomly.
-    ///
-    /// The lower the number, the more the rays bounce towards a full reflection.
+    /// How rough the material is, see [crate::object::Material::roughness].
     pub roughness: f64,
+    /// How metallic the material is, see [crate::object::Material::metallic].
+    pub metallic: f64,
+    /// Makes the object transparent, see [Dielectric]. [None] for opaque objects.
+    pub dielectric: Option<Dielectric>,
 }
 impl Material  {
-    /// creates a new material with the given specs
+    /// Creates a new material with the given specs, as metallic as it is smooth, see [crate::object::Material::new].
     pub const fn new(base_color: Vector3, emission_color: Vector3, roughness: f64) -> Self {
-        Self { base_color, emission_color, roughness }
+        Self { base_color, emission_color, roughness, metallic: 1f64 - roughness, dielectric: None }
+    }
+    /// Creates a new metal, see [crate::object::Material::metal].
+    pub const fn metal(color: Vector3, roughness: f64) -> Self {
+        let mut material = Self::new(color, Vector3::zeros(), roughness);
+        material.metallic = 1f64;
+        material
+    }
+    /// returns a copy of the material with the given [Material::metallic]
+    pub fn with_metallic(&self, metallic: f64) -> Self {
+        Self { metallic, ..self.clone() }
+    }
+    /// Creates a new clear and smooth transparent material, see [crate::object::Material::glass].
+    pub const fn glass(ior: f64) -> Self {
+        Self::dielectric(ior, Vector3::zeros())
+    }
+    /// Creates a new smooth transparent material that absorbs light inside it, see [crate::object::Material::dielectric].
+    pub const fn dielectric(ior: f64, absorption: Vector3) -> Self {
+        Self {
+            base_color: Vector3::ones(),
+            emission_color: Vector3::zeros(),
+            roughness: 0f64,
+            metallic: 0f64,
+            dielectric: Some(Dielectric { ior, absorption }),
+        }
     }
     /// Creates a new material with only a color component.
     ///
@@ -118,8 +158,9 @@ impl Material  {
     pub const fn light(light_color: Vector3) -> Self {
         Self::new(Vector3::zeros(), light_color, 1f64)
     }
+    /// Creates a perfect mirror
     pub const fn mirror() -> Self {
-        Self::new(Vector3::ones(), Vector3::zeros(), 0f64)
+        Self::metal(Vector3::ones(), 0f64)
     }
 }
 
@@ -0,0 +1,27 @@
+// rng, in its own file so the tests can run it without the rest of the shader
+var<private> rng_seed: u32 = 0u;
+fn initRngSeed(pixel: vec2<u32>, frame_index: u32) {
+    // Hashes the integer pixel coordinates one after the other, together with the frame index.
+    // Every step of `jenkinsHash` can be undone, so the pixels of a row never share a seed.
+    // The frame gets hashed on its own, so neighbouring frames don't give neighbouring seeds.
+    let frame_hash = jenkinsHash(frame_index * 0x9e3779b9u);
+    rng_seed = jenkinsHash(pixel.x ^ jenkinsHash(pixel.y ^ frame_hash));
+}
+fn jenkinsHash(input: u32) -> u32 {
+  var x = input;
+  x += x << 10u;
+  x ^= x >> 6u;
+  x += x << 3u;
+  x ^= x >> 11u;
+  x += x << 15u;
+  return x;
+}
+fn random_int() -> u32 {
+    let newSeed = rng_seed * 747796405u + 2891336453u;
+    rng_seed = newSeed;
+    let word = ((newSeed >> ((newSeed >> 28u) + 4u)) ^ newSeed) * 277803737u;
+    return (word >> 22u) ^ word;
+}
+fn random_float() -> f32 {
+    return f32(random_int()) / f32(0xffffffffu);
+}
@@ -1,19 +1,26 @@
 use crate::raytracing::gpu::gpu_state::State;
 use crate::raytracing::gpu::object::Object;
+use crate::raytracing::environment::Environment;
+use crate::raytracing::light::Light;
+use crate::raytracing::tone_mapping::ToneMapping;
 use crate::{Camera, Config};
 use wgpu::{ColorTargetState, Device, Queue, TextureView};
 
 pub struct Scene<'a> {
     camera: Camera,
     objects: Vec<Object>,
+    lights: Vec<Light>,
+    environment: Environment,
     state: State<'a>
 }
 impl<'a> Scene<'a> {
     pub fn new(camera: Camera, device: &Device, targets: Vec<Option<ColorTargetState>>, config: Config) -> Self {
-        let state = State::new(&device, targets, &camera, config);
+        let state = State::new(device, targets, &camera, config);
         Self {
             camera,
             objects: Vec::new(),
+            lights: Vec::new(),
+            environment: Environment::default(),
             state,
         }
     }
@@ -24,7 +31,54 @@ impl<'a> Scene<'a> {
         self.objects.push(object.clone());
         self.state.add_object(object);
     }
+    pub fn add_light(&mut self, light: Light) {
+        self.lights.push(light);
+        self.state.add_light(light);
+    }
+    /// sets what rays that don't hit any object see
+    pub fn set_environment(&mut self, environment: Environment) {
+        self.state.set_environment(&environment);
+        self.environment = environment;
+    }
+    /// Tone maps the colors before they are written to the target, [None] writes the linear colors.
+    /// Targets without an sRGB format also get the sRGB transfer function applied.
+    pub fn set_tone_mapping(&mut self, tone_mapping: Option<ToneMapping>) {
+        self.state.set_tone_mapping(tone_mapping);
+    }
+    /// moves the camera, which starts the accumulation of [Scene::render_progressive] over
+    pub fn set_camera(&mut self, camera: Camera) {
+        self.state.set_camera(&camera);
+        self.camera = camera;
+    }
+    pub fn get_camera(&self) -> &Camera {
+        &self.camera
+    }
+    /// Renders a frame on its own. Every frame gets other random numbers, so the noise changes from frame to frame.
     pub fn render(&mut self, view: &TextureView, aspect_ratio: f32, queue: &Queue) {
         self.state.render(aspect_ratio, queue, view)//, &self.objects)
     }
+    /// Renders one more frame and writes the average of all frames since the last change to the view,
+    /// like [ProgressiveRenderer::step](crate::ProgressiveRenderer::step) and [ProgressiveRenderer::image](crate::ProgressiveRenderer::image) do on the CPU.
+    ///
+    /// The averages are kept in a pair of textures with the size of the view, that take turns being read and written.
+    /// Adding objects or lights, or changing the camera, environment or size starts the average over.
+    /// Only the first target is written.
+    ///
+    /// # Arguments
+    ///
+    /// * `view`: The first target, to which the tone mapped average is written
+    /// * `width`: The width of the view in pixels
+    /// * `height`: The height of the view in pixels
+    /// * `queue`: The queue of the device of the scene
+    pub fn render_progressive(&mut self, view: &TextureView, width: u32, height: u32, queue: &Queue) {
+        self.state.render_accumulated(width, height, queue, view)
+    }
+    /// starts the average of [Scene::render_progressive] over, for changes that the scene can't see
+    pub fn reset_accumulation(&mut self) {
+        self.state.reset_accumulation();
+    }
+    /// the frames in the current average of [Scene::render_progressive]
+    pub fn accumulated_frames(&self) -> u32 {
+        self.state.accumulated_frames()
+    }
 }
\ No newline at end of file
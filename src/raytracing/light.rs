@@ -0,0 +1,179 @@
+//! Lights without a surface: they can't be seen or hit by rays, they only light up the objects around them.
+use crate::math::Vector3;
+use crate::raytracing::bsdf::orthonormal_basis;
+#[cfg(feature = "gpu")]
+use crate::raytracing::gpu::GpuSerialize;
+use std::f64::consts::PI;
+
+/// A punctual light source.
+///
+/// The color is multiplied with the intensity. For point and spot lights the intensity is the radiant intensity,
+/// so the light reaching a surface falls off with the squared distance.
+/// For directional lights it is the irradiance of a surface facing the light.
+///
+/// # Examples
+///
+/// ```
+/// use rtx::light::Light;
+/// use rtx::math::Vector3;
+/// use rtx::Scene;
+///
+/// let mut scene = Scene::default();
+/// scene.add_light(Light::point(Vector3::new(0, 0, 5), Vector3::ones(), 20.0));
+/// // the sun, shining down at an angle
+/// scene.add_light(Light::directional(Vector3::new(1, 0, -1), 0.53f64.to_radians(), Vector3::new(1.0, 0.95, 0.9), 3.0));
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Light {
+    /// Shines equally in all directions from one point.
+    Point {
+        position: Vector3,
+        color: Vector3,
+        intensity: f64,
+    },
+    /// Shines from one point into a cone.
+    Spot {
+        position: Vector3,
+        /// The direction the spot points to, doesn't have to be normalized
+        direction: Vector3,
+        /// The angle between the direction and the edge of the fully lit inner cone, in radians
+        inner_angle: f64,
+        /// The angle between the direction and the edge of the cone, in radians. The light fades out between the two cones.
+        outer_angle: f64,
+        color: Vector3,
+        intensity: f64,
+    },
+    /// Light from very far away, like the sun.
+    Directional {
+        /// The direction the light travels in, doesn't have to be normalized
+        direction: Vector3,
+        /// The apparent diameter of the light source in radians, 0 for perfectly sharp shadows
+        angular_diameter: f64,
+        color: Vector3,
+        intensity: f64,
+    },
+}
+/// The light arriving at a point from a [Light]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) struct LightContribution {
+    /// The normalized direction from the point towards the light
+    pub direction: Vector3,
+    /// The distance to the light, infinite for directional lights
+    pub distance: f64,
+    /// The irradiance arriving at a surface perpendicular to `direction`
+    pub irradiance: Vector3,
+}
+impl Light {
+    /// creates a new point light
+    pub const fn point(position: Vector3, color: Vector3, intensity: f64) -> Self {
+        Self::Point { position, color, intensity }
+    }
+    /// creates a new spot light, the angles are in radians
+    pub const fn spot(position: Vector3, direction: Vector3, inner_angle: f64, outer_angle: f64, color: Vector3, intensity: f64) -> Self {
+        Self::Spot { position, direction, inner_angle, outer_angle, color, intensity }
+    }
+    /// creates a new directional light, the angular diameter is in radians
+    pub const fn directional(direction: Vector3, angular_diameter: f64, color: Vector3, intensity: f64) -> Self {
+        Self::Directional { direction, angular_diameter, color, intensity }
+    }
+    /// Calculates the light that arrives at a point.
+    ///
+    /// # Arguments
+    ///
+    /// * `position`: The point that is lit.
+    /// * `u`: Two uniformly distributed random numbers in `[0, 1)`, used to pick a direction within the disk of directional lights.
+    ///
+    /// returns: Option<LightContribution>
+    ///     [None] if no light arrives (e.g. outside the cone of a spot light)
+    pub(crate) fn sample(&self, position: Vector3, u: (f64, f64)) -> Option<LightContribution> {
+        match *self {
+            Light::Point { position: light_position, color, intensity } => {
+                let to_light = light_position - position;
+                let distance = to_light.len();
+                Some(LightContribution { direction: to_light / distance, distance, irradiance: color * (intensity / (distance * distance)) })
+            }
+            Light::Spot { position: light_position, direction, inner_angle, outer_angle, color, intensity } => {
+                let to_light = light_position - position;
+                let distance = to_light.len();
+                let falloff = spot_falloff(-to_light.dot(direction.norm()) / distance, inner_angle.cos(), outer_angle.cos());
+                if falloff <= 0.0 {
+                    return None;
+                }
+                Some(LightContribution { direction: to_light / distance, distance, irradiance: color * (intensity * falloff / (distance * distance)) })
+            }
+            Light::Directional { direction, angular_diameter, color, intensity } => {
+                let to_light = -direction.norm();
+                // uniformly within the cone of directions the light comes from
+                let cos_max = (angular_diameter / 2.0).cos();
+                let cos_theta = 1.0 - u.0 * (1.0 - cos_max);
+                let sin_theta = (1.0 - cos_theta * cos_theta).max(0.0).sqrt();
+                let phi = 2.0 * PI * u.1;
+                let (tangent, bitangent) = orthonormal_basis(to_light);
+                let direction = tangent * (sin_theta * phi.cos()) + bitangent * (sin_theta * phi.sin()) + to_light * cos_theta;
+                Some(LightContribution { direction, distance: f64::INFINITY, irradiance: color * intensity })
+            }
+        }
+    }
+}
+/// smoothly fades from 1 inside the inner cone to 0 outside the outer cone
+fn spot_falloff(cos_angle: f64, cos_inner: f64, cos_outer: f64) -> f64 {
+    if cos_inner <= cos_outer {
+        return if cos_angle >= cos_outer { 1.0 } else { 0.0 };
+    }
+    let t = ((cos_angle - cos_outer) / (cos_inner - cos_outer)).clamp(0.0, 1.0);
+    t * t * (3.0 - 2.0 * t)
+}
+#[cfg(feature = "gpu")]
+impl GpuSerialize for Light {
+    fn serialize(&self) -> Vec<u8> {
+        // kind, position, direction, color * intensity, cosines of the inner, outer and directional cone
+        let (kind, position, direction, radiant, cos_inner, cos_outer, cos_cone) = match *self {
+            Light::Point { position, color, intensity } =>
+                (1u32, position, Vector3::zeros(), color * intensity, 0.0, 0.0, 1.0),
+            Light::Spot { position, direction, inner_angle, outer_angle, color, intensity } =>
+                (2, position, direction.norm(), color * intensity, inner_angle.cos(), outer_angle.cos(), 1.0),
+            Light::Directional { direction, angular_diameter, color, intensity } =>
+                (3, Vector3::zeros(), direction.norm(), color * intensity, 0.0, 0.0, (angular_diameter / 2.0).cos()),
+        };
+        position.serialize().into_iter()
+            .chain(kind.to_le_bytes())
+            .chain(direction.serialize())
+            .chain(cos_inner.serialize())
+            .chain(radiant.serialize())
+            .chain(cos_outer.serialize())
+            .chain(cos_cone.serialize())
+            .chain([0; 12]) // alignment
+            .collect()
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn point_light_falls_off_with_squared_distance() {
+        let light = Light::point(Vector3::new(0, 0, 2), Vector3::ones(), 8.0);
+        let sample = light.sample(Vector3::zeros(), (0.5, 0.5)).unwrap();
+        assert_eq!(sample.direction, Vector3::z());
+        assert_eq!(sample.distance, 2.0);
+        assert_eq!(sample.irradiance, Vector3::ones() * 2);
+    }
+    #[test]
+    fn spot_light_fades_between_cones() {
+        let light = Light::spot(Vector3::zeros(), -Vector3::z(), 0.2, 0.4, Vector3::ones(), 1.0);
+        let at_angle = |angle: f64| light.sample(Vector3::new(angle.tan(), 0.0, -1.0), (0.5, 0.5))
+            .map_or(0.0, |sample| sample.irradiance.x * sample.distance * sample.distance);
+        assert!((at_angle(0.1) - 1.0).abs() < 1e-12);
+        assert!(at_angle(0.3) > 0.0 && at_angle(0.3) < 1.0);
+        assert_eq!(at_angle(0.5), 0.0);
+    }
+    #[test]
+    fn directional_light_stays_within_its_disk() {
+        let light = Light::directional(Vector3::new(1, 0, -1), 0.1, Vector3::ones(), 1.0);
+        for _ in 0..100 {
+            let sample = light.sample(Vector3::zeros(), (fastrand::f64(), fastrand::f64())).unwrap();
+            assert!((sample.direction.len() - 1.0).abs() < 1e-12);
+            assert!(sample.direction.dot(Vector3::new(-1, 0, 1).norm()) >= 0.05f64.cos() - 1e-12);
+        }
+    }
+}
@@ -1,6 +1,18 @@
+pub mod adaptive;
+pub mod aov;
 pub mod camera;
-pub(self) mod ray;
+pub mod control;
+pub mod denoise;
+pub mod bsdf;
+pub mod environment;
+pub mod film;
+pub mod light;
+pub mod motion;
+mod ray;
+pub mod sampler;
+mod bvh;
 pub mod object;
 pub mod scene;
+pub mod tone_mapping;
 #[cfg(feature = "gpu")]
 pub mod gpu;
\ No newline at end of file
@@ -0,0 +1,216 @@
+//! Motion blur: objects and cameras that move while the shutter is open.
+use crate::math::{BoundingBox, Vector3};
+
+/// The time span in which the camera rays are shot, see [Config::shutter](crate::Config::shutter).
+///
+/// Every camera ray gets a time between [Shutter::open] and [Shutter::close],
+/// and sees the objects and the camera where their [Motion] puts them at that time.
+/// With the same time for both, nothing is blurred.
+///
+/// # Examples
+///
+/// ```
+/// use rtx::motion::{Motion, Shutter, Transform};
+/// use rtx::object::{sphere::Sphere, Material, Object};
+/// use rtx::{Config, Scene};
+///
+/// let mut scene = Scene::default();
+/// // a 180° shutter, open for the first half of the frame
+/// scene.config = Config::default().with_shutter(Shutter::new(0.0, 0.5));
+/// // the ball moves 1 unit to the left within the frame
+/// let motion = Motion::linear(Transform::translation((5, 0, 0).into()), Transform::translation((5, 1, 0).into()));
+/// scene.add_object(Object::new(Sphere::new((0, 0, 0).into(), 0.5), Material::colored((1, 0, 0).into())).with_motion(motion));
+/// let film = scene.render(16, 8);
+/// ```
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Shutter {
+    /// The time the shutter opens
+    pub open: f64,
+    /// The time the shutter closes
+    pub close: f64,
+}
+impl Shutter {
+    pub fn new(open: f64, close: f64) -> Self {
+        Self { open, close }
+    }
+    /// the time at the fraction `u` of the way from opening to closing
+    pub(crate) fn time(&self, u: f64) -> f64 {
+        self.open + (self.close - self.open) * u
+    }
+}
+
+/// A rigid transform: a rotation around the origin, followed by a translation
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Transform {
+    pub translation: Vector3,
+    /// The axis of the rotation, scaled by the angle in radians (counterclockwise when the axis points at the viewer)
+    pub rotation: Vector3,
+}
+impl Transform {
+    pub fn new(translation: Vector3, rotation: Vector3) -> Self {
+        Self { translation, rotation }
+    }
+    /// a transform that only moves things
+    pub fn translation(translation: Vector3) -> Self {
+        Self::new(translation, Vector3::zeros())
+    }
+    /// a transform that only turns things by `angle` radians around `axis`
+    pub fn rotation(axis: Vector3, angle: f64) -> Self {
+        Self::new(Vector3::zeros(), axis.norm() * angle)
+    }
+    /// moves a point from the space of the transformed thing to world space
+    pub fn apply_point(&self, point: Vector3) -> Vector3 {
+        rotate(point, self.rotation) + self.translation
+    }
+    /// turns a direction from the space of the transformed thing to world space
+    pub fn apply_vector(&self, vector: Vector3) -> Vector3 {
+        rotate(vector, self.rotation)
+    }
+    /// moves a point from world space to the space of the transformed thing
+    pub fn inverse_point(&self, point: Vector3) -> Vector3 {
+        rotate(point - self.translation, -self.rotation)
+    }
+    /// turns a direction from world space to the space of the transformed thing
+    pub fn inverse_vector(&self, vector: Vector3) -> Vector3 {
+        rotate(vector, -self.rotation)
+    }
+    /// blends the translations and the rotations linearly, `t` = 0 gives this transform and `t` = 1 the other one
+    fn lerp(&self, other: &Transform, t: f64) -> Self {
+        Self {
+            translation: self.translation + (other.translation - self.translation) * t,
+            rotation: self.rotation + (other.rotation - self.rotation) * t,
+        }
+    }
+}
+/// rotates a vector around the axis of `rotation` by its length (Rodrigues' rotation formula)
+fn rotate(vector: Vector3, rotation: Vector3) -> Vector3 {
+    let angle = rotation.len();
+    if angle == 0.0 {
+        return vector;
+    }
+    let axis = rotation / angle;
+    let (sin, cos) = angle.sin_cos();
+    vector * cos + axis.cross(vector) * sin + axis * (axis.dot(vector) * (1.0 - cos))
+}
+
+/// Transforms at points in time (keyframes) that are blended linearly in between.
+/// Before the first keyframe and after the last one, things stay where those keyframes put them.
+///
+/// Rotations are blended by their [axis and angle](Transform::rotation), which keeps turns around one axis even.
+/// Turns around changing axes need more keyframes to follow a specific path.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Motion {
+    /// sorted by time
+    keyframes: Vec<(f64, Transform)>,
+}
+impl Motion {
+    /// Creates a motion through the given keyframes.
+    ///
+    /// # Arguments
+    ///
+    /// * `keyframes`: The times and the transforms at them, in any order
+    ///
+    /// returns: Motion
+    ///
+    /// # Panics
+    ///
+    /// If there are no keyframes.
+    pub fn new(mut keyframes: Vec<(f64, Transform)>) -> Self {
+        assert!(!keyframes.is_empty(), "a motion needs at least one keyframe");
+        keyframes.sort_by(|(a, _), (b, _)| a.total_cmp(b));
+        Self { keyframes }
+    }
+    /// a motion from `start` at time 0 to `end` at time 1
+    pub fn linear(start: Transform, end: Transform) -> Self {
+        Self::new(vec![(0.0, start), (1.0, end)])
+    }
+    /// the keyframes, sorted by time
+    pub fn keyframes(&self) -> &[(f64, Transform)] {
+        &self.keyframes
+    }
+    /// the transform at the given time
+    pub fn at(&self, time: f64) -> Transform {
+        let next = self.keyframes.partition_point(|(keyframe, _)| *keyframe <= time);
+        if next == 0 {
+            return self.keyframes[0].1;
+        }
+        let (start_time, start) = &self.keyframes[next - 1];
+        let Some((end_time, end)) = self.keyframes.get(next) else {
+            return *start;
+        };
+        start.lerp(end, (time - start_time) / (end_time - start_time))
+    }
+    /// Calculates a box that contains the given box at all times, transformed by the motion.
+    ///
+    /// # Arguments
+    ///
+    /// * `bounds`: The box in the space of the moving thing
+    ///
+    /// returns: BoundingBox
+    pub fn bounds(&self, bounds: &BoundingBox) -> BoundingBox {
+        if bounds.is_empty() {
+            return *bounds;
+        }
+        let corners = (0..8).map(|i| Vector3::new(
+            if i & 1 == 0 { bounds.min.x } else { bounds.max.x },
+            if i & 2 == 0 { bounds.min.y } else { bounds.max.y },
+            if i & 4 == 0 { bounds.min.z } else { bounds.max.z },
+        )).collect::<Vec<_>>();
+        let transformed = |transform: Transform| corners.iter()
+            .fold(BoundingBox::empty(), |moved, corner| moved.grow(transform.apply_point(*corner)));
+        let radius = corners.iter().map(|corner| corner.len()).fold(0.0, f64::max);
+        let mut result = transformed(self.keyframes[0].1);
+        for pair in self.keyframes.windows(2) {
+            let ((start_time, start), (end_time, end)) = (pair[0], pair[1]);
+            // the turn between two samples is at most the change of the rotation vector.
+            // Between samples, the corners leave the line between their sampled positions by less than radius * turn² / 8,
+            // which is doubled to be on the safe side
+            let steps = ((end.rotation - start.rotation).len() / MAX_BOUNDS_TURN).ceil().max(1.0);
+            let turn = (end.rotation - start.rotation).len() / steps;
+            for step in 1..=steps as usize {
+                let time = start_time + (end_time - start_time) * step as f64 / steps;
+                result = result.union(&transformed(self.at(time)));
+            }
+            result = result.expand(radius * turn * turn / 4.0);
+        }
+        result
+    }
+}
+/// the largest turn in radians between the samples of [Motion::bounds]
+const MAX_BOUNDS_TURN: f64 = 0.1;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::f64::consts::PI;
+
+    #[test]
+    fn keyframes_are_blended() {
+        let motion = Motion::new(vec![
+            (2.0, Transform::new(Vector3::new(0, 2, 0), Vector3::z() * PI)),
+            (0.0, Transform::translation(Vector3::zeros())),
+        ]);
+        assert_eq!(motion.at(-1.0), Transform::default());
+        assert_eq!(motion.at(3.0), motion.keyframes()[1].1);
+        let halfway = motion.at(1.0);
+        // a quarter turn around z and halfway along y
+        assert!((halfway.apply_point(Vector3::x()) - Vector3::new(0, 2, 0)).len() < 1e-12);
+        assert!((halfway.inverse_point(halfway.apply_point(Vector3::new(1, 2, 3))) - Vector3::new(1, 2, 3)).len() < 1e-12);
+        assert!((halfway.apply_vector(Vector3::y()) + Vector3::x()).len() < 1e-12);
+    }
+    #[test]
+    fn bounds_cover_the_whole_motion() {
+        let bounds = BoundingBox::new(Vector3::new(1.0, -0.1, -0.1), Vector3::new(1.2, 0.1, 0.1));
+        // a full turn around z, with the box far from the axis
+        let motion = Motion::linear(Transform::default(), Transform::new(Vector3::new(0, 0, 3), Vector3::z() * 2.0 * PI));
+        let moved = motion.bounds(&bounds);
+        for i in 0..=1000 {
+            let transform = motion.at(i as f64 / 1000.0);
+            for corner in [bounds.min, bounds.max, Vector3::new(1.2, 0.1, -0.1), Vector3::new(1.0, -0.1, 0.1)] {
+                assert!(moved.contains(transform.apply_point(corner)));
+            }
+        }
+        // but not much more
+        assert!(moved.extent().x < 2.5 && moved.extent().z < 3.3);
+    }
+}
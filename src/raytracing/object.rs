@@ -1,17 +1,26 @@
 pub mod sphere;
 pub mod plane;
 pub mod triangle;
+pub mod mesh;
 
-use crate::math::Vector3;
-use std::sync::{Arc, Mutex};
+use crate::math::{BoundingBox, Vector3};
+use crate::raytracing::bsdf::{Bsdf, Microfacet};
+use crate::raytracing::motion::Motion;
+use std::any::Any;
+use std::sync::Arc;
 
 /// An object that can be raytraced/raymarched
 #[derive(Clone)]
 pub struct Object {
-    /// The shape of the Object
-    shape: Arc<Mutex<dyn CustomShape + Send + Sync>>,
+    /// The shape of the Object, shared between clones.
+    /// Shapes only need `&self` to be intersected, so any number of threads can use it at once without locking.
+    shape: Arc<dyn CustomShape + Send + Sync>,
     /// The material of the Object
     pub material: Material,
+    /// Moves the shape while the shutter is open, see [Config::shutter](crate::Config::shutter).
+    /// The shape is in the space of the transforms, so rotations turn it around the origin.
+    /// Only the cpu renderer supports motion.
+    pub motion: Option<Motion>,
 }
 impl Object {
     /// Creates a new object
@@ -23,34 +32,116 @@ impl Object {
     ///
     /// returns: Object
     pub fn new<T: CustomShape + Send + Sync + 'static>(shape: T, material: Material) -> Self {
-        let shape = Arc::new(Mutex::new(shape));
-        Self { shape, material }
+        let shape = Arc::new(shape);
+        Self { shape, material, motion: None }
     }
-    /// Returns the normal at the given position.
-    /// Under the hood this is a call to [CustomShape::normal].
+    /// returns the object with the given [Object::motion]
+    pub fn with_motion(mut self, motion: Motion) -> Self {
+        self.motion = Some(motion);
+        self
+    }
+    /// Returns the normal at the given position and time.
+    /// Under the hood this is a call to [CustomShape::normal_at], moved by the [Object::motion].
     ///
     /// # Arguments
     ///
     /// * `world_pos`: The position in world-space where the normal is requested from.
+    /// * `time`: The time of the ray that hit the object.
     ///
     /// returns: Vector3
-    pub fn normal_at(&self, world_pos: Vector3) -> Vector3 {
-        self.shape.lock().unwrap().normal(world_pos).norm()
+    pub fn normal_at(&self, world_pos: Vector3, time: f64) -> Vector3 {
+        match &self.motion {
+            Some(motion) => {
+                let transform = motion.at(time);
+                transform.apply_vector(self.shape.normal_at(transform.inverse_point(world_pos), time)).norm()
+            }
+            None => self.shape.normal_at(world_pos, time).norm(),
+        }
     }
     /// Calculates the distance to the hit point.
-    /// This is just a call to [CustomShape::distance] under the hood
+    /// This is a call to [CustomShape::distance_at] under the hood, with the ray moved into the space of the [Object::motion].
     ///
     /// # Arguments
     ///
     /// * `ray_position`: The position of the ray in world space.
     /// * `ray_direction`: The direction of the ray in world space.
+    /// * `time`: The time of the ray.
     ///
     /// returns: Option<f64>
-    pub fn distance(&self, ray_position: Vector3, ray_direction: Vector3) -> Option<f64> {
-        self.shape.lock().unwrap().distance(ray_position, ray_direction)
+    pub fn distance(&self, ray_position: Vector3, ray_direction: Vector3, time: f64) -> Option<f64> {
+        match &self.motion {
+            // the transforms are rigid, so the distances stay the same
+            Some(motion) => {
+                let transform = motion.at(time);
+                self.shape.distance_at(transform.inverse_point(ray_position), transform.inverse_vector(ray_direction), time)
+            }
+            None => self.shape.distance_at(ray_position, ray_direction, time),
+        }
+    }
+    /// Returns whether a ray hitting the object at the given position enters it (or leaves it).
+    /// This is a call to [CustomShape::is_entering_at] under the hood, moved by the [Object::motion].
+    pub fn is_entering(&self, world_position: Vector3, ray_direction: Vector3, time: f64) -> bool {
+        match &self.motion {
+            Some(motion) => {
+                let transform = motion.at(time);
+                self.shape.is_entering_at(transform.inverse_point(world_position), transform.inverse_vector(ray_direction), time)
+            }
+            None => self.shape.is_entering_at(world_position, ray_direction, time),
+        }
+    }
+    /// Returns the surface area of the object or [None] if it can't be sampled.
+    /// This is just a call to [CustomShape::surface_area] under the hood
+    pub fn surface_area(&self) -> Option<f64> {
+        self.shape.surface_area()
+    }
+    /// Picks a uniformly distributed point on the surface of the object, where the [Object::motion] puts it at the given time.
+    /// This is a call to [CustomShape::sample_surface_at] under the hood
+    pub fn sample_surface(&self, u: (f64, f64), time: f64) -> Option<SurfaceSample> {
+        let sample = self.shape.sample_surface_at(u, time)?;
+        let Some(motion) = &self.motion else {
+            return Some(sample);
+        };
+        let transform = motion.at(time);
+        Some(SurfaceSample { position: transform.apply_point(sample.position), normal: transform.apply_vector(sample.normal) })
+    }
+    /// Returns the bounding box of the object over its whole [Object::motion] or [None] if it is unbounded.
+    /// This is a call to [CustomShape::bounding_box] under the hood
+    pub fn bounding_box(&self) -> Option<BoundingBox> {
+        let bounds = self.shape.bounding_box()?;
+        Some(match &self.motion {
+            Some(motion) => motion.bounds(&bounds),
+            None => bounds,
+        })
+    }
+    /// Calls `f` with the shape of the object, if it is a `T`.
+    ///
+    /// # Arguments
+    ///
+    /// * `f`: Gets called with the shape.
+    ///
+    /// returns: Option<R>
+    ///     The result of `f` or [None] if the shape is of another type.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rtx::math::Vector3;
+    /// use rtx::object::{Material, Object};
+    /// use rtx::object::sphere::Sphere;
+    ///
+    /// let object = Object::new(Sphere::new(Vector3::zeros(), 2.0), Material::mirror());
+    /// assert_eq!(object.with_shape(|sphere: &Sphere| sphere.radius), Some(2.0));
+    /// ```
+    pub fn with_shape<T: CustomShape, R>(&self, f: impl FnOnce(&T) -> R) -> Option<R> {
+        let shape: &dyn Any = &*self.shape;
+        shape.downcast_ref::<T>().map(f)
+    }
+    /// returns an identifier of the shape, which is shared by all clones of this object
+    pub(crate) fn shape_id(&self) -> usize {
+        Arc::as_ptr(&self.shape) as *const () as usize
     }
 }
-pub trait CustomShape {
+pub trait CustomShape: Any {
     /// Calculates the distance to the Object/Shape for a given ray.
     ///
     /// # Arguments
@@ -62,8 +153,8 @@ pub trait CustomShape {
     ///
     /// # Notes
     /// * This version of the renderer only supports raytracing.
-    /// This means, that the distance returned by this function is expected to be the distance to the hit point.
-    /// If the object is not hit, this function should return [None].
+    ///   This means, that the distance returned by this function is expected to be the distance to the hit point.
+    ///   If the object is not hit, this function should return [None].
     fn distance(&self, ray_position: Vector3, ray_direction: Vector3) -> Option<f64>;
     /// Calculates the normal vector of the Object/Shape at the given point.
     ///
@@ -73,6 +164,111 @@ pub trait CustomShape {
     ///
     /// returns: Vector3
     fn normal(&self, world_position: Vector3) -> Vector3;
+    /// Calculates the axis aligned box enclosing the Object/Shape in world-space.
+    ///
+    /// returns: Option<BoundingBox>
+    ///
+    /// # Notes
+    /// * Shapes that return a box are put into the acceleration structure of the [Scene](crate::Scene)
+    ///   and are only tested against rays that hit their box.
+    /// * Shapes that are infinite (like planes) or that can't tell their size should return [None].
+    ///   They are tested against every ray. This is the default.
+    /// * Shapes that change over time (see [CustomShape::distance_at]) have to be inside the box at all times.
+    fn bounding_box(&self) -> Option<BoundingBox> {
+        None
+    }
+    /// Calculates the distance to the Object/Shape for a ray at a point in time, for shapes that change while the shutter is open.
+    ///
+    /// # Arguments
+    ///
+    /// * `ray_position`: The position of the ray in world-space.
+    /// * `ray_direction`: The normalized direction of the ray in world-space.
+    /// * `time`: The time of the ray, see [Config::shutter](crate::Config::shutter).
+    ///
+    /// returns: Option<f64>
+    ///
+    /// # Notes
+    /// * The default ignores the time and calls [CustomShape::distance].
+    /// * Shapes that only move as a whole can use [Object::motion] instead.
+    /// * Shapes that can be sampled as lights also need [CustomShape::sample_surface_at].
+    fn distance_at(&self, ray_position: Vector3, ray_direction: Vector3, _time: f64) -> Option<f64> {
+        self.distance(ray_position, ray_direction)
+    }
+    /// Calculates the normal vector of the Object/Shape at the given point and time, see [CustomShape::distance_at].
+    /// The default ignores the time and calls [CustomShape::normal].
+    fn normal_at(&self, world_position: Vector3, _time: f64) -> Vector3 {
+        self.normal(world_position)
+    }
+    /// Tells, whether a ray that hits the Object/Shape at the given point enters or leaves it.
+    ///
+    /// # Arguments
+    ///
+    /// * `world_position`: The hit point in world space.
+    /// * `ray_direction`: The direction of the ray in world space.
+    ///
+    /// returns: bool
+    ///
+    /// # Notes
+    /// * This decides on which side of the surface a refracting ray is, see [Dielectric].
+    /// * The default assumes that [CustomShape::normal] points outwards.
+    fn is_entering(&self, world_position: Vector3, ray_direction: Vector3) -> bool {
+        ray_direction.dot(self.normal(world_position)) < 0.0
+    }
+    /// Tells, whether a ray that hits the Object/Shape at the given point and time enters or leaves it, see [CustomShape::distance_at].
+    /// The default ignores the time and calls [CustomShape::is_entering].
+    fn is_entering_at(&self, world_position: Vector3, ray_direction: Vector3, _time: f64) -> bool {
+        self.is_entering(world_position, ray_direction)
+    }
+    /// Calculates the area of the surface of the Object/Shape.
+    ///
+    /// returns: Option<f64>
+    ///
+    /// # Notes
+    /// * Emissive shapes that implement this and [CustomShape::sample_surface] are sampled directly as lights,
+    ///   which makes small lights a lot less noisy.
+    /// * Infinite shapes or shapes that can't be sampled should return [None]. This is the default.
+    fn surface_area(&self) -> Option<f64> {
+        None
+    }
+    /// Picks a point on the surface of the Object/Shape.
+    ///
+    /// # Arguments
+    ///
+    /// * `u`: Two uniformly distributed random numbers in `[0, 1)`.
+    ///
+    /// returns: Option<SurfaceSample>
+    ///
+    /// # Notes
+    /// * The points have to be distributed uniformly over the area returned by [CustomShape::surface_area].
+    /// * The default returns [None], which means the shape can't be sampled.
+    fn sample_surface(&self, _u: (f64, f64)) -> Option<SurfaceSample> {
+        None
+    }
+    /// Picks a point on the surface of the Object/Shape at a point in time, see [CustomShape::distance_at].
+    /// Shapes that change over time need this to be sampled where rays at that time hit them.
+    ///
+    /// # Arguments
+    ///
+    /// * `u`: Two uniformly distributed random numbers in `[0, 1)`.
+    /// * `time`: The time of the ray that the point is sampled for, see [Config::shutter](crate::Config::shutter).
+    ///
+    /// returns: Option<SurfaceSample>
+    ///
+    /// # Notes
+    /// * The default ignores the time and calls [CustomShape::sample_surface].
+    /// * The area has to stay the one returned by [CustomShape::surface_area].
+    ///   Shapes whose area changes should return [None] there, so they aren't sampled as lights.
+    fn sample_surface_at(&self, u: (f64, f64), _time: f64) -> Option<SurfaceSample> {
+        self.sample_surface(u)
+    }
+}
+/// A point on the surface of a shape, see [CustomShape::sample_surface]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SurfaceSample {
+    /// The position of the point in world space
+    pub position: Vector3,
+    /// The normalized normal of the surface at the point
+    pub normal: Vector3,
 }
 /// represents the material of an [Object]
 #[derive(Clone, Debug)]
@@ -82,15 +278,121 @@ pub struct Material {
     /// The color of emissions on the object.
     pub emission_color: Vector3,
     /// How rough the material is.
-    /// 0 means rays are only reflected, 1 means rays bounce randomly.
+    /// 0 means the surface is polished, 1 means it is matte.
     ///
-    /// The lower the number, the more the rays bounce towards a full reflection.
+    /// This is the perceived roughness of the [Microfacet] bsdf (its GGX alpha is the square of it).
     pub roughness: f64,
+    /// How metallic the material is.
+    /// 0 means the material is a non-metal with a diffuse [Material::base_color] under a clear reflection,
+    /// 1 means it is a metal that reflects everything tinted by [Material::base_color].
+    pub metallic: f64,
+    /// A custom scattering function that replaces the [Microfacet] bsdf made of
+    /// [Material::base_color], [Material::roughness] and [Material::metallic].
+    ///
+    /// Only the cpu renderer supports custom bsdfs.
+    pub bsdf: Option<Arc<dyn Bsdf>>,
+    /// Makes the object transparent, see [Dielectric]. [None] for opaque objects.
+    ///
+    /// Light passing through the surface is tinted by [Material::base_color],
+    /// [Material::roughness] blurs both the reflection and the refraction.
+    pub dielectric: Option<Dielectric>,
 }
 impl Material  {
-    /// creates a new material with the given specs
+    /// Creates a new material with the given specs.
+    ///
+    /// Materials made this way keep the look they had before [Material::metallic] existed:
+    /// the smoother they are, the more metallic they get, so a roughness of 0 is a mirror tinted by the base color
+    /// and a roughness of 1 is matte. Use [Material::with_metallic] to choose the metalness on its own.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rtx::math::Vector3;
+    /// use rtx::object::Material;
+    /// let mirror = Material::new(Vector3::ones(), Vector3::zeros(), 0.0);
+    /// assert_eq!(mirror.metallic, 1.0);
+    /// let plastic = Material::new(Vector3::x(), Vector3::zeros(), 0.2).with_metallic(0.0);
+    /// assert_eq!(plastic.metallic, 0.0);
+    /// ```
     pub const fn new(base_color: Vector3, emission_color: Vector3, roughness: f64) -> Self {
-        Self { base_color, emission_color, roughness }
+        Self { base_color, emission_color, roughness, metallic: 1f64 - roughness, bsdf: None, dielectric: None }
+    }
+    /// Creates a new metal.
+    ///
+    /// # Arguments
+    ///
+    /// * `color`: The color of the reflection
+    /// * `roughness`: How blurry the reflection is, see [Material::roughness]
+    ///
+    /// returns: Material
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rtx::math::Vector3;
+    /// use rtx::object::Material;
+    /// let gold = Material::metal(Vector3::new(1.0, 0.78, 0.34), 0.2);
+    /// ```
+    pub const fn metal(color: Vector3, roughness: f64) -> Self {
+        let mut material = Self::new(color, Vector3::zeros(), roughness);
+        material.metallic = 1f64;
+        material
+    }
+    /// returns a copy of the material with the given [Material::metallic]
+    pub fn with_metallic(&self, metallic: f64) -> Self {
+        Self { metallic, ..self.clone() }
+    }
+    /// returns a copy of the material with a custom [Material::bsdf]
+    pub fn with_bsdf(&self, bsdf: impl Bsdf + 'static) -> Self {
+        Self { bsdf: Some(Arc::new(bsdf)), ..self.clone() }
+    }
+    /// returns the [Microfacet] bsdf described by the parameters of the material
+    pub fn microfacet(&self) -> Microfacet {
+        Microfacet::new(self.base_color, self.roughness, self.metallic)
+    }
+    /// Creates a new clear and smooth transparent material.
+    ///
+    /// # Arguments
+    ///
+    /// * `ior`: The index of refraction, see [Dielectric::ior].
+    ///
+    /// returns: Material
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rtx::object::Material;
+    /// let glass = Material::glass(1.5);
+    /// let water = Material::glass(1.33);
+    /// ```
+    pub const fn glass(ior: f64) -> Self {
+        Self::dielectric(ior, Vector3::zeros())
+    }
+    /// Creates a new smooth transparent material that absorbs light inside it.
+    ///
+    /// # Arguments
+    ///
+    /// * `ior`: The index of refraction, see [Dielectric::ior].
+    /// * `absorption`: How much of each color is absorbed, see [Dielectric::absorption].
+    ///
+    /// returns: Material
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rtx::math::Vector3;
+    /// use rtx::object::Material;
+    /// let green_glass = Material::dielectric(1.5, Vector3::new(0.5, 0.05, 0.5));
+    /// ```
+    pub const fn dielectric(ior: f64, absorption: Vector3) -> Self {
+        Self {
+            base_color: Vector3::ones(),
+            emission_color: Vector3::zeros(),
+            roughness: 0f64,
+            metallic: 0f64,
+            bsdf: None,
+            dielectric: Some(Dielectric { ior, absorption }),
+        }
     }
     /// Creates a new material with only a color component.
     ///
@@ -130,7 +432,21 @@ impl Material  {
     pub const fn light(light_color: Vector3) -> Self {
         Self::new(Vector3::zeros(), light_color, 1f64)
     }
+    /// Creates a perfect mirror
     pub const fn mirror() -> Self {
-        Self::new(Vector3::ones(), Vector3::zeros(), 1f64)
+        Self::metal(Vector3::ones(), 0f64)
     }
-}
\ No newline at end of file
+}
+/// A transparent medium (like glass or water) that refracts the light passing through it.
+///
+/// Rays hitting the surface are either reflected or refracted, weighted by the Fresnel equations.
+/// Rays leaving the medium at a shallow angle are reflected totally.
+/// The space around objects is assumed to be air (an index of refraction of 1).
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Dielectric {
+    /// The index of refraction, e.g. 1.33 for water, 1.5 for glass or 2.42 for diamond.
+    pub ior: f64,
+    /// The fraction of each color that is absorbed per unit of distance travelled inside the medium (Beer–Lambert law).
+    /// Zero means the medium is clear.
+    pub absorption: Vector3,
+}
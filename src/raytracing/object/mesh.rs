@@ -0,0 +1,471 @@
+use crate::math::{BoundingBox, Vector3};
+use crate::object::{CustomShape, SurfaceSample};
+use crate::object::triangle::{sample_triangle, triangle_area};
+use crate::raytracing::bvh::Bvh;
+#[cfg(feature = "gpu")]
+use crate::raytracing::gpu::GpuSerialize;
+#[cfg(feature = "gpu")]
+use crate::raytracing::gpu::object::GpuShape;
+
+/// Rays closer to a triangle than this are considered to start on it and don't hit it.
+const EPSILON: f64 = 1e-9;
+
+/// A triangle mesh with shared vertices.
+///
+/// The whole mesh is a single shape, no matter how many triangles it has.
+/// The triangles are put into their own acceleration structure, so only a few of them are tested per ray.
+#[derive(Clone, Debug)]
+pub struct Mesh {
+    vertices: Vec<Vector3>,
+    indices: Vec<[usize; 3]>,
+    normals: Option<Vec<Vector3>>,
+    uvs: Option<Vec<(f64, f64)>>,
+    bvh: Bvh,
+    /// The summed up areas of the triangles, used to pick triangles proportional to their area
+    area_cdf: Vec<f64>,
+}
+/// Information about where a ray hit a [Mesh]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct MeshHit {
+    /// The distance from the ray's position to the hit point
+    pub distance: f64,
+    /// The index of the triangle that was hit (in [Mesh::indices])
+    pub triangle: usize,
+    /// The weights of the three vertices of the triangle at the hit point. They always add up to 1.
+    pub barycentrics: [f64; 3],
+}
+impl Mesh {
+    /// Creates a new mesh.
+    ///
+    /// # Arguments
+    ///
+    /// * `vertices`: The positions of the vertices in world space.
+    /// * `indices`: The triangles of the mesh, each one as the indices of its three vertices.
+    ///
+    /// returns: Mesh
+    ///
+    /// # Panics
+    /// If a triangle references a vertex that doesn't exist.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rtx::math::Vector3;
+    /// use rtx::object::mesh::Mesh;
+    /// // a unit square made of two triangles
+    /// let square = Mesh::new(
+    ///     vec![Vector3::zeros(), Vector3::x(), Vector3::new(1, 1, 0), Vector3::y()],
+    ///     vec![[0, 1, 2], [0, 2, 3]],
+    /// );
+    /// assert_eq!(square.triangle_count(), 2);
+    /// ```
+    pub fn new(vertices: Vec<Vector3>, indices: Vec<[usize; 3]>) -> Self {
+        assert!(indices.iter().flatten().all(|&i| i < vertices.len()), "mesh references a vertex that doesn't exist");
+        let bounds = indices.iter()
+            .map(|triangle| triangle_bounds(&vertices, triangle))
+            .collect::<Vec<_>>();
+        let bvh = Bvh::build(&bounds);
+        let area_cdf = indices.iter()
+            .scan(0.0, |total, triangle| {
+                *total += triangle_area(triangle.map(|i| vertices[i]));
+                Some(*total)
+            })
+            .collect();
+        Self { vertices, indices, normals: None, uvs: None, bvh, area_cdf }
+    }
+    /// Adds per-vertex normals to the mesh. They are interpolated across each triangle.
+    ///
+    /// # Panics
+    /// If there isn't exactly one normal per vertex.
+    pub fn with_normals(mut self, normals: Vec<Vector3>) -> Self {
+        assert_eq!(normals.len(), self.vertices.len(), "a mesh needs exactly one normal per vertex");
+        self.normals = Some(normals.iter().map(Vector3::norm).collect());
+        self
+    }
+    /// Adds per-vertex texture coordinates to the mesh.
+    ///
+    /// # Panics
+    /// If there isn't exactly one coordinate per vertex.
+    pub fn with_uvs(mut self, uvs: Vec<(f64, f64)>) -> Self {
+        assert_eq!(uvs.len(), self.vertices.len(), "a mesh needs exactly one texture coordinate per vertex");
+        self.uvs = Some(uvs);
+        self
+    }
+    /// returns the positions of the vertices
+    pub fn vertices(&self) -> &[Vector3] {
+        &self.vertices
+    }
+    /// returns the triangles as indices into [Mesh::vertices]
+    pub fn indices(&self) -> &[[usize; 3]] {
+        &self.indices
+    }
+    /// returns the per-vertex normals, if the mesh has any
+    pub fn normals(&self) -> Option<&[Vector3]> {
+        self.normals.as_deref()
+    }
+    /// returns the per-vertex texture coordinates, if the mesh has any
+    pub fn uvs(&self) -> Option<&[(f64, f64)]> {
+        self.uvs.as_deref()
+    }
+    /// returns the number of triangles in the mesh
+    pub fn triangle_count(&self) -> usize {
+        self.indices.len()
+    }
+    /// Finds the closest triangle hit by a ray.
+    ///
+    /// # Arguments
+    ///
+    /// * `ray_position`: The position of the ray in world-space.
+    /// * `ray_direction`: The direction of the ray in world-space.
+    ///
+    /// returns: Option<MeshHit>
+    pub fn intersect(&self, ray_position: Vector3, ray_direction: Vector3) -> Option<MeshHit> {
+        let ray_direction = ray_direction.norm();
+        let (distance, triangle) = self.bvh.closest_hit(ray_position, ray_direction, |triangle| {
+            self.intersect_triangle(triangle, ray_position, ray_direction).map(|(distance, _)| distance)
+        })?;
+        let (_, barycentrics) = self.intersect_triangle(triangle, ray_position, ray_direction)?;
+        Some(MeshHit { distance, triangle, barycentrics })
+    }
+    /// returns the (interpolated, if the mesh has normals) normal at the hit point
+    pub fn hit_normal(&self, hit: &MeshHit) -> Vector3 {
+        let triangle = &self.indices[hit.triangle];
+        match &self.normals {
+            Some(normals) => interpolate(triangle.map(|i| normals[i]), hit.barycentrics).norm(),
+            None => self.face_normal(hit.triangle),
+        }
+    }
+    /// returns the interpolated texture coordinates at the hit point, if the mesh has any
+    pub fn hit_uv(&self, hit: &MeshHit) -> Option<(f64, f64)> {
+        let uvs = self.uvs.as_ref()?;
+        let [a, b, c] = self.indices[hit.triangle].map(|i| uvs[i]);
+        let [wa, wb, wc] = hit.barycentrics;
+        Some((a.0 * wa + b.0 * wb + c.0 * wc, a.1 * wa + b.1 * wb + c.1 * wc))
+    }
+    /// returns the geometric normal of a triangle
+    fn face_normal(&self, triangle: usize) -> Vector3 {
+        let [a, b, c] = self.triangle_vertices(triangle);
+        (b - a).cross(c - a).norm()
+    }
+    fn triangle_vertices(&self, triangle: usize) -> [Vector3; 3] {
+        self.indices[triangle].map(|i| self.vertices[i])
+    }
+    /// Möller–Trumbore intersection of a ray with a single triangle.
+    ///
+    /// returns: Option<(distance, barycentrics)>
+    fn intersect_triangle(&self, triangle: usize, ray_position: Vector3, ray_direction: Vector3) -> Option<(f64, [f64; 3])> {
+        let [a, b, c] = self.triangle_vertices(triangle);
+        let r = b - a;
+        let s = c - a;
+        let p_vec = ray_direction.cross(s);
+        let det = r.dot(p_vec);
+        if det == 0.0 {
+            return None;
+        }
+        let inverse_det = 1.0 / det;
+        let offset = ray_position - a;
+        let u = offset.dot(p_vec) * inverse_det;
+        if !(0.0..=1.0).contains(&u) {
+            return None;
+        }
+        let q_vec = offset.cross(r);
+        let v = ray_direction.dot(q_vec) * inverse_det;
+        if v < 0.0 || u + v > 1.0 {
+            return None;
+        }
+        let distance = s.dot(q_vec) * inverse_det;
+        (distance > EPSILON).then_some((distance, [1.0 - u - v, u, v]))
+    }
+    /// Finds the triangle a point on the surface of the mesh lies on.
+    ///
+    /// returns: Option<MeshHit>
+    ///     The triangle closest to the point with the barycentrics of the point projected onto it.
+    ///     The distance is the distance of the point to the triangle's plane.
+    fn locate(&self, point: Vector3) -> Option<MeshHit> {
+        let mut closest: Option<MeshHit> = None;
+        self.bvh.visit_containing(point, self.point_tolerance(), |triangle| {
+            let [a, b, c] = self.triangle_vertices(triangle);
+            let normal = (b - a).cross(c - a).norm();
+            let distance = (point - a).dot(normal).abs();
+            let barycentrics = barycentrics(point, [a, b, c]);
+            // points that were calculated from a hit are slightly off the triangle due to floating point errors
+            let inside = barycentrics.iter().all(|&w| w >= -1e-6);
+            if inside && closest.is_none_or(|hit| distance < hit.distance) {
+                closest = Some(MeshHit { distance, triangle, barycentrics });
+            }
+        });
+        closest
+    }
+    /// returns by how much points may lie outside of the bounds of a triangle and still be considered on it
+    fn point_tolerance(&self) -> f64 {
+        let bounds = self.bvh.bounds();
+        1e-7 * bounds.min.len().max(bounds.max.len()).max(1.0)
+    }
+}
+impl CustomShape for Mesh {
+    fn distance(&self, ray_position: Vector3, ray_direction: Vector3) -> Option<f64> {
+        self.intersect(ray_position, ray_direction).map(|hit| hit.distance)
+    }
+    fn normal(&self, world_position: Vector3) -> Vector3 {
+        match self.locate(world_position) {
+            Some(hit) => self.hit_normal(&hit),
+            None => Vector3::z(),
+        }
+    }
+    fn bounding_box(&self) -> Option<BoundingBox> {
+        (!self.indices.is_empty()).then(|| self.bvh.bounds())
+    }
+    fn surface_area(&self) -> Option<f64> {
+        self.area_cdf.last().copied().filter(|&area| area > 0.0)
+    }
+    fn sample_surface(&self, u: (f64, f64)) -> Option<SurfaceSample> {
+        let total = self.surface_area()?;
+        let target = u.0 * total;
+        let triangle = self.area_cdf.partition_point(|&area| area <= target).min(self.indices.len() - 1);
+        // reuse the position of the target within the triangle's area as a new random number
+        let start = if triangle == 0 { 0.0 } else { self.area_cdf[triangle - 1] };
+        let u0 = ((target - start) / (self.area_cdf[triangle] - start)).clamp(0.0, 1.0);
+        Some(SurfaceSample {
+            position: sample_triangle(self.triangle_vertices(triangle), (u0, u.1)),
+            normal: self.face_normal(triangle),
+        })
+    }
+}
+fn triangle_bounds(vertices: &[Vector3], triangle: &[usize; 3]) -> BoundingBox {
+    triangle.iter().fold(BoundingBox::empty(), |bounds, &i| bounds.grow(vertices[i]))
+}
+fn interpolate(values: [Vector3; 3], weights: [f64; 3]) -> Vector3 {
+    values[0] * weights[0] + values[1] * weights[1] + values[2] * weights[2]
+}
+/// calculates the barycentric coordinates of a point projected onto the triangle
+fn barycentrics(point: Vector3, [a, b, c]: [Vector3; 3]) -> [f64; 3] {
+    let r = b - a;
+    let s = c - a;
+    let p = point - a;
+    let (rr, rs, ss) = (r.dot(r), r.dot(s), s.dot(s));
+    let (pr, ps) = (p.dot(r), p.dot(s));
+    let denominator = rr * ss - rs * rs;
+    if denominator == 0.0 {
+        return [1.0, 0.0, 0.0];
+    }
+    let u = (ss * pr - rs * ps) / denominator;
+    let v = (rr * ps - rs * pr) / denominator;
+    [1.0 - u - v, u, v]
+}
+#[cfg(feature = "gpu")]
+impl GpuSerialize for Mesh {
+    fn serialize(&self) -> Vec<u8> {
+        let bounds = self.bvh.bounds();
+        bounds.min.serialize().into_iter()
+            .chain((self.indices.len() as u32).to_le_bytes())
+            .chain(bounds.max.serialize())
+            .chain((self.normals.is_some() as u32).to_le_bytes())
+            .collect()
+    }
+}
+#[cfg(feature = "gpu")]
+impl GpuShape for Mesh {
+    fn struct_fields(&self) -> Vec<(String, String)> {
+        vec![
+            ("bounds_min".to_string(), "vec3<f32>".to_string()),
+            ("triangle_count".to_string(), "u32".to_string()),
+            ("bounds_max".to_string(), "vec3<f32>".to_string()),
+            ("has_normals".to_string(), "u32".to_string()),
+        ]
+    }
+    /// The auxiliary data consists of (each entry being one `vec4<f32>`):
+    /// * a header with the number of nodes, vertices and triangles and whether there are normals (as `u32`s)
+    /// * the nodes of the bvh, each as two entries: the bounds in `xyz` and the first index/count in `w` (as `u32`)
+    /// * the vertices in `xyz`
+    /// * the normals in `xyz`, if there are any
+    /// * the triangles in the order the bvh references them, with the vertex indices in `xyz` (as `u32`)
+    fn auxiliary_data(&self) -> Vec<u8> {
+        fn entry(vector: Vector3, w: [u8; 4]) -> impl Iterator<Item = u8> {
+            vector.serialize().into_iter().chain(w)
+        }
+        let header = [self.bvh.nodes.len(), self.vertices.len(), self.indices.len(), self.normals.is_some() as usize]
+            .into_iter()
+            .flat_map(|value| (value as u32).to_le_bytes());
+        let nodes = self.bvh.nodes.iter()
+            .flat_map(|node| entry(node.bounds.min, (node.first as u32).to_le_bytes())
+                .chain(entry(node.bounds.max, (node.count as u32).to_le_bytes())));
+        let vertices = self.vertices.iter()
+            .chain(self.normals.iter().flatten())
+            .flat_map(|&vertex| entry(vertex, [0; 4]));
+        let triangles = self.bvh.indices.iter()
+            .flat_map(|&triangle| self.indices[triangle].into_iter().chain([triangle]))
+            .flat_map(|index| (index as u32).to_le_bytes());
+        header.chain(nodes).chain(vertices).chain(triangles).collect()
+    }
+    fn distance_code(&self) -> String {
+        "let header = bitcast<vec4<u32>>(auxiliary[auxiliary_offset]);
+    let nodes = auxiliary_offset + 1u;
+    let vertices = nodes + 2u * header.x;
+    let triangles = vertices + header.y * (1u + header.w);
+    if (header.z == 0u) {
+        return DistanceInfo(false, 0.0);
+    }
+    let dir = normalize(ray_direction);
+    let inv_dir = 1.0 / dir;
+    var stack: array<u32, 64>;
+    var stack_size: u32 = 1u;
+    stack[0] = 0u;
+    var closest: f32 = -1.0;
+    while (stack_size > 0u) {
+        stack_size -= 1u;
+        let node = stack[stack_size];
+        let node_min = auxiliary[nodes + 2u * node];
+        let node_max = auxiliary[nodes + 2u * node + 1u];
+        let t0 = (node_min.xyz - ray_position) * inv_dir;
+        let t1 = (node_max.xyz - ray_position) * inv_dir;
+        let t_near = min(t0, t1);
+        let t_far = max(t0, t1);
+        let t_min = max(max(t_near.x, t_near.y), max(t_near.z, 0.0));
+        var t_max = min(min(t_far.x, t_far.y), t_far.z);
+        if (closest >= 0.0) {
+            t_max = min(t_max, closest);
+        }
+        if (t_min > t_max) {
+            continue;
+        }
+        let first = bitcast<u32>(node_min.w);
+        let count = bitcast<u32>(node_max.w);
+        if (count == 0u) {
+            if (stack_size + 2u <= 64u) {
+                stack[stack_size] = first;
+                stack[stack_size + 1u] = first + 1u;
+                stack_size += 2u;
+            }
+            continue;
+        }
+        for (var i: u32 = first; i < first + count; i++) {
+            let triangle = bitcast<vec4<u32>>(auxiliary[triangles + i]);
+            let a = auxiliary[vertices + triangle.x].xyz;
+            let r = auxiliary[vertices + triangle.y].xyz - a;
+            let s = auxiliary[vertices + triangle.z].xyz - a;
+            let p_vec = cross(dir, s);
+            let det = dot(r, p_vec);
+            if (det == 0.0) {
+                continue;
+            }
+            let inv_det = 1.0 / det;
+            let offset = ray_position - a;
+            let u = dot(offset, p_vec) * inv_det;
+            if (u < 0.0 || u > 1.0) {
+                continue;
+            }
+            let q_vec = cross(offset, r);
+            let v = dot(dir, q_vec) * inv_det;
+            if (v < 0.0 || u + v > 1.0) {
+                continue;
+            }
+            let t = dot(s, q_vec) * inv_det;
+            if (t > 1e-6 && (closest < 0.0 || t < closest)) {
+                closest = t;
+            }
+        }
+    }
+    if (closest < 0.0) {
+        return DistanceInfo(false, 0.0);
+    }
+    return DistanceInfo(true, closest);".to_string()
+    }
+    fn normal_calculation_code(&self) -> String {
+        "let header = bitcast<vec4<u32>>(auxiliary[auxiliary_offset]);
+    let nodes = auxiliary_offset + 1u;
+    let vertices = nodes + 2u * header.x;
+    let normals = vertices + header.y;
+    let triangles = vertices + header.y * (1u + header.w);
+    let tolerance = 1e-4 * max(1.0, max(length(current.bounds_min), length(current.bounds_max)));
+    if (header.z == 0u) {
+        return vec3<f32>(0.0, 0.0, 1.0);
+    }
+    var stack: array<u32, 64>;
+    var stack_size: u32 = 1u;
+    stack[0] = 0u;
+    var closest: f32 = -1.0;
+    var normal = vec3<f32>(0.0, 0.0, 1.0);
+    while (stack_size > 0u) {
+        stack_size -= 1u;
+        let node = stack[stack_size];
+        let node_min = auxiliary[nodes + 2u * node];
+        let node_max = auxiliary[nodes + 2u * node + 1u];
+        if (any(world_position < node_min.xyz - tolerance) || any(world_position > node_max.xyz + tolerance)) {
+            continue;
+        }
+        let first = bitcast<u32>(node_min.w);
+        let count = bitcast<u32>(node_max.w);
+        if (count == 0u) {
+            if (stack_size + 2u <= 64u) {
+                stack[stack_size] = first;
+                stack[stack_size + 1u] = first + 1u;
+                stack_size += 2u;
+            }
+            continue;
+        }
+        for (var i: u32 = first; i < first + count; i++) {
+            let triangle = bitcast<vec4<u32>>(auxiliary[triangles + i]);
+            let a = auxiliary[vertices + triangle.x].xyz;
+            let r = auxiliary[vertices + triangle.y].xyz - a;
+            let s = auxiliary[vertices + triangle.z].xyz - a;
+            let p = world_position - a;
+            let face_normal = normalize(cross(r, s));
+            let distance = abs(dot(p, face_normal));
+            let rr = dot(r, r);
+            let rs = dot(r, s);
+            let ss = dot(s, s);
+            let denominator = rr * ss - rs * rs;
+            if (denominator == 0.0) {
+                continue;
+            }
+            let u = (ss * dot(p, r) - rs * dot(p, s)) / denominator;
+            let v = (rr * dot(p, s) - rs * dot(p, r)) / denominator;
+            if (u < -1e-3 || v < -1e-3 || u + v > 1.001 || (closest >= 0.0 && distance >= closest)) {
+                continue;
+            }
+            closest = distance;
+            if (header.w == 0u) {
+                normal = face_normal;
+            } else {
+                normal = normalize(
+                    auxiliary[normals + triangle.x].xyz * (1.0 - u - v)
+                    + auxiliary[normals + triangle.y].xyz * u
+                    + auxiliary[normals + triangle.z].xyz * v
+                );
+            }
+        }
+    }
+    return normal;".to_string()
+    }
+    fn object_type(&self) -> String {
+        format!("{}::mesh", module_path!())
+    }
+    fn bounding_box_code(&self) -> String {
+        "return BoundingBox(true, current.bounds_min, current.bounds_max);".to_string()
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn square() -> Mesh {
+        Mesh::new(
+            vec![Vector3::zeros(), Vector3::x(), Vector3::new(1, 1, 0), Vector3::y()],
+            vec![[0, 1, 2], [0, 2, 3]],
+        )
+    }
+    #[test]
+    fn hit_reports_triangle_and_barycentrics() {
+        let hit = square().intersect(Vector3::new(0.25, 0.5, 1.0), -Vector3::z()).unwrap();
+        assert_eq!(hit.triangle, 1);
+        assert!((hit.distance - 1.0).abs() < 1e-12);
+        let [a, b, c] = hit.barycentrics;
+        assert!((a - 0.5).abs() < 1e-12 && (b - 0.25).abs() < 1e-12 && (c - 0.25).abs() < 1e-12);
+    }
+    #[test]
+    fn normal_is_interpolated() {
+        let mesh = square().with_normals(vec![Vector3::z(), Vector3::z(), Vector3::x(), Vector3::x()]);
+        let normal = mesh.normal(Vector3::new(0.5, 0.5, 0.0));
+        assert!((normal - Vector3::new(1, 0, 1).norm()).len() < 1e-9);
+    }
+}
@@ -1,5 +1,6 @@
-use crate::math::Vector3;
-use crate::object::CustomShape;
+use crate::math::{BoundingBox, Vector3};
+use crate::object::{CustomShape, SurfaceSample};
+use std::f64::consts::PI;
 #[cfg(feature = "gpu")]
 use crate::raytracing::gpu::GpuSerialize;
 #[cfg(feature = "gpu")]
@@ -26,11 +27,29 @@ impl CustomShape for Sphere {
         if discriminant <= 1e-100 {
             return None;
         }
-        Some((-b - discriminant.sqrt()) / (2f64 * a))
+        let near = (-b - discriminant.sqrt()) / (2f64 * a);
+        if near > 0.0 {
+            return Some(near);
+        }
+        // the ray starts inside the sphere, so it hits the far side
+        Some((-b + discriminant.sqrt()) / (2f64 * a))
     }
     fn normal(&self, world_position: Vector3) -> Vector3 {
         (world_position - self.position).norm()
     }
+    fn bounding_box(&self) -> Option<BoundingBox> {
+        Some(BoundingBox::new(self.position - self.radius, self.position + self.radius))
+    }
+    fn surface_area(&self) -> Option<f64> {
+        Some(4.0 * PI * self.radius * self.radius)
+    }
+    fn sample_surface(&self, u: (f64, f64)) -> Option<SurfaceSample> {
+        let z = 1.0 - 2.0 * u.0;
+        let radius = (1.0 - z * z).max(0.0).sqrt();
+        let phi = 2.0 * PI * u.1;
+        let normal = Vector3::new(radius * phi.cos(), radius * phi.sin(), z);
+        Some(SurfaceSample { position: self.position + normal * self.radius, normal })
+    }
 }
 #[cfg(feature = "gpu")]
 impl GpuSerialize for Sphere {
@@ -68,9 +87,13 @@ let c = dot(offset, offset) - current.radius * current.radius;
 let discriminant: f32 = b * b - 4.0 * a * c;
 if (discriminant < 1e-100) {
     return DistanceInfo(false, 0.0);
-} else {
-    return DistanceInfo(true, (-b - sqrt(discriminant)) / (2.0 * a));
-}".to_string()
+}
+let near: f32 = (-b - sqrt(discriminant)) / (2.0 * a);
+if (near > 0.0) {
+    return DistanceInfo(true, near);
+}
+// the ray starts inside the sphere, so it hits the far side
+return DistanceInfo(true, (-b + sqrt(discriminant)) / (2.0 * a));".to_string()
     }
 
     fn normal_calculation_code(&self) -> String {
@@ -1,5 +1,5 @@
-use crate::math::Vector3;
-use crate::object::CustomShape;
+use crate::math::{BoundingBox, Vector3};
+use crate::object::{CustomShape, SurfaceSample};
 #[cfg(feature = "gpu")]
 use crate::raytracing::gpu::GpuSerialize;
 #[cfg(feature = "gpu")]
@@ -97,7 +97,7 @@ impl Triangle {
 
         // println!("{:?} => {:?}",(lgs1, lgs2, lgs3), (a, b));
         // print!("{a} {b}; ");
-        0. <= a && a <= 1. && 0. <= b && b <= 1. && (a+b) <= 1. //&& (lgs3.z.abs() < 1e10)
+        (0. ..=1.).contains(&a) && (0. ..=1.).contains(&b) && (a+b) <= 1. //&& (lgs3.z.abs() < 1e10)
     }
 }
 impl CustomShape for Triangle {
@@ -125,6 +125,25 @@ impl CustomShape for Triangle {
         }
         Some(distance)
     }
+    fn bounding_box(&self) -> Option<BoundingBox> {
+        Some(self.vertices.iter().fold(BoundingBox::empty(), |bounds, &vertex| bounds.grow(vertex)))
+    }
+    fn surface_area(&self) -> Option<f64> {
+        Some(triangle_area(self.vertices))
+    }
+    fn sample_surface(&self, u: (f64, f64)) -> Option<SurfaceSample> {
+        Some(SurfaceSample { position: sample_triangle(self.vertices, u), normal: self.normal(Vector3::zeros()) })
+    }
+}
+/// returns the area of the triangle spanned by the vertices
+pub(crate) fn triangle_area([a, b, c]: [Vector3; 3]) -> f64 {
+    (b - a).cross(c - a).len() / 2.0
+}
+/// maps two uniformly distributed numbers in `[0, 1)` to a uniformly distributed point on the triangle
+pub(crate) fn sample_triangle([a, b, c]: [Vector3; 3], u: (f64, f64)) -> Vector3 {
+    let root = u.0.sqrt();
+    let (wa, wb) = (1.0 - root, u.1 * root);
+    a * wa + b * wb + c * (1.0 - wa - wb)
 }
 #[cfg(feature = "gpu")]
 impl GpuSerialize for Triangle {
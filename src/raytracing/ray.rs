@@ -4,6 +4,8 @@ use crate::math::Vector3;
 pub(crate) struct Ray {
     pub position: Vector3,
     pub direction: Vector3,
+    /// The time the ray was shot at, see [Config::shutter](crate::Config::shutter)
+    pub time: f64,
     /// The final color of the ray
     pub resulting_color: Vector3,
     /// The color of the light ray, if the ray were to hit something
@@ -11,10 +13,11 @@ pub(crate) struct Ray {
 }
 
 impl Ray {
-    pub(crate) fn new(position: Vector3, direction: Vector3) -> Self {
+    pub(crate) fn new(position: Vector3, direction: Vector3, time: f64) -> Self {
         Self {
             position,
             direction,
+            time,
             resulting_color: Vector3::zeros(),
             light_color: Vector3::ones(),
         }
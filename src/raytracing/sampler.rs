@@ -0,0 +1,349 @@
+//! The numbers that decide where rays go, see [Config::sampler](crate::Config::sampler).
+//!
+//! Every number only depends on the seed, the pixel, the index of the sample in the pixel and how many numbers
+//! the sample used before it (its dimension). So renders are the same, no matter which thread rendered which pixel.
+
+/// Gives the numbers in `[0, 1)` for the samples of the pixels.
+///
+/// A sample asks for its numbers one after another, each one is the next dimension.
+/// Pairs that are used together, like the two coordinates of a direction, should be asked for with [Sampler::next_2d],
+/// so they are spread out well over the unit square.
+pub trait Sampler {
+    /// starts the sample with the given index in a pixel, at the first dimension
+    fn start_sample(&mut self, pixel: (usize, usize), index: usize);
+    /// the number of the next dimension
+    fn next_1d(&mut self) -> f64;
+    /// the numbers of the next two dimensions
+    fn next_2d(&mut self) -> (f64, f64);
+}
+/// The samplers that a [Config](crate::Config) can choose from
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum SamplerKind {
+    /// Unrelated numbers, every sample is on its own
+    Independent,
+    /// Splits every dimension into as many parts as there are [rays per pixel](crate::Config::rays_per_pixel)
+    /// and puts a sample in each part, in a random order
+    Stratified,
+    /// The Halton sequence, with prime bases for the dimensions and the digits shuffled per pixel
+    Halton,
+    /// The Sobol sequence, scrambled per pixel and dimension, so every power of two of samples is well spread out
+    #[default]
+    Sobol,
+}
+impl SamplerKind {
+    pub const ALL: [SamplerKind; 4] = [SamplerKind::Independent, SamplerKind::Stratified, SamplerKind::Halton, SamplerKind::Sobol];
+
+    /// the name in scene files
+    pub fn name(&self) -> &'static str {
+        match self {
+            SamplerKind::Independent => "independent",
+            SamplerKind::Stratified => "stratified",
+            SamplerKind::Halton => "halton",
+            SamplerKind::Sobol => "sobol",
+        }
+    }
+    /// finds the sampler with the given [SamplerKind::name]
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|kind| kind.name() == name)
+    }
+    /// Creates a sampler of this kind.
+    ///
+    /// # Arguments
+    ///
+    /// * `seed`: Renders with different seeds get different numbers
+    /// * `samples_per_pixel`: How many samples the pixels are going to get, [SamplerKind::Stratified] makes that many parts
+    ///
+    /// returns: Box<dyn Sampler>
+    pub fn create(&self, seed: u64, samples_per_pixel: usize) -> Box<dyn Sampler> {
+        let state = SampleState { seed, ..SampleState::default() };
+        match self {
+            SamplerKind::Independent => Box::new(IndependentSampler(state)),
+            SamplerKind::Stratified => Box::new(StratifiedSampler { state, strata: samples_per_pixel.max(1) }),
+            SamplerKind::Halton => Box::new(HaltonSampler(state)),
+            SamplerKind::Sobol => Box::new(SobolSampler(state)),
+        }
+    }
+}
+/// where the samplers are
+#[derive(Clone, Copy, Debug, Default)]
+struct SampleState {
+    seed: u64,
+    pixel: (usize, usize),
+    index: usize,
+    dimension: usize,
+}
+impl SampleState {
+    fn start(&mut self, pixel: (usize, usize), index: usize) {
+        self.pixel = pixel;
+        self.index = index;
+        self.dimension = 0;
+    }
+    /// moves on by `count` dimensions and returns the first one
+    fn take(&mut self, count: usize) -> usize {
+        self.dimension += count;
+        self.dimension - count
+    }
+    /// a hash of the pixel and dimension, the same for all samples of the pixel
+    fn pixel_hash(&self, dimension: usize) -> u64 {
+        hash(&[self.seed, self.pixel.0 as u64, self.pixel.1 as u64, dimension as u64])
+    }
+    /// a hash of the sample and dimension
+    fn sample_hash(&self, dimension: usize) -> u64 {
+        hash(&[self.seed, self.pixel.0 as u64, self.pixel.1 as u64, dimension as u64, self.index as u64])
+    }
+}
+
+/// See [SamplerKind::Independent]
+struct IndependentSampler(SampleState);
+impl Sampler for IndependentSampler {
+    fn start_sample(&mut self, pixel: (usize, usize), index: usize) {
+        self.0.start(pixel, index);
+    }
+    fn next_1d(&mut self) -> f64 {
+        let dimension = self.0.take(1);
+        to_unit(self.0.sample_hash(dimension))
+    }
+    fn next_2d(&mut self) -> (f64, f64) {
+        (self.next_1d(), self.next_1d())
+    }
+}
+
+/// See [SamplerKind::Stratified]
+struct StratifiedSampler {
+    state: SampleState,
+    /// the number of parts of every dimension
+    strata: usize,
+}
+impl StratifiedSampler {
+    /// The part of a dimension that the current sample gets.
+    /// Once all parts were used, the next round gets them in another order.
+    fn stratum(&self, dimension: usize, strata: usize) -> usize {
+        let round = (self.state.index / strata) as u64;
+        let order = hash(&[self.state.pixel_hash(dimension), round]) as u32;
+        permute((self.state.index % strata) as u32, strata as u32, order) as usize
+    }
+}
+impl Sampler for StratifiedSampler {
+    fn start_sample(&mut self, pixel: (usize, usize), index: usize) {
+        self.state.start(pixel, index);
+    }
+    fn next_1d(&mut self) -> f64 {
+        let dimension = self.state.take(1);
+        let stratum = self.stratum(dimension, self.strata);
+        (stratum as f64 + to_unit(self.state.sample_hash(dimension))) / self.strata as f64
+    }
+    /// splits the unit square into a grid with at least as many cells as there are samples
+    fn next_2d(&mut self) -> (f64, f64) {
+        let dimension = self.state.take(2);
+        let columns = (self.strata as f64).sqrt().ceil() as usize;
+        let rows = self.strata.div_ceil(columns);
+        let stratum = self.stratum(dimension, columns * rows);
+        let jitter = (to_unit(self.state.sample_hash(dimension)), to_unit(self.state.sample_hash(dimension + 1)));
+        (
+            ((stratum % columns) as f64 + jitter.0) / columns as f64,
+            ((stratum / columns) as f64 + jitter.1) / rows as f64,
+        )
+    }
+}
+
+/// The bases of the dimensions of the Halton sequence, later dimensions use [IndependentSampler]'s numbers
+const PRIMES: [u64; 32] = [
+    2, 3, 5, 7, 11, 13, 17, 19, 23, 29, 31, 37, 41, 43, 47, 53,
+    59, 61, 67, 71, 73, 79, 83, 89, 97, 101, 103, 107, 109, 113, 127, 131,
+];
+/// See [SamplerKind::Halton]
+struct HaltonSampler(SampleState);
+impl Sampler for HaltonSampler {
+    fn start_sample(&mut self, pixel: (usize, usize), index: usize) {
+        self.0.start(pixel, index);
+    }
+    fn next_1d(&mut self) -> f64 {
+        let dimension = self.0.take(1);
+        let Some(&base) = PRIMES.get(dimension) else {
+            // higher bases are barely better than random numbers
+            return to_unit(self.0.sample_hash(dimension));
+        };
+        scrambled_radical_inverse(self.0.index as u64, base, self.0.pixel_hash(dimension))
+    }
+    fn next_2d(&mut self) -> (f64, f64) {
+        (self.next_1d(), self.next_1d())
+    }
+}
+/// Mirrors the digits of the index in the base at the decimal point, with every digit position shuffled by the seed.
+/// Without the shuffle, the first samples of the larger bases would all be close to 0, and all pixels would get the same numbers.
+fn scrambled_radical_inverse(mut index: u64, base: u64, seed: u64) -> f64 {
+    let (mut reversed, mut denominator) = (0u64, 1u64);
+    let mut position = 0;
+    // the zeros after the last digit are shuffled too, until they no longer change the number
+    while denominator < 1 << 53 {
+        let digit = permute((index % base) as u32, base as u32, hash(&[seed, position]) as u32) as u64;
+        reversed = reversed * base + digit;
+        denominator *= base;
+        index /= base;
+        position += 1;
+    }
+    (reversed as f64 / denominator as f64).min(ONE_MINUS_EPSILON)
+}
+
+/// See [SamplerKind::Sobol].
+///
+/// Every pair of dimensions uses the first two dimensions of the Sobol sequence, with the order of the samples shuffled
+/// and the points scrambled with their own seed (Burley 2020, "Practical Hash-based Owen Scrambling").
+/// That needs no tables of direction numbers and works for any number of dimensions.
+struct SobolSampler(SampleState);
+impl SobolSampler {
+    /// the first two Sobol dimensions of the shuffled sample
+    fn point(&self, dimension: usize) -> (u32, u32) {
+        let seed = self.0.pixel_hash(dimension);
+        let index = nested_uniform_scramble(self.0.index as u32, seed as u32);
+        (
+            nested_uniform_scramble(index.reverse_bits(), (seed >> 32) as u32),
+            nested_uniform_scramble(sobol_second_dimension(index), hash(&[seed]) as u32),
+        )
+    }
+}
+impl Sampler for SobolSampler {
+    fn start_sample(&mut self, pixel: (usize, usize), index: usize) {
+        self.0.start(pixel, index);
+    }
+    fn next_1d(&mut self) -> f64 {
+        let dimension = self.0.take(1);
+        u32_to_unit(self.point(dimension).0)
+    }
+    fn next_2d(&mut self) -> (f64, f64) {
+        let dimension = self.0.take(2);
+        let (x, y) = self.point(dimension);
+        (u32_to_unit(x), u32_to_unit(y))
+    }
+}
+/// the second dimension of the Sobol sequence, whose direction numbers come from the polynomial `x + 1`
+fn sobol_second_dimension(index: u32) -> u32 {
+    let mut direction = 1u32 << 31;
+    let mut value = 0;
+    for bit in 0..32 {
+        if index >> bit & 1 == 1 {
+            value ^= direction;
+        }
+        direction ^= direction >> 1;
+    }
+    value
+}
+/// An Owen scramble: flips every bit depending on the bits above it, so stratified points stay stratified
+fn nested_uniform_scramble(value: u32, seed: u32) -> u32 {
+    laine_karras_permutation(value.reverse_bits(), seed).reverse_bits()
+}
+/// a hash in which every bit only depends on the bits below it
+fn laine_karras_permutation(mut x: u32, seed: u32) -> u32 {
+    x = x.wrapping_add(seed);
+    x ^= x.wrapping_mul(0x6c50b47c);
+    x ^= x.wrapping_mul(0xb82f1e52);
+    x ^= x.wrapping_mul(0xc7afe638);
+    x ^= x.wrapping_mul(0x8d22f6e6);
+    x
+}
+
+/// The largest f64 below 1
+const ONE_MINUS_EPSILON: f64 = 1.0 - f64::EPSILON / 2.0;
+/// Mixes numbers into a hash with the finalizer of SplitMix64
+fn hash(values: &[u64]) -> u64 {
+    values.iter().fold(0x9e3779b97f4a7c15, |hash, &value| {
+        let mut x = (hash ^ value).wrapping_add(0x9e3779b97f4a7c15);
+        x = (x ^ (x >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+        x = (x ^ (x >> 27)).wrapping_mul(0x94d049bb133111eb);
+        x ^ (x >> 31)
+    })
+}
+/// a number in `[0, 1)` from the upper 53 bits of a hash
+fn to_unit(hash: u64) -> f64 {
+    (hash >> 11) as f64 / (1u64 << 53) as f64
+}
+fn u32_to_unit(value: u32) -> f64 {
+    value as f64 / (1u64 << 32) as f64
+}
+/// A permutation of `0..length` that is picked by the seed, without storing it (Kensler 2013, "Correlated Multi-Jittered Sampling")
+fn permute(mut i: u32, length: u32, seed: u32) -> u32 {
+    let mut mask = length - 1;
+    mask |= mask >> 1;
+    mask |= mask >> 2;
+    mask |= mask >> 4;
+    mask |= mask >> 8;
+    mask |= mask >> 16;
+    // permutes within the next power of two, until the index lands below the length
+    loop {
+        i ^= seed;
+        i = i.wrapping_mul(0xe170893d);
+        i ^= seed >> 16;
+        i ^= (i & mask) >> 4;
+        i ^= seed >> 8;
+        i = i.wrapping_mul(0x0929eb3f);
+        i ^= seed >> 23;
+        i ^= (i & mask) >> 1;
+        i = i.wrapping_mul(1 | seed >> 27);
+        i = i.wrapping_mul(0x6935fa69);
+        i ^= (i & mask) >> 11;
+        i = i.wrapping_mul(0x74dcb303);
+        i ^= (i & mask) >> 2;
+        i = i.wrapping_mul(0x9e501cc3);
+        i ^= (i & mask) >> 2;
+        i = i.wrapping_mul(0xc860a3df);
+        i &= mask;
+        i ^= i >> 5;
+        if i < length {
+            return (i + seed) % length;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// the first numbers of a dimension for the samples of a pixel
+    fn first_numbers(kind: SamplerKind, samples: usize, dimension: usize) -> Vec<f64> {
+        let mut sampler = kind.create(1, samples);
+        (0..samples)
+            .map(|index| {
+                sampler.start_sample((3, 5), index);
+                (0..dimension).for_each(|_| { sampler.next_1d(); });
+                sampler.next_1d()
+            })
+            .collect()
+    }
+    #[test]
+    fn samples_are_deterministic() {
+        for kind in SamplerKind::ALL {
+            let mut first = kind.create(7, 16);
+            let mut second = kind.create(7, 16);
+            second.start_sample((0, 0), 3);
+            second.next_2d();
+            first.start_sample((1, 2), 5);
+            second.start_sample((1, 2), 5);
+            for _ in 0..40 {
+                let (a, b) = (first.next_1d(), second.next_1d());
+                assert_eq!(a, b, "{kind:?}");
+                assert!((0.0..1.0).contains(&a), "{kind:?}");
+            }
+        }
+    }
+    #[test]
+    fn every_part_of_a_dimension_gets_a_sample() {
+        // powers of two for sobol, powers of the base of the dimension for halton, any count for stratified
+        let cases = [(SamplerKind::Sobol, 64, &[0, 5][..]), (SamplerKind::Stratified, 50, &[0, 5]), (SamplerKind::Halton, 64, &[0]), (SamplerKind::Halton, 125, &[2])];
+        for (kind, samples, dimensions) in cases {
+            for &dimension in dimensions {
+                let mut parts = vec![0; samples];
+                for number in first_numbers(kind, samples, dimension) {
+                    parts[(number * samples as f64) as usize] += 1;
+                }
+                assert!(parts.iter().all(|&count| count == 1), "{kind:?} {dimension} {parts:?}");
+            }
+        }
+    }
+    #[test]
+    fn sequences_match_their_definitions() {
+        assert_eq!((0..4).map(sobol_second_dimension).collect::<Vec<_>>(), [0, 1 << 31, 3 << 30, 1 << 30]);
+        let mut permutation = (0..13).map(|i| permute(i, 13, 12345)).collect::<Vec<_>>();
+        permutation.sort();
+        assert_eq!(permutation, (0..13).collect::<Vec<_>>());
+    }
+}
@@ -1,8 +1,29 @@
+mod acceleration;
+mod light_sampler;
+mod tiles;
+mod progressive;
+
 use crate::math::Vector3;
-use crate::raytracing::camera::Camera;
+use crate::raytracing::bsdf::Bsdf;
+use crate::raytracing::camera::{Camera, CameraRay};
+use crate::raytracing::camera::stereo::{Eye, StereoRig};
+use crate::raytracing::environment::Environment;
+use crate::raytracing::film::Film;
+use crate::raytracing::adaptive::{AdaptiveSampling, PixelStatistics};
+use crate::raytracing::aov::{Aov, AovAccumulator, FirstHit};
+use crate::raytracing::denoise::Denoiser;
+use crate::raytracing::control::{Progress, RenderControl};
+use crate::raytracing::tone_mapping::ToneMapping;
+use crate::raytracing::light::Light;
+use crate::raytracing::motion::Shutter;
 use crate::raytracing::object::Object;
 use crate::raytracing::ray::Ray;
-use std::thread;
+use crate::raytracing::sampler::{Sampler, SamplerKind};
+use acceleration::{AccelerationCache, ObjectBvh};
+use light_sampler::{power_heuristic, to_solid_angle, LightSampler};
+use tiles::Tile;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Instant;
 
 #[cfg(feature = "images")]
 use image::{
@@ -12,6 +33,11 @@ use image::{
 #[cfg(feature = "gpu")]
 use crate::raytracing::gpu::GpuSerialize;
 
+pub use progressive::ProgressiveRenderer;
+
+/// How far rays are moved off a surface after hitting it, so they don't hit the same spot again.
+const RAY_OFFSET: f64 = 1e-7;
+
 #[derive(Clone, Debug)]
 pub struct Config {
     /// determines, how many rays are shot out per pixel. The more, the lower quality, the higher, the more costly the renderer will get.
@@ -19,12 +45,24 @@ pub struct Config {
     /// The maximum number of bounces a ray can make.
     /// The higher, the more indirect lighting will appear, the less, the faster the rendering will be.
     pub max_bounces: usize,
-    /// The distance of the focal point.
-    pub focal_length: f64,
-    /// The maximum offset of each ray at the focal point. Can be used for a bit of anti-Aliasing at the focal point
-    pub focal_offset: f64,
-    /// The maximum offset of each ray's start position. This makes the focus effect stronger/weaker.
-    pub non_focal_offset: f64,
+    /// The extra buffers that [Scene::render] adds to the [Film], see [Film::aov]. Only used by the CPU renderer.
+    pub aovs: Vec<Aov>,
+    /// Denoises the result of [Scene::render], guided by the normals and albedos of the first hits. Only used by the CPU renderer.
+    pub denoiser: Option<Denoiser>,
+    /// Stops giving rays to pixels once they are known well enough, with [Config::rays_per_pixel] as the most rays a pixel gets.
+    /// Only used by the CPU renderer.
+    pub adaptive_sampling: Option<AdaptiveSampling>,
+    /// Where the rays of the pixels go, see [SamplerKind]. Only used by the CPU renderer.
+    pub sampler: SamplerKind,
+    /// Renders with different seeds get different noise, renders with the same one are the same. Only used by the CPU renderer.
+    pub seed: u64,
+    /// The number of threads that render on the CPU, 0 uses one per core
+    pub threads: usize,
+    /// The width and height of the square tiles that the threads take turns rendering, in pixels
+    pub tile_size: usize,
+    /// When the camera rays are shot, for motion blur of the [objects](Object::motion) and the [camera](Camera::motion).
+    /// Only used by the CPU renderer.
+    pub shutter: Shutter,
 }
 macro_rules! reassign {
     ($self:ident, $field:ident) => {
@@ -42,14 +80,29 @@ impl Config {
     pub fn with_max_bounces(&self, max_bounces: usize) -> Self {
         reassign!(self, max_bounces)
     }
-    pub fn with_focal_length(&self, focal_length: f64) -> Self {
-        reassign!(self, focal_length)
+    pub fn with_aovs(&self, aovs: Vec<Aov>) -> Self {
+        reassign!(self, aovs)
+    }
+    pub fn with_denoiser(&self, denoiser: Option<Denoiser>) -> Self {
+        reassign!(self, denoiser)
+    }
+    pub fn with_adaptive_sampling(&self, adaptive_sampling: Option<AdaptiveSampling>) -> Self {
+        reassign!(self, adaptive_sampling)
+    }
+    pub fn with_sampler(&self, sampler: SamplerKind) -> Self {
+        reassign!(self, sampler)
+    }
+    pub fn with_seed(&self, seed: u64) -> Self {
+        reassign!(self, seed)
+    }
+    pub fn with_threads(&self, threads: usize) -> Self {
+        reassign!(self, threads)
     }
-    pub fn with_focal_offset(&self, focal_offset: f64) -> Self {
-        reassign!(self, focal_offset)
+    pub fn with_tile_size(&self, tile_size: usize) -> Self {
+        reassign!(self, tile_size)
     }
-    pub fn with_non_focal_offset(&self, non_focal_offset: f64) -> Self {
-        reassign!(self, non_focal_offset)
+    pub fn with_shutter(&self, shutter: Shutter) -> Self {
+        reassign!(self, shutter)
     }
 }
 impl Default for Config {
@@ -57,9 +110,14 @@ impl Default for Config {
         Self {
             rays_per_pixel: 16,
             max_bounces: 10,
-            focal_length: 10f64,
-            focal_offset: 1e-4,
-            non_focal_offset: 1e-1,
+            aovs: Vec::new(),
+            denoiser: None,
+            adaptive_sampling: None,
+            sampler: SamplerKind::default(),
+            seed: 0,
+            threads: 0,
+            tile_size: 32,
+            shutter: Shutter::default(),
         }
     }
 }
@@ -68,9 +126,6 @@ impl GpuSerialize for Config {
     fn serialize(&self) -> Vec<u8> {
         (self.rays_per_pixel as u32).to_le_bytes().into_iter()
             .chain((self.max_bounces as u32).to_le_bytes())
-            .chain((self.focal_length as f32).to_le_bytes())
-            .chain((self.focal_offset as f32).to_le_bytes())
-            .chain((self.non_focal_offset as f32).to_le_bytes())
             .collect()
     }
 }
@@ -78,17 +133,29 @@ impl GpuSerialize for Config {
 #[derive(Clone)]
 pub struct Scene {
     pub objects: Vec<Object>,
+    /// The punctual lights, in addition to the emissive objects
+    pub lights: Vec<Light>,
+    /// What rays that don't hit any object see
+    pub environment: Environment,
     /// The camera that this scene is rendered from
     pub camera: Camera,
     /// The configuration of this scene.
     pub config: Config,
+    /// How [Scene::render_to_image] turns the rendered colors into 8 bit colors
+    pub tone_mapping: ToneMapping,
+    /// The acceleration structure over [Scene::objects] from the last render
+    acceleration: AccelerationCache,
 }
 impl Default for Scene {
     fn default() -> Self {
         Self {
             config: Config::default(),
-            camera: Camera::new((0, 0, 0).into(), (1, 0, 0).into(), 90f64),
+            camera: Camera::new((0, 0, 0).into(), (1, 0, 0).into(), 90f64.to_radians()),
             objects: Vec::new(),
+            lights: Vec::new(),
+            environment: Environment::default(),
+            tone_mapping: ToneMapping::default(),
+            acceleration: AccelerationCache::default(),
         }
     }
 }
@@ -107,13 +174,17 @@ impl Scene {
     /// ```
     /// use rtx::{Camera, Scene, Config};
     ///
-    /// let scene = Scene::new(Config::default().with_rays_per_pixel(32), Camera::new((-1,0,0).into(), (1,0,0).into(), 90f64));
+    /// let scene = Scene::new(Config::default().with_rays_per_pixel(32), Camera::new((-1,0,0).into(), (1,0,0).into(), 90f64.to_radians()));
     /// ```
     pub fn new(config: Config, camera: Camera) -> Self {
         Self {
             config,
             camera,
             objects: Vec::new(),
+            lights: Vec::new(),
+            environment: Environment::default(),
+            tone_mapping: ToneMapping::default(),
+            acceleration: AccelerationCache::default(),
         }
     }
     /// Adds a new Object to the scene.
@@ -123,9 +194,19 @@ impl Scene {
     /// * `object`: The object to add
     ///
     /// returns: ()
-    pub fn add_object(&mut self, object: Object) -> () {
+    pub fn add_object(&mut self, object: Object) {
         self.objects.push(object);
     }
+    /// Adds a new punctual light to the scene.
+    ///
+    /// # Arguments
+    ///
+    /// * `light`: The light to add
+    ///
+    /// returns: ()
+    pub fn add_light(&mut self, light: Light) {
+        self.lights.push(light);
+    }
     /// Renders the scene as an image.
     ///
     /// # Arguments
@@ -133,160 +214,664 @@ impl Scene {
     /// * `width`: The width of the resulting image in pixels
     /// * `height`: The height of the resulting image in pixels
     ///
-    /// returns: Vec<Vec<Vector3, Global>, Global>
-    ///     The image, indexed with `img[y][x]`
+    /// returns: Film
+    ///     The linear colors of the image, see [Film::to_rgb8] for 8 bit colors
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rtx::Scene;
+    ///
+    /// let film = Scene::default().render(16, 8);
+    /// assert_eq!((film.width(), film.height()), (16, 8));
+    /// ```
+    pub fn render(&self, width: usize, height: usize) -> Film {
+        self.render_with_progress(width, height, |_| {})
+    }
+    /// Renders the scene as an image and reports how far the render is.
+    ///
+    /// # Arguments
+    ///
+    /// * `width`: The width of the resulting image in pixels
+    /// * `height`: The height of the resulting image in pixels
+    /// * `progress`: Gets called from the render threads every time a tile is done.
+    ///   It used to get the number of finished rows. Since the image is rendered in tiles,
+    ///   it gets a [Progress] that counts finished tiles and camera rays instead.
+    ///
+    /// returns: Film
+    ///     The linear colors of the image
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rtx::Scene;
+    ///
+    /// let film = Scene::default().render_with_progress(16, 8, |progress| println!("{:.0}% done", progress.fraction() * 100.0));
+    /// assert_eq!(film.height(), 8);
+    /// ```
+    pub fn render_with_progress(&self, width: usize, height: usize, progress: impl Fn(Progress) + Sync) -> Film {
+        self.render_with_control(width, height, &RenderControl::default(), progress).0
+    }
+    /// Renders the scene as an image, reports how far the render is and stops early if asked to.
+    ///
+    /// The image is rendered in passes over all tiles, with more camera rays per pixel in every pass.
+    /// So a render that is stopped early still covers the whole image, just with more noise.
+    ///
+    /// # Arguments
+    ///
+    /// * `width`: The width of the resulting image in pixels
+    /// * `height`: The height of the resulting image in pixels
+    /// * `control`: Cancels the render or limits its time, see [RenderControl]
+    /// * `progress`: Gets called from the render threads every time a tile is done.
+    ///
+    /// returns: (Film, Progress)
+    ///     The image and how much of it was rendered.
+    ///     Each pixel is the average of the rays it got, pixels without any are transparent black.
+    pub fn render_with_control(&self, width: usize, height: usize, control: &RenderControl, progress: impl Fn(Progress) + Sync) -> (Film, Progress) {
+        let aspect_ratio = width as f64 / height as f64;
+        self.render_film(width, height, control, progress, |uv, lens_sample, time| self.camera.traced_ray(uv, aspect_ratio, lens_sample, time))
+    }
+    /// Renders the images of both eyes of a stereo rig at once, packed into one image by the [layout](StereoRig::layout) of the rig.
+    /// The camera of the rig is used instead of [Scene::camera], also for the depth of the [AOVs](Config::aovs).
+    ///
+    /// # Arguments
+    ///
+    /// * `rig`: The eyes to render
+    /// * `width`: The width of the image of one eye in pixels
+    /// * `height`: The height of the image of one eye in pixels
+    ///
+    /// returns: Film
+    ///     The linear colors of the packed image, see [StereoLayout::packed_size](crate::camera::stereo::StereoLayout::packed_size)
     ///
     /// # Examples
     ///
     /// ```
+    /// use rtx::camera::model::Equirectangular;
+    /// use rtx::camera::stereo::StereoRig;
+    /// use rtx::{Camera, Scene};
     ///
+    /// // an omni-directional stereo panorama for a headset
+    /// let camera = Camera::new((0, 0, 1.7).into(), (1, 0, 0).into(), 1.0).with_model(Equirectangular);
+    /// let film = Scene::default().render_stereo(&StereoRig::new(camera), 16, 8);
+    /// assert_eq!((film.width(), film.height()), (32, 8));
     /// ```
-    pub fn render(&self, width: usize, height: usize) -> Vec<Vec<Vector3>> {
-        let vertical_fov = (height as f64) / (width as f64) * self.camera.fov;
-        vec![vec![Vector3::default(); width]; height]
-            .into_iter()
-            .enumerate()
-            .map(|(y, row)| {
-                let this = self.clone();
-                thread::spawn(move || {
-                    // let row_index = y;
-                    let y = (y as f64) / (height as f64);
-                    let res = row.into_iter()
-                        .enumerate()
-                        .map(|(x, _pixel)| {
-                            let x = (x as f64) / (width as f64);
-                            let uv = (x, y);
-                            this.render_pixel(uv, vertical_fov)
-                        })
-                        .collect::<Vec<_>>();
-                    // let done = (y) / (height as f64);
-                    // println!("rendered row {} out of {} ({:.2}%)", row_index, height, done * 100.0);
-                    res
-                })
-            })
-            .collect::<Vec<_>>().into_iter()
-            .map(|t| t.join().unwrap())
-            .collect::<Vec<_>>()
-    }
-    /// Renders the scene to an ImageBuffer. Requires the `images` feature
+    pub fn render_stereo(&self, rig: &StereoRig, width: usize, height: usize) -> Film {
+        self.render_stereo_with_control(rig, width, height, &RenderControl::default(), |_| {}).0
+    }
+    /// Renders the images of both eyes of a stereo rig, reports how far the render is and stops early if asked to,
+    /// see [Scene::render_stereo] and [Scene::render_with_control].
+    pub fn render_stereo_with_control(&self, rig: &StereoRig, width: usize, height: usize, control: &RenderControl, progress: impl Fn(Progress) + Sync) -> (Film, Progress) {
+        let (left, right) = (rig.eye(Eye::Left), rig.eye(Eye::Right));
+        let aspect_ratio = width as f64 / height as f64;
+        let (packed_width, packed_height) = rig.layout.packed_size(width, height);
+        // both eyes are tiles of the same image, so they share the threads, the passes and the acceleration structure
+        self.render_film(packed_width, packed_height, control, progress, |uv, lens_sample, time| match rig.layout.split(uv) {
+            (Eye::Left, uv) => left.traced_ray(uv, aspect_ratio, lens_sample, time),
+            (Eye::Right, uv) => right.traced_ray(uv, aspect_ratio, lens_sample, time),
+        })
+    }
+    /// renders an image in passes with the rays that `camera_ray` makes for points of the image, see [Scene::render_pass]
+    fn render_film(&self, width: usize, height: usize, control: &RenderControl, progress: impl Fn(Progress) + Sync, camera_ray: impl Fn((f64, f64), (f64, f64), f64) -> Option<CameraRay> + Sync) -> (Film, Progress) {
+        let start = Instant::now();
+        let mut accumulation = Accumulation::new(width, height, self.config.tile_size);
+        let passes = sample_passes(self.config.rays_per_pixel);
+        let total_tiles = accumulation.tiles.len() * passes.len();
+        let finished_tiles = AtomicUsize::new(0);
+        let finished_samples = AtomicUsize::new(0);
+        let skipped_samples = AtomicUsize::new(0);
+        let current_progress = || Progress {
+            finished_tiles: finished_tiles.load(Ordering::Relaxed),
+            total_tiles,
+            finished_samples: finished_samples.load(Ordering::Relaxed),
+            skipped_samples: skipped_samples.load(Ordering::Relaxed),
+            total_samples: width * height * self.config.rays_per_pixel,
+            elapsed: start.elapsed(),
+        };
+        for &samples in &passes {
+            if control.should_stop(start) {
+                break;
+            }
+            self.render_pass(&mut accumulation, &camera_ray, samples, self.config.rays_per_pixel, || control.should_stop(start), |traced, skipped, complete| {
+                finished_samples.fetch_add(traced, Ordering::Relaxed);
+                skipped_samples.fetch_add(skipped, Ordering::Relaxed);
+                if complete {
+                    finished_tiles.fetch_add(1, Ordering::Relaxed);
+                }
+                progress(current_progress());
+            });
+        }
+        (accumulation.film(&self.config), current_progress())
+    }
+    /// Renders the scene to an 8 bit sRGB ImageBuffer, after applying [Scene::tone_mapping]. Requires the `images` feature
     #[cfg(feature = "images")]
     pub fn render_to_image(&self, width: usize, height: usize) -> ImageBuffer<Rgb<u8>, Vec<u8>> {
-        let img = self.render(width, height);
-        ImageBuffer::from_fn(width as u32, height as u32, |x, y| {
-            let col = img[height - y as usize - 1][x as usize].clone() * 256;
-            Rgb([col.x as u8, col.y as u8, col.z as u8])
-        })
-        // let vertical_fov = (height as f64) / (width as f64) * self.camera.fov;
-        // ImageBuffer::from_fn(width as u32, height as u32, |x, y| {
-        //     if x == 0 {
-        //         let done = (y as f64) / (height as f64);
-        //         println!("rendering row {} out of {} ({:.2}%)", y, height, done * 100.0);
-        //     }
-        //     let x = (x as f64) / (width as f64);
-        //     let y = (y as f64) / (height as f64);
-        //     let col = self.render_pixel((x, 1.0 - y), vertical_fov) * 256;
-        //     Rgb([col.x as u8, col.y as u8, col.z as u8])
-        // })
+        self.render_to_image_with_progress(width, height, |_| {})
+    }
+    /// Renders the scene to an 8 bit sRGB ImageBuffer and reports how far the render is, see [Scene::render_with_progress].
+    /// Requires the `images` feature
+    #[cfg(feature = "images")]
+    pub fn render_to_image_with_progress(&self, width: usize, height: usize, progress: impl Fn(Progress) + Sync) -> ImageBuffer<Rgb<u8>, Vec<u8>> {
+        self.tone_mapping.apply_to_film(&self.render_with_progress(width, height, progress)).to_rgb8()
     }
-
 }
 impl Scene {
-    fn render_pixel(&self, uv: (f64, f64), vertical_fov: f64) -> Vector3 {
-        let ray_dir = self.get_ray_dir(uv.0, uv.1, vertical_fov);
-        let ray = Ray::new(self.camera.position, ray_dir);
-        avg(
-            (0..self.config.rays_per_pixel)
-                .map(|_| {
-                    // focal point calculations
-                    let mut ray = ray.clone();
-                    let ray_position = ray.position + Vector3::random() * self.config.non_focal_offset;
-                    let focal_point = ray.position + ray.direction * self.config.focal_length;
-                    let target_point = focal_point + Vector3::random() * self.config.focal_offset;
-                    let ray_direction = target_point - ray_position;
-                    ray.position = ray_position;
-                    ray.direction = ray_direction.norm();
-                    // actual rendering happening
-                    self.render_ray(ray)
-                })
-        )
-    }
-    fn get_ray_dir(&self, x: f64, y: f64, vertical_fov: f64) -> Vector3 {
-        let angle_x = self.camera.fov * (x - 0.5);
-        let angle_y = vertical_fov * (y - 0.5);
-        let cam_space_dir = Vector3::new(
-            angle_x.sin(),
-            angle_y.sin(),
-            angle_x.cos() * angle_y.cos()
-        );
-        self.camera.rotate_to_world_space(cam_space_dir)
-    }
-    fn render_ray(&self, mut ray: Ray) -> Vector3 {
-        if self.objects.is_empty() {
-            return ray.resulting_color;
+    /// Adds camera rays to every pixel of the accumulation, tile by tile.
+    ///
+    /// # Arguments
+    ///
+    /// * `camera_ray`: Makes the ray through a point of the image, from (0, 0) in the bottom left corner to (1, 1) in the top right one,
+    ///   a point on the lens and a time
+    /// * `samples`: The number of camera rays per pixel
+    /// * `planned`: The rays per pixel that the whole render is going to have, to count the ones that adaptive sampling skips
+    /// * `stop`: Gets checked before every pixel, the pass stops once it returns true
+    /// * `tile_done`: Gets called after every tile with the number of traced rays, the number of skipped rays,
+    ///   and whether every pixel of the tile got its rays
+    fn render_pass(&self, accumulation: &mut Accumulation, camera_ray: impl Fn((f64, f64), (f64, f64), f64) -> Option<CameraRay> + Sync, samples: usize, planned: usize, stop: impl Fn() -> bool + Sync, tile_done: impl Fn(usize, usize, bool) + Sync) {
+        let (width, height) = (accumulation.width, accumulation.height);
+        let acceleration = self.acceleration.get(&self.objects);
+        let lights = LightSampler::new(&self.objects);
+        // the denoiser needs the first hits too
+        let with_aovs = self.config.aovs.iter().any(Aov::is_first_hit) || self.config.denoiser.is_some();
+        let threads = tiles::thread_count(self.config.threads);
+        tiles::run(accumulation.tiles.iter().zip(&mut accumulation.pixels).collect(), threads, |(tile, pixels)| {
+            let mut sampler = self.config.sampler.create(self.config.seed, self.config.rays_per_pixel);
+            let (mut traced, mut skipped, mut rendered) = (0, 0, 0);
+            for ((x, y), pixel) in tile.pixels().zip(pixels.iter_mut()) {
+                if stop() {
+                    break;
+                }
+                rendered += 1;
+                if pixel.converged {
+                    continue;
+                }
+                let pixel_ray = |offset: (f64, f64), lens_sample, time| {
+                    // the film goes from the top of the image to the bottom
+                    let uv = ((x as f64 + offset.0) / width as f64, (height as f64 - y as f64 - offset.1) / height as f64);
+                    camera_ray(uv, lens_sample, time)
+                };
+                traced += self.render_pixel(&acceleration, &lights, pixel_ray, samples, (x, y), pixel, &mut *sampler, with_aovs);
+                if pixel.converged {
+                    skipped += planned.saturating_sub(pixel.samples);
+                }
+            }
+            tile_done(traced, skipped, rendered == pixels.len());
+        });
+    }
+    /// Adds camera rays through random points of a pixel to its state, and their first hits if `with_aovs` is set.
+    /// Stops early once the pixel converged, if adaptive sampling is on.
+    /// The samples continue the numbering of the samples that the pixel already has, for the sampler.
+    ///
+    /// # Arguments
+    ///
+    /// * `camera_ray`: Makes the ray through a point of the pixel, from (0, 0) in its top left corner to (1, 1) in the bottom right one,
+    ///   a point on the lens and a time. Points without a ray count as black samples that don't hit anything.
+    ///
+    /// returns: usize
+    ///     The number of rays that were traced
+    #[allow(clippy::too_many_arguments)]
+    fn render_pixel(&self, acceleration: &ObjectBvh, lights: &LightSampler, camera_ray: impl Fn((f64, f64), (f64, f64), f64) -> Option<CameraRay>, samples: usize, coordinates: (usize, usize), pixel: &mut PixelState, sampler: &mut dyn Sampler, with_aovs: bool) -> usize {
+        for traced in 0..samples {
+            if pixel.converged {
+                return traced;
+            }
+            sampler.start_sample(coordinates, pixel.samples);
+            let offset = sampler.next_2d();
+            let lens_sample = sampler.next_2d();
+            let time = self.config.shutter.time(sampler.next_1d());
+            let camera_ray = camera_ray(offset, lens_sample, time);
+            if with_aovs {
+                pixel.aovs.add(camera_ray.as_ref().and_then(|camera_ray| self.first_hit(acceleration, camera_ray)));
+            }
+            // actual rendering happening
+            let color = camera_ray.map_or(Vector3::zeros(), |camera_ray| self.render_ray(acceleration, lights, camera_ray.ray, sampler));
+            pixel.add(color, self.config.adaptive_sampling.as_ref());
         }
+        samples
+    }
+    /// finds what a camera ray hits first, for the AOVs
+    fn first_hit(&self, acceleration: &ObjectBvh, camera_ray: &CameraRay) -> Option<FirstHit> {
+        let (ray, (depth_origin, depth_axis)) = (camera_ray.ray, camera_ray.depth_axis);
+        let (distance, index) = acceleration.closest_object(&self.objects, ray)?;
+        let object = &self.objects[index];
+        let position = ray.position + ray.direction * distance;
+        let normal = object.normal_at(position, ray.time);
+        Some(FirstHit {
+            depth: (position - depth_origin).dot(depth_axis),
+            normal: if normal.dot(ray.direction) > 0.0 { -normal } else { normal },
+            albedo: object.material.base_color,
+            emission: object.material.emission_color,
+            object: index,
+            position,
+        })
+    }
+    fn render_ray(&self, acceleration: &ObjectBvh, lights: &LightSampler, mut ray: Ray, sampler: &mut dyn Sampler) -> Vector3 {
+        // the pdf of the bsdf sample the ray comes from.
+        // [None] for rays whose direction wasn't sampled from a bsdf (camera rays and refractions)
+        let mut bsdf_pdf: Option<f64> = None;
         for _ in 0..(self.config.max_bounces+1) {
             if ray.light_color == Vector3::zeros() {
                 break;
             }
-            let rtx_hit = self.closest_object(ray);
-            match rtx_hit {
-                Some((dst, obj)) => {
-                    ray.position += ray.direction * dst;
-                    ray_hit(&mut ray, obj);
-                    // return ray.direction;
-                },
-                None => break,
-            }
+            let Some((dst, index)) = acceleration.closest_object(&self.objects, ray) else {
+                // the environment is weighted against sampling it directly, just like lights
+                let weight = bsdf_pdf.map_or(1.0, |pdf| power_heuristic(pdf, self.environment.pdf(ray.direction)));
+                ray.resulting_color += ray.light_color * self.environment.radiance(ray.direction) * weight;
+                break;
+            };
+            let object = &self.objects[index];
+            ray.position += ray.direction * dst;
+            let normal = object.normal_at(ray.position, ray.time);
+            // lights that are hit by chance are weighted against sampling them directly
+            let emission_weight = match bsdf_pdf {
+                Some(pdf) => power_heuristic(pdf, to_solid_angle(lights.pdf_area(index), dst, normal.dot(ray.direction))),
+                None => 1.0,
+            };
+            ray.resulting_color += ray.light_color * object.material.emission_color * emission_weight;
+            bsdf_pdf = self.ray_hit(acceleration, lights, &mut ray, object, normal, dst, sampler);
         }
         ray.resulting_color
     }
-    fn closest_object(&self, ray: Ray) -> Option<(f64, &Object)> {
-        self.objects.iter()
-            .filter_map(|obj| {
-                obj.distance(ray.position, ray.direction)
-                    .map(|dst| (dst, obj))
-            })
-            .filter(|(dst, _)| dst.is_normal() && dst.is_sign_positive())
-            .min_by(move |(dst, _), (dst2, _)| dst.total_cmp(dst2))
+    /// Scatters the ray at the point where it hit the object.
+    ///
+    /// returns: Option<f64>
+    ///     The pdf of the new direction, if it was sampled from a bsdf.
+    #[allow(clippy::too_many_arguments)]
+    fn ray_hit(&self, acceleration: &ObjectBvh, lights: &LightSampler, ray: &mut Ray, object: &Object, normal: Vector3, distance: f64, sampler: &mut dyn Sampler) -> Option<f64> {
+        let material = &object.material;
+        let bsdf_pdf = match material.dielectric {
+            Some(dielectric) => {
+                ray.light_color *= material.base_color;
+                let entering = object.is_entering(ray.position, ray.direction, ray.time);
+                if !entering {
+                    // the ray travelled `distance` through the medium
+                    let transmittance = -dielectric.absorption * distance;
+                    ray.light_color *= Vector3::new(transmittance.x.exp(), transmittance.y.exp(), transmittance.z.exp());
+                }
+                let facing_normal = if entering { normal } else { -normal };
+                let eta = if entering { 1.0 / dielectric.ior } else { dielectric.ior };
+                ray.direction = refract_or_reflect_dir(ray.direction, facing_normal, eta, material.roughness, sampler);
+                None
+            }
+            None => {
+                // scatter back to the side the ray came from
+                let facing_normal = if ray.direction.dot(normal) > 0.0 { -normal } else { normal };
+                let microfacet = material.microfacet();
+                let bsdf: &dyn Bsdf = material.bsdf.as_deref().unwrap_or(&microfacet);
+                let outgoing = -ray.direction;
+                if !lights.is_empty() {
+                    let light = self.sample_light(acceleration, lights, bsdf, ray.position, ray.time, outgoing, facing_normal, sampler);
+                    ray.resulting_color += ray.light_color * light;
+                }
+                if !self.environment.is_black() {
+                    let light = self.sample_environment(acceleration, bsdf, ray.position, ray.time, outgoing, facing_normal, sampler);
+                    ray.resulting_color += ray.light_color * light;
+                }
+                for light in &self.lights {
+                    let light = self.punctual_light(acceleration, light, bsdf, ray.position, ray.time, outgoing, facing_normal, sampler);
+                    ray.resulting_color += ray.light_color * light;
+                }
+                match bsdf.sample(outgoing, facing_normal, sampler.next_2d()) {
+                    Some(sample) => {
+                        ray.direction = sample.direction;
+                        ray.light_color *= sample.weight;
+                        Some(sample.pdf)
+                    }
+                    None => {
+                        ray.light_color = Vector3::zeros();
+                        None
+                    }
+                }
+            }
+        };
+        // move the ray off the surface, so it doesn't hit the same spot again
+        let side = if ray.direction.dot(normal) > 0.0 { normal } else { -normal };
+        ray.position += side * RAY_OFFSET;
+        bsdf_pdf
+    }
+    /// Next-event estimation: picks a point on a light and calculates how much of its light is reflected towards `outgoing`.
+    ///
+    /// The result is weighted against finding the light through bsdf sampling with multiple importance sampling.
+    #[allow(clippy::too_many_arguments)]
+    fn sample_light(&self, acceleration: &ObjectBvh, lights: &LightSampler, bsdf: &dyn Bsdf, position: Vector3, time: f64, outgoing: Vector3, normal: Vector3, sampler: &mut dyn Sampler) -> Vector3 {
+        // the light is picked with one number, the point on it with the other two
+        let choice = sampler.next_1d();
+        let point = sampler.next_2d();
+        let Some(light) = lights.sample(&self.objects, (choice, point.0, point.1), time) else {
+            return Vector3::zeros();
+        };
+        let to_light = light.position - position;
+        let distance = to_light.len();
+        let direction = to_light / distance;
+        let cos_surface = direction.dot(normal);
+        let light_pdf = to_solid_angle(light.pdf_area, distance, light.normal.dot(direction));
+        if cos_surface <= 0.0 || light_pdf <= 0.0 || !light_pdf.is_finite() {
+            return Vector3::zeros();
+        }
+        let reflectance = bsdf.eval(outgoing, direction, normal);
+        if reflectance == Vector3::zeros() {
+            return Vector3::zeros();
+        }
+        if !self.is_visible(acceleration, position, time, normal, direction, distance) {
+            return Vector3::zeros();
+        }
+        let weight = power_heuristic(light_pdf, bsdf.pdf(outgoing, direction, normal));
+        reflectance * self.objects[light.object].material.emission_color * (cos_surface * weight / light_pdf)
+    }
+    /// Next-event estimation for the environment, weighted with multiple importance sampling like [Scene::sample_light].
+    #[allow(clippy::too_many_arguments)]
+    fn sample_environment(&self, acceleration: &ObjectBvh, bsdf: &dyn Bsdf, position: Vector3, time: f64, outgoing: Vector3, normal: Vector3, sampler: &mut dyn Sampler) -> Vector3 {
+        let Some(sample) = self.environment.sample(sampler.next_2d()) else {
+            return Vector3::zeros();
+        };
+        let cos_surface = sample.direction.dot(normal);
+        if cos_surface <= 0.0 {
+            return Vector3::zeros();
+        }
+        let reflectance = bsdf.eval(outgoing, sample.direction, normal);
+        if reflectance == Vector3::zeros() || !self.is_visible(acceleration, position, time, normal, sample.direction, f64::INFINITY) {
+            return Vector3::zeros();
+        }
+        let weight = power_heuristic(sample.pdf, bsdf.pdf(outgoing, sample.direction, normal));
+        reflectance * sample.radiance * (cos_surface * weight / sample.pdf)
+    }
+    /// Calculates how much of the light of a punctual light is reflected towards `outgoing`.
+    ///
+    /// Punctual lights can't be hit by rays, so they are only found this way.
+    #[allow(clippy::too_many_arguments)]
+    fn punctual_light(&self, acceleration: &ObjectBvh, light: &Light, bsdf: &dyn Bsdf, position: Vector3, time: f64, outgoing: Vector3, normal: Vector3, sampler: &mut dyn Sampler) -> Vector3 {
+        let Some(light) = light.sample(position, sampler.next_2d()) else {
+            return Vector3::zeros();
+        };
+        let cos_surface = light.direction.dot(normal);
+        if cos_surface <= 0.0 {
+            return Vector3::zeros();
+        }
+        let reflectance = bsdf.eval(outgoing, light.direction, normal);
+        if reflectance == Vector3::zeros() || !self.is_visible(acceleration, position, time, normal, light.direction, light.distance) {
+            return Vector3::zeros();
+        }
+        reflectance * light.irradiance * cos_surface
+    }
+    /// Shoots a shadow ray at the given time, anything (noticeably) closer than `distance` blocks it.
+    /// An infinite distance means that anything blocks it.
+    fn is_visible(&self, acceleration: &ObjectBvh, position: Vector3, time: f64, normal: Vector3, direction: Vector3, distance: f64) -> bool {
+        let shadow_ray = Ray::new(position + normal * RAY_OFFSET, direction, time);
+        let limit = if distance.is_finite() { distance - distance * 1e-5 - RAY_OFFSET * 2.0 } else { f64::INFINITY };
+        !acceleration.closest_object(&self.objects, shadow_ray).is_some_and(|(dst, _)| dst < limit)
     }
 }
-fn avg<I: ExactSizeIterator, R>(iter: I) -> R
-where <I as Iterator>::Item: std::iter::Sum,
-    <I as Iterator>::Item: std::ops::Div<usize, Output = R>
-{
-    let length = iter.len();
-    iter.sum::<I::Item>() / length
+/// what a render has of a pixel so far
+#[derive(Default)]
+struct PixelState {
+    /// the sum of the colors of all rays
+    color: Vector3,
+    samples: usize,
+    aovs: AovAccumulator,
+    /// only kept with adaptive sampling
+    statistics: PixelStatistics,
+    /// whether adaptive sampling found that the pixel has enough rays
+    converged: bool,
 }
-fn ray_hit(ray: &mut Ray, object: &Object) {
-    // let surface_normal = object.normal_at(ray.position);
-    // let random_dir = Vector3::random_direction();
-    // let reflected_dir = ray.direction - surface_normal * (ray.direction.dot(surface_normal)) * 2;
-    // let offset = random_dir - reflected_dir;
-    //
-    // let reflection_index = (1.0 - object.material.roughness);
-    // let new_dir = random_dir + offset * reflection_index;
-    // let random_dir = Vector3::random_direction();
-    // let random_dir = if random_dir.dot(surface_normal) < 0.0 {
-    //     -random_dir
-    // } else {random_dir};
-    // let reflected_dir = ray.direction - surface_normal * (ray.direction.dot(surface_normal)) * 2;
-    // let new_dir = if fastrand::f64() > object.material.roughness * ( random_dir.dot(reflected_dir) * 0.5 + 0.5) { reflected_dir } else {random_dir};
-
-    ray.direction = random_bounce_dir(ray.direction, object.normal_at(ray.position), object.material.roughness);
-    ray.resulting_color += ray.light_color * object.material.emission_color;
-    ray.light_color *= object.material.base_color;
+impl PixelState {
+    fn add(&mut self, color: Vector3, adaptive_sampling: Option<&AdaptiveSampling>) {
+        self.color += color;
+        self.samples += 1;
+        if let Some(adaptive_sampling) = adaptive_sampling {
+            self.statistics.add(color);
+            self.converged = adaptive_sampling.is_converged(&self.statistics);
+        }
+    }
 }
-fn random_bounce_dir(ray_dir: Vector3, surface_normal: Vector3, surface_roughness: f64) -> Vector3 {
-    let random_dir = Vector3::random_direction();
-    let reflection_dir = ray_dir - surface_normal * 2 * ray_dir.dot(surface_normal);
-    let random_to_reflection_dir = reflection_dir - random_dir;
-    let reflection_mult = 1.0 - surface_roughness;
-    let final_direction = random_dir + random_to_reflection_dir * reflection_mult;
+/// The rays that a render summed up so far, tile by tile
+struct Accumulation {
+    width: usize,
+    height: usize,
+    tiles: Vec<Tile>,
+    /// the pixels of every tile, row by row
+    pixels: Vec<Vec<PixelState>>,
+}
+impl Accumulation {
+    fn new(width: usize, height: usize, tile_size: usize) -> Self {
+        let tiles = tiles::split(width, height, tile_size);
+        let pixels = tiles.iter()
+            .map(|tile| (0..tile.width * tile.height).map(|_| PixelState::default()).collect())
+            .collect();
+        Self { width, height, tiles, pixels }
+    }
+    /// The average colors of the pixels, with the AOVs of the config and denoised if the config says so.
+    /// Pixels without any rays are transparent black.
+    fn film(&self, config: &Config) -> Film {
+        let pixels = || self.tiles.iter().zip(&self.pixels).flat_map(|(tile, pixels)| tile.pixels().zip(pixels));
+        let aov_film = |aov| {
+            let mut film = Film::new(self.width, self.height);
+            for ((x, y), pixel) in pixels() {
+                match aov {
+                    Aov::SampleCount => film.set(x, y, Vector3::ones() * pixel.samples as f64),
+                    aov => film.set(x, y, pixel.aovs.value(aov)),
+                }
+            }
+            film
+        };
+        let mut film = Film::new(self.width, self.height);
+        for ((x, y), pixel) in pixels() {
+            match pixel.samples {
+                0 => film.pixels_mut()[y * self.width + x] = [0.0; 4],
+                samples => film.set(x, y, pixel.color / samples),
+            }
+        }
+        for &aov in &config.aovs {
+            film.set_aov(aov, aov_film(aov));
+        }
+        match &config.denoiser {
+            Some(denoiser) => denoiser.denoise(&film, Some(&aov_film(Aov::Normal)), Some(&aov_film(Aov::Albedo))),
+            None => film,
+        }
+    }
+}
+/// Splits the rays per pixel into passes over the image that double in size (1, 1, 2, 4, ...),
+/// so a render that is stopped early had a similar number of rays in every pixel.
+fn sample_passes(rays_per_pixel: usize) -> Vec<usize> {
+    let mut passes = Vec::new();
+    let mut rendered = 0;
+    while rendered < rays_per_pixel {
+        let samples = rendered.max(1).min(rays_per_pixel - rendered);
+        passes.push(samples);
+        rendered += samples;
+    }
+    passes
+}
+/// Picks randomly between reflecting and refracting the ray, weighted by the Fresnel equations.
+///
+/// # Arguments
+///
+/// * `ray_dir`: The normalized direction of the incoming ray.
+/// * `facing_normal`: The normal of the surface on the side of the incoming ray.
+/// * `eta`: The ratio of the indices of refraction (outside / inside the medium the ray goes into).
+/// * `surface_roughness`: How much the new direction gets randomized.
+/// * `sampler`: Gives the numbers for the choice and the randomized direction.
+///
+/// returns: Vector3
+fn refract_or_reflect_dir(ray_dir: Vector3, facing_normal: Vector3, eta: f64, surface_roughness: f64, sampler: &mut dyn Sampler) -> Vector3 {
+    // the same number of dimensions for both choices, so the following bounces use the same ones
+    let choice = sampler.next_1d();
+    let scatter = sampler.next_2d();
+    let cos_incident = -ray_dir.dot(facing_normal);
+    let sin2_transmitted = eta * eta * (1.0 - cos_incident * cos_incident);
+    let reflection_dir = ray_dir + facing_normal * 2 * cos_incident;
+    // total internal reflection
+    if sin2_transmitted >= 1.0 {
+        return scatter_dir(reflection_dir, facing_normal, surface_roughness, scatter);
+    }
+    let cos_transmitted = (1.0 - sin2_transmitted).sqrt();
+    let reflectance = fresnel_reflectance(cos_incident, cos_transmitted, eta);
+    if choice < reflectance {
+        scatter_dir(reflection_dir, facing_normal, surface_roughness, scatter)
+    } else {
+        let refraction_dir = ray_dir * eta + facing_normal * (eta * cos_incident - cos_transmitted);
+        scatter_dir(refraction_dir, -facing_normal, surface_roughness, scatter)
+    }
+}
+/// The fraction of unpolarized light that is reflected at the boundary between two media.
+fn fresnel_reflectance(cos_incident: f64, cos_transmitted: f64, eta: f64) -> f64 {
+    let perpendicular = (eta * cos_incident - cos_transmitted) / (eta * cos_incident + cos_transmitted);
+    let parallel = (cos_incident - eta * cos_transmitted) / (cos_incident + eta * cos_transmitted);
+    (perpendicular * perpendicular + parallel * parallel) / 2.0
+}
+/// Blends the ideal direction with a random one made from `u`, depending on the roughness, and keeps it on the side of `hemisphere_normal`.
+fn scatter_dir(ideal_dir: Vector3, hemisphere_normal: Vector3, surface_roughness: f64, u: (f64, f64)) -> Vector3 {
+    let random_dir = Vector3::direction_from(u);
+    let random_to_ideal_dir = ideal_dir - random_dir;
+    let ideal_mult = 1.0 - surface_roughness;
+    let final_direction = random_dir + random_to_ideal_dir * ideal_mult;
 
     let final_direction = final_direction.norm();
-    if final_direction.dot(surface_normal) > 0.0 {
+    if final_direction.dot(hemisphere_normal) > 0.0 {
         final_direction
     } else {
         -final_direction
     }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::object::sphere::Sphere;
+    use crate::object::Material;
+    use crate::motion::{Motion, Transform};
+    use crate::camera::model::Equirectangular;
+    use crate::object::plane::Plane;
+
+    #[test]
+    fn glass_reflects_four_percent_head_on() {
+        let reflectance = fresnel_reflectance(1.0, 1.0, 1.0 / 1.5);
+        assert!((reflectance - 0.04).abs() < 1e-12);
+    }
+    #[test]
+    fn refraction_follows_snells_law() {
+        let incoming = Vector3::new(1.0, 0.0, -1.0).norm();
+        let normal = Vector3::z();
+        let eta = 1.0 / 1.5;
+        let mut sampler = SamplerKind::Independent.create(0, 100);
+        // smooth surfaces either reflect or refract exactly
+        for i in 0..100 {
+            sampler.start_sample((0, 0), i);
+            let outgoing = refract_or_reflect_dir(incoming, normal, eta, 0.0, &mut *sampler);
+            if outgoing.z < 0.0 {
+                let sin_outgoing = outgoing.x / outgoing.len();
+                assert!((sin_outgoing - incoming.x * eta).abs() < 1e-12);
+            } else {
+                assert!((outgoing - Vector3::new(incoming.x, 0.0, -incoming.z)).len() < 1e-12);
+            }
+        }
+    }
+    #[test]
+    fn shallow_rays_are_totally_reflected_inside() {
+        let incoming = Vector3::new(1.0, 0.0, 0.5).norm();
+        let mut sampler = SamplerKind::Independent.create(0, 100);
+        for i in 0..100 {
+            sampler.start_sample((0, 0), i);
+            assert!(refract_or_reflect_dir(incoming, -Vector3::z(), 1.5, 0.0, &mut *sampler).z < 0.0);
+        }
+    }
+    #[test]
+    fn sample_passes_add_up_to_the_rays_per_pixel() {
+        assert_eq!(sample_passes(1), vec![1]);
+        assert_eq!(sample_passes(10), vec![1, 1, 2, 4, 2]);
+        assert_eq!(sample_passes(16).iter().sum::<usize>(), 16);
+    }
+    #[test]
+    fn cancelled_renders_keep_the_finished_passes() {
+        let scene = Scene {
+            config: Config::default().with_rays_per_pixel(8).with_threads(1),
+            ..Scene::default()
+        };
+        let control = RenderControl::new();
+        // stops during the second pass
+        let (film, progress) = scene.render_with_control(64, 64, &control, |progress| {
+            if progress.finished_samples > 64 * 64 {
+                control.cancel();
+            }
+        });
+        assert!(!progress.is_complete());
+        assert!(progress.finished_samples < 64 * 64 * 2);
+        // every pixel got the first pass
+        assert!(film.pixels().iter().all(|pixel| pixel[3] == 1.0));
+    }
+    #[test]
+    fn renders_do_not_depend_on_the_threads() {
+        let mut scene = Scene {
+            config: Config::default().with_rays_per_pixel(4).with_tile_size(4),
+            environment: Environment::Gradient { bottom: Vector3::zeros(), top: Vector3::new(0.3, 0.5, 1.0) },
+            ..Scene::default()
+        };
+        scene.add_object(Object::new(Sphere::new(Vector3::new(0, 0, 1), 1.0), Material::new(Vector3::ones(), Vector3::zeros(), 0.5)));
+        scene.add_object(Object::new(Sphere::new(Vector3::new(-1, 2, 1), 1.0), Material::dielectric(1.5, Vector3::zeros())));
+        scene.add_light(Light::point(Vector3::new(-2, 0, 4), Vector3::ones(), 10.0));
+        for sampler in SamplerKind::ALL {
+            scene.config = scene.config.with_sampler(sampler).with_threads(1);
+            let film = scene.render(24, 16);
+            scene.config = scene.config.with_threads(3);
+            assert_eq!(film.pixels(), scene.render(24, 16).pixels(), "{sampler:?}");
+        }
+        // but on the seed
+        let other = scene.config.with_seed(1);
+        assert_ne!(scene.render(24, 16).pixels(), Scene { config: other, ..scene.clone() }.render(24, 16).pixels());
+    }
+    #[test]
+    fn panoramas_measure_depth_along_the_rays() {
+        let mut scene = Scene {
+            config: Config::default().with_rays_per_pixel(1).with_max_bounces(0).with_aovs(vec![Aov::Depth]),
+            camera: Camera::new(Vector3::zeros(), Vector3::x(), 1.0).with_model(Equirectangular),
+            ..Scene::default()
+        };
+        // all around the camera, also behind it
+        scene.add_object(Object::new(Sphere::new(Vector3::zeros(), 5.0), Material::colored(Vector3::ones())));
+        let film = scene.render(16, 8);
+        assert!(film.aov(Aov::Depth).unwrap().pixels().iter().all(|depth| (depth[0] - 5.0).abs() < 1e-3));
+    }
+    #[test]
+    fn stereo_depth_is_measured_from_the_eyes() {
+        let mut scene = Scene {
+            config: Config::default().with_rays_per_pixel(1).with_max_bounces(0).with_aovs(vec![Aov::Depth]),
+            ..Scene::default()
+        };
+        scene.add_object(Object::new(Plane::new(Vector3::zeros(), Vector3::z()), Material::colored(Vector3::ones())));
+        // looking straight down at the ground, unlike the camera of the scene
+        let rig = StereoRig::new(Camera::new(Vector3::new(0, 0, 5), -Vector3::z(), 1.0));
+        let film = scene.render_stereo(&rig, 8, 8);
+        assert!(film.aov(Aov::Depth).unwrap().pixels().iter().all(|depth| (depth[0] - 5.0).abs() < 1e-3));
+    }
+    #[test]
+    fn moving_cameras_measure_depth_where_they_are() {
+        let mut scene = Scene {
+            // the shutter is only open at the end of the motion
+            config: Config::default().with_rays_per_pixel(1).with_max_bounces(0).with_aovs(vec![Aov::Depth]).with_shutter(Shutter::new(1.0, 1.0)),
+            ..Scene::default()
+        };
+        scene.add_object(Object::new(Plane::new(Vector3::new(5, 0, 0), -Vector3::x()), Material::colored(Vector3::ones())));
+        scene.camera = scene.camera.clone().with_motion(Motion::linear(Transform::default(), Transform::translation(Vector3::new(2, 0, 0))));
+        let film = scene.render(8, 8);
+        assert!(film.aov(Aov::Depth).unwrap().pixels().iter().all(|depth| (depth[0] - 3.0).abs() < 1e-3));
+    }
+    #[test]
+    fn moving_objects_are_blurred_over_the_shutter() {
+        let mut scene = Scene {
+            config: Config::default().with_rays_per_pixel(64).with_max_bounces(0),
+            environment: Environment::Color(Vector3::zeros()),
+            ..Scene::default()
+        };
+        // a small light that crosses the image from right to left
+        let motion = Motion::linear(Transform::translation(Vector3::new(4, -2, 0)), Transform::translation(Vector3::new(4, 2, 0)));
+        scene.add_object(Object::new(Sphere::new(Vector3::zeros(), 0.25), Material::light(Vector3::ones())).with_motion(motion));
+        let brightness = |film: &Film, x: usize| film.get(x, 4).x;
+        // with a closed shutter, it stays on the right
+        let film = scene.render(32, 8);
+        assert_eq!(brightness(&film, 16), 0.0);
+        assert!(brightness(&film, 23) > 0.5 && brightness(&film, 24) > 0.5);
+        // with an open one, it lights every pixel on the way for a part of the time
+        scene.config = scene.config.with_shutter(Shutter::new(0.0, 1.0));
+        let film = scene.render(32, 8);
+        for x in [10, 16, 22] {
+            assert!((0.05..0.25).contains(&brightness(&film, x)));
+        }
+    }
+}
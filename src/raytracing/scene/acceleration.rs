@@ -0,0 +1,109 @@
+use crate::math::BoundingBox;
+use crate::raytracing::bvh::Bvh;
+use crate::raytracing::object::Object;
+use crate::raytracing::ray::Ray;
+use std::sync::{Arc, Mutex};
+
+/// The acceleration structure over the objects of a [Scene](super::Scene).
+///
+/// Objects with a bounding box are put into a [Bvh], objects without one are tested against every ray.
+#[derive(Clone, Debug)]
+pub(crate) struct ObjectBvh {
+    /// The [Object::shape_id]s of all objects this was built for, in order
+    shape_ids: Vec<usize>,
+    /// The bounding boxes of the bounded objects, in the order they appear in `bounded`
+    bounds: Vec<BoundingBox>,
+    /// Indices of the objects that are in the bvh
+    bounded: Vec<usize>,
+    /// Indices of the objects that have no bounding box
+    unbounded: Vec<usize>,
+    bvh: Bvh,
+}
+impl ObjectBvh {
+    fn build(shape_ids: Vec<usize>, bounds: Vec<Option<BoundingBox>>) -> Self {
+        let (bounded, unbounded): (Vec<_>, Vec<_>) = (0..bounds.len()).partition(|&i| bounds[i].is_some());
+        let bounds = bounded.iter().filter_map(|&i| bounds[i]).collect::<Vec<_>>();
+        let bvh = Bvh::build(&bounds);
+        Self { shape_ids, bounds, bounded, unbounded, bvh }
+    }
+    /// returns whether the objects are split the same way into bounded and unbounded objects as when this was built
+    fn same_split(&self, bounds: &[Option<BoundingBox>]) -> bool {
+        bounds.len() == self.bounded.len() + self.unbounded.len()
+            && self.bounded.iter().all(|&i| bounds[i].is_some())
+            && self.unbounded.iter().all(|&i| bounds[i].is_none())
+    }
+    /// returns whether nothing changed about the bounds of the objects
+    fn is_up_to_date(&self, bounds: &[Option<BoundingBox>]) -> bool {
+        self.same_split(bounds)
+            && self.bounded.iter().zip(&self.bounds).all(|(&i, old)| bounds[i].as_ref() == Some(old))
+    }
+    /// Tries to update the structure to the new bounds of the same objects.
+    ///
+    /// returns: Option<ObjectBvh>
+    ///     [None] if the objects changed too much for a refit and a rebuild is necessary.
+    fn refit(&self, bounds: &[Option<BoundingBox>]) -> Option<Self> {
+        if !self.same_split(bounds) {
+            return None;
+        }
+        let bounds = self.bounded.iter().filter_map(|&i| bounds[i]).collect::<Vec<_>>();
+        let mut bvh = self.bvh.clone();
+        bvh.refit(&bounds);
+        Some(Self {
+            shape_ids: self.shape_ids.clone(),
+            bounds,
+            bounded: self.bounded.clone(),
+            unbounded: self.unbounded.clone(),
+            bvh,
+        })
+    }
+    /// Finds the closest object hit by the ray.
+    ///
+    /// # Arguments
+    ///
+    /// * `objects`: The objects this structure was built for.
+    /// * `ray`: The ray to trace.
+    ///
+    /// returns: Option<(f64, usize)>
+    ///     The distance to the closest object and its index.
+    pub fn closest_object(&self, objects: &[Object], ray: Ray) -> Option<(f64, usize)> {
+        let distance = |index: usize| {
+            objects[index].distance(ray.position, ray.direction, ray.time)
+                .filter(|dst| dst.is_normal() && dst.is_sign_positive())
+        };
+        let bounded = self.bvh.closest_hit(ray.position, ray.direction, |i| distance(self.bounded[i]))
+            .map(|(dst, i)| (dst, self.bounded[i]));
+        self.unbounded.iter()
+            .filter_map(|&i| distance(i).map(|dst| (dst, i)))
+            .chain(bounded)
+            .min_by(|(dst, _), (dst2, _)| dst.total_cmp(dst2))
+    }
+}
+
+/// Caches the [ObjectBvh] of a scene between renders.
+///
+/// The structure gets refitted if only the bounds of the objects changed and rebuilt if objects were added, removed or replaced.
+#[derive(Debug, Default)]
+pub(crate) struct AccelerationCache {
+    cached: Mutex<Option<Arc<ObjectBvh>>>,
+}
+impl Clone for AccelerationCache {
+    fn clone(&self) -> Self {
+        Self { cached: Mutex::new(self.cached.lock().unwrap().clone()) }
+    }
+}
+impl AccelerationCache {
+    /// returns an acceleration structure that is up-to-date with the given objects
+    pub fn get(&self, objects: &[Object]) -> Arc<ObjectBvh> {
+        let shape_ids = objects.iter().map(Object::shape_id).collect::<Vec<_>>();
+        let bounds = objects.iter().map(Object::bounding_box).collect::<Vec<_>>();
+        let mut cached = self.cached.lock().unwrap();
+        let updated = match cached.as_ref() {
+            Some(current) if current.shape_ids != shape_ids => None,
+            Some(current) if current.is_up_to_date(&bounds) => Some(current.clone()),
+            Some(current) => current.refit(&bounds).map(Arc::new),
+            None => None,
+        }.unwrap_or_else(|| Arc::new(ObjectBvh::build(shape_ids, bounds)));
+        *cached = Some(updated.clone());
+        updated
+    }
+}
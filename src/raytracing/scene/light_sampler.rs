@@ -0,0 +1,159 @@
+use crate::math::Vector3;
+use crate::raytracing::bsdf::luminance;
+use crate::raytracing::object::Object;
+
+/// A point on a light that was picked for next-event estimation
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct LightSample {
+    /// The index of the light's object in the scene
+    pub object: usize,
+    pub position: Vector3,
+    pub normal: Vector3,
+    /// The probability density of picking the point, in area measure (the light selection included)
+    pub pdf_area: f64,
+}
+
+/// Picks points on the emissive objects of a scene, proportional to how much light they emit.
+///
+/// Only objects that can be sampled (see [CustomShape::sample_surface](crate::object::CustomShape::sample_surface)) are included.
+/// All other emissive objects are only found by chance.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct LightSampler {
+    /// The indices of the emissive objects
+    lights: Vec<usize>,
+    /// The summed up power of the lights
+    power_cdf: Vec<f64>,
+    /// The area pdf of each object (selection included), zero for everything that isn't a light
+    pdfs: Vec<f64>,
+}
+impl LightSampler {
+    pub fn new(objects: &[Object]) -> Self {
+        let mut sampler = Self { lights: Vec::new(), power_cdf: Vec::new(), pdfs: vec![0.0; objects.len()] };
+        let mut total = 0.0;
+        let mut areas = Vec::new();
+        for (i, object) in objects.iter().enumerate() {
+            let power = luminance(object.material.emission_color);
+            if power <= 0.0 {
+                continue;
+            }
+            let Some(area) = object.surface_area().filter(|area| area.is_normal()) else {
+                continue;
+            };
+            total += power * area;
+            sampler.lights.push(i);
+            sampler.power_cdf.push(total);
+            areas.push((i, power * area, area));
+        }
+        for (i, power, area) in areas {
+            // picking the light has a probability of power / total, then each point on it 1 / area
+            sampler.pdfs[i] = power / total / area;
+        }
+        sampler
+    }
+    pub fn is_empty(&self) -> bool {
+        self.lights.is_empty()
+    }
+    /// Picks a light and a point on it.
+    ///
+    /// # Arguments
+    ///
+    /// * `objects`: The objects the sampler was created for.
+    /// * `u`: Three uniformly distributed random numbers in `[0, 1)`.
+    /// * `time`: The time of the ray that the light is sampled for, moving lights are sampled where they are at that time.
+    pub fn sample(&self, objects: &[Object], u: (f64, f64, f64), time: f64) -> Option<LightSample> {
+        let total = *self.power_cdf.last()?;
+        let index = self.power_cdf.partition_point(|&power| power <= u.0 * total).min(self.lights.len() - 1);
+        let object = self.lights[index];
+        let surface = objects[object].sample_surface((u.1, u.2), time)?;
+        Some(LightSample { object, position: surface.position, normal: surface.normal, pdf_area: self.pdfs[object] })
+    }
+    /// returns the probability density (in area measure) of [LightSampler::sample] picking a point on the given object
+    pub fn pdf_area(&self, object: usize) -> f64 {
+        self.pdfs.get(object).copied().unwrap_or(0.0)
+    }
+}
+/// Converts a probability density from area measure to solid angle measure.
+///
+/// # Arguments
+///
+/// * `pdf_area`: The density on the surface.
+/// * `distance`: The distance between the point that is looked from and the point on the surface.
+/// * `cos_surface`: The cosine between the surface normal and the direction between the points.
+pub(crate) fn to_solid_angle(pdf_area: f64, distance: f64, cos_surface: f64) -> f64 {
+    if cos_surface.abs() <= 0.0 {
+        return 0.0;
+    }
+    pdf_area * distance * distance / cos_surface.abs()
+}
+/// The power heuristic (with an exponent of 2) for multiple importance sampling
+pub(crate) fn power_heuristic(pdf: f64, other_pdf: f64) -> f64 {
+    let (a, b) = (pdf * pdf, other_pdf * other_pdf);
+    if a + b <= 0.0 {
+        return 0.0;
+    }
+    a / (a + b)
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::object::{sphere::Sphere, triangle::Triangle, CustomShape, Material, SurfaceSample};
+    use std::f64::consts::PI;
+
+    #[test]
+    fn lights_are_picked_by_power() {
+        let objects = [
+            Object::new(Sphere::new(Vector3::zeros(), 1.0), Material::colored(Vector3::ones())),
+            Object::new(Sphere::new(Vector3::new(5, 0, 0), 2.0), Material::light(Vector3::ones())),
+            Object::new(Triangle::new([Vector3::zeros(), Vector3::x(), Vector3::y()]), Material::light(Vector3::ones() * 2)),
+        ];
+        let sampler = LightSampler::new(&objects);
+        let sphere_power = 16.0 * PI;
+        let triangle_power = 2.0 * 0.5;
+        let total = sphere_power + triangle_power;
+        assert_eq!(sampler.pdf_area(0), 0.0);
+        assert!((sampler.pdf_area(1) - sphere_power / total / (16.0 * PI)).abs() < 1e-12);
+        assert!((sampler.pdf_area(2) - triangle_power / total / 0.5).abs() < 1e-12);
+        for _ in 0..100 {
+            let sample = sampler.sample(&objects, (fastrand::f64(), fastrand::f64(), fastrand::f64()), 0.0).unwrap();
+            assert_ne!(sample.object, 0);
+            assert_eq!(sample.pdf_area, sampler.pdf_area(sample.object));
+            assert!(objects[sample.object].distance(sample.position + sample.normal, -sample.normal, 0.0).is_some_and(|dst| (dst - 1.0).abs() < 1e-9));
+        }
+    }
+    /// a unit sphere that moves along x by itself, without an [Object::motion]
+    struct Sliding;
+    impl Sliding {
+        fn at(time: f64) -> Sphere {
+            Sphere::new(Vector3::x() * time, 1.0)
+        }
+    }
+    impl CustomShape for Sliding {
+        fn distance(&self, ray_position: Vector3, ray_direction: Vector3) -> Option<f64> {
+            self.distance_at(ray_position, ray_direction, 0.0)
+        }
+        fn normal(&self, world_position: Vector3) -> Vector3 {
+            self.normal_at(world_position, 0.0)
+        }
+        fn distance_at(&self, ray_position: Vector3, ray_direction: Vector3, time: f64) -> Option<f64> {
+            Self::at(time).distance(ray_position, ray_direction)
+        }
+        fn normal_at(&self, world_position: Vector3, time: f64) -> Vector3 {
+            Self::at(time).normal(world_position)
+        }
+        fn surface_area(&self) -> Option<f64> {
+            Some(4.0 * PI)
+        }
+        fn sample_surface_at(&self, u: (f64, f64), time: f64) -> Option<SurfaceSample> {
+            Self::at(time).sample_surface(u)
+        }
+    }
+    #[test]
+    fn changing_shapes_are_sampled_at_the_time_of_the_ray() {
+        let objects = [Object::new(Sliding, Material::light(Vector3::ones()))];
+        let sampler = LightSampler::new(&objects);
+        for time in [0.0, 2.5] {
+            let sample = sampler.sample(&objects, (0.5, fastrand::f64(), fastrand::f64()), time).unwrap();
+            assert!(((sample.position - Vector3::x() * time).len() - 1.0).abs() < 1e-9);
+        }
+    }
+}
@@ -0,0 +1,113 @@
+use super::{Accumulation, Scene};
+use crate::raytracing::camera::Camera;
+use crate::raytracing::film::Film;
+
+/// Renders a scene a few rays per pixel at a time, for previews that get better the longer they run.
+///
+/// Every [ProgressiveRenderer::step] adds rays to a running sum per pixel and [ProgressiveRenderer::image]
+/// averages them, so [Config::rays_per_pixel](crate::Config::rays_per_pixel) doesn't have to be chosen upfront.
+/// Changing the scene throws away the rays of the old one.
+///
+/// # Examples
+///
+/// ```
+/// use rtx::{ProgressiveRenderer, Scene};
+///
+/// let mut renderer = ProgressiveRenderer::new(Scene::default(), 16, 8);
+/// renderer.step(1);
+/// let preview = renderer.image();
+/// renderer.step(3);
+/// assert_eq!(renderer.samples(), 4);
+/// // moving the camera starts over
+/// renderer.scene_mut().camera.position = (0, 1, 0).into();
+/// assert_eq!(renderer.samples(), 0);
+/// ```
+pub struct ProgressiveRenderer {
+    scene: Scene,
+    accumulation: Accumulation,
+    /// the rays per pixel in the accumulation
+    samples: usize,
+}
+impl ProgressiveRenderer {
+    pub fn new(scene: Scene, width: usize, height: usize) -> Self {
+        let accumulation = Accumulation::new(width, height, scene.config.tile_size);
+        Self { scene, accumulation, samples: 0 }
+    }
+    pub fn scene(&self) -> &Scene {
+        &self.scene
+    }
+    /// Gives access to the scene to change it, like its camera or objects.
+    /// This starts the accumulation over, even if nothing is changed.
+    pub fn scene_mut(&mut self) -> &mut Scene {
+        self.reset();
+        &mut self.scene
+    }
+    /// moves the camera and starts the accumulation over
+    pub fn set_camera(&mut self, camera: Camera) {
+        self.scene_mut().camera = camera;
+    }
+    /// changes the size of the image and starts the accumulation over
+    pub fn resize(&mut self, width: usize, height: usize) {
+        self.accumulation = Accumulation::new(width, height, self.scene.config.tile_size);
+        self.samples = 0;
+    }
+    /// throws away all rays rendered so far
+    pub fn reset(&mut self) {
+        self.resize(self.accumulation.width, self.accumulation.height);
+    }
+    pub fn width(&self) -> usize {
+        self.accumulation.width
+    }
+    pub fn height(&self) -> usize {
+        self.accumulation.height
+    }
+    /// The camera rays per pixel since the last reset.
+    /// With [adaptive sampling](crate::Config::adaptive_sampling), pixels that converged stop at fewer rays.
+    pub fn samples(&self) -> usize {
+        self.samples
+    }
+    /// Adds the given number of camera rays to every pixel, rendered on the threads of the [Config](crate::Config).
+    pub fn step(&mut self, samples: usize) {
+        let aspect_ratio = self.accumulation.width as f64 / self.accumulation.height as f64;
+        let camera = &self.scene.camera;
+        self.scene.render_pass(&mut self.accumulation, |uv, lens_sample, time| camera.traced_ray(uv, aspect_ratio, lens_sample, time), samples, self.samples + samples, || false, |_, _, _| {});
+        self.samples += samples;
+    }
+    /// The current estimate of the image, see [Scene::render].
+    /// Before the first step, all pixels are transparent black.
+    pub fn image(&self) -> Film {
+        self.accumulation.film(&self.scene.config)
+    }
+    /// gives the scene back
+    pub fn into_scene(self) -> Scene {
+        self.scene
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::environment::Environment;
+    use crate::math::Vector3;
+
+    #[test]
+    fn steps_add_up_and_changes_reset() {
+        let scene = Scene {
+            environment: Environment::Color(Vector3::new(0.25, 0.5, 1.0)),
+            ..Scene::default()
+        };
+        let mut renderer = ProgressiveRenderer::new(scene, 8, 4);
+        assert_eq!(renderer.image().pixels()[0], [0.0; 4]);
+        renderer.step(2);
+        renderer.step(3);
+        assert_eq!(renderer.samples(), 5);
+        // every ray sees the same color, so the average is exact
+        assert!(renderer.image().pixels().iter().all(|pixel| *pixel == [0.25, 0.5, 1.0, 1.0]));
+        renderer.set_camera(Camera::new((0, 0, 1).into(), (0, 1, 0).into(), 90f64.to_radians()));
+        assert_eq!(renderer.samples(), 0);
+        assert_eq!(renderer.image().pixels()[0], [0.0; 4]);
+        renderer.resize(3, 3);
+        renderer.step(1);
+        assert_eq!((renderer.image().width(), renderer.samples()), (3, 1));
+    }
+}
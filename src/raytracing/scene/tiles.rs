@@ -0,0 +1,117 @@
+use std::collections::VecDeque;
+use std::sync::Mutex;
+use std::thread;
+
+/// A rectangle of pixels that is rendered as one piece of work
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) struct Tile {
+    pub x: usize,
+    pub y: usize,
+    pub width: usize,
+    pub height: usize,
+}
+impl Tile {
+    /// the pixels of the tile, row by row
+    pub fn pixels(&self) -> impl Iterator<Item = (usize, usize)> + '_ {
+        (self.y..self.y + self.height).flat_map(move |y| (self.x..self.x + self.width).map(move |x| (x, y)))
+    }
+}
+/// Splits an image into square tiles (smaller at the right and bottom edges), row by row.
+pub(crate) fn split(width: usize, height: usize, tile_size: usize) -> Vec<Tile> {
+    let tile_size = tile_size.max(1);
+    (0..height).step_by(tile_size)
+        .flat_map(|y| (0..width).step_by(tile_size).map(move |x| Tile {
+            x,
+            y,
+            width: tile_size.min(width - x),
+            height: tile_size.min(height - y),
+        }))
+        .collect()
+}
+/// the number of threads to use, 0 meaning one per core
+pub(crate) fn thread_count(threads: usize) -> usize {
+    match threads {
+        0 => thread::available_parallelism().map_or(1, |threads| threads.get()),
+        threads => threads,
+    }
+}
+/// Runs `work` on every job with a fixed number of threads that steal work from each other.
+///
+/// The jobs are dealt out to the threads in turns. Every thread works through its own queue from the front
+/// and once it's empty, takes jobs from the back of the fullest other queue, so no thread idles while work is left.
+///
+/// returns: Vec<R>
+///     The results in the order of the jobs
+pub(crate) fn run<J: Send, R: Send>(jobs: Vec<J>, threads: usize, work: impl Fn(J) -> R + Sync) -> Vec<R> {
+    let job_count = jobs.len();
+    let threads = threads.clamp(1, job_count.max(1));
+    let mut queues = (0..threads).map(|_| VecDeque::new()).collect::<Vec<_>>();
+    for (i, job) in jobs.into_iter().enumerate() {
+        queues[i % threads].push_back((i, job));
+    }
+    let queues = queues.into_iter().map(Mutex::new).collect::<Vec<_>>();
+    let (queues, work) = (&queues, &work);
+    let mut results = thread::scope(|scope| {
+        (0..threads)
+            .map(|own| scope.spawn(move || {
+                let mut results = Vec::new();
+                while let Some((i, job)) = next_job(queues, own) {
+                    results.push((i, work(job)));
+                }
+                results
+            }))
+            .collect::<Vec<_>>().into_iter()
+            .flat_map(|thread| thread.join().unwrap())
+            .collect::<Vec<_>>()
+    });
+    results.sort_unstable_by_key(|(i, _)| *i);
+    results.into_iter().map(|(_, result)| result).collect()
+}
+/// takes the next job of the own queue, or steals one from the fullest other queue
+fn next_job<J>(queues: &[Mutex<VecDeque<J>>], own: usize) -> Option<J> {
+    if let Some(job) = queues[own].lock().unwrap().pop_front() {
+        return Some(job);
+    }
+    loop {
+        let (victim, len) = (0..queues.len())
+            .filter(|&i| i != own)
+            .map(|i| (i, queues[i].lock().unwrap().len()))
+            .max_by_key(|&(_, len)| len)?;
+        // the queues only shrink, so once every one was seen empty, all work is taken
+        if len == 0 {
+            return None;
+        }
+        // another thread may have emptied the queue in the meantime, then look again
+        if let Some(job) = queues[victim].lock().unwrap().pop_back() {
+            return Some(job);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tiles_cover_the_image_once() {
+        let tiles = split(70, 33, 32);
+        assert_eq!(tiles.len(), 3 * 2);
+        assert_eq!(tiles[2], Tile { x: 64, y: 0, width: 6, height: 32 });
+        let mut covered = vec![0; 70 * 33];
+        for (x, y) in tiles.iter().flat_map(Tile::pixels) {
+            covered[y * 70 + x] += 1;
+        }
+        assert!(covered.iter().all(|&count| count == 1));
+    }
+    #[test]
+    fn results_keep_the_order_of_the_jobs() {
+        // uneven jobs, so the threads have to steal
+        let results = run((0..200u64).collect(), 4, |job| {
+            if job % 50 == 0 {
+                thread::sleep(std::time::Duration::from_millis(5));
+            }
+            job * 2
+        });
+        assert_eq!(results, (0..200).map(|job| job * 2).collect::<Vec<_>>());
+    }
+}
@@ -0,0 +1,160 @@
+//! Turns the unbounded linear colors of a render into displayable colors in `[0, 1]`.
+//!
+//! A [ToneMapping] first scales the colors by the exposure and then compresses them with a [ToneMapOperator].
+//! The result is still linear, the sRGB transfer function is applied when the colors are encoded, see [Film::to_rgb8].
+use crate::math::{Mat3x3, Vector3};
+use crate::Film;
+
+/// How colors above 1 are brought into the displayable range
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum ToneMapOperator {
+    /// Cuts off every channel at 1
+    #[default]
+    Clamp,
+    /// `c / (1 + c)` per channel, which never reaches white
+    Reinhard,
+    /// Reinhard, but colors with a brightness of `white` become white
+    ExtendedReinhard { white: f64 },
+    /// Stephen Hill's fit of the ACES reference rendering and output transforms
+    Aces,
+    /// A polynomial fit of the AgX base look, which desaturates very bright colors instead of skewing their hue
+    Agx,
+}
+/// Exposure and tone mapping, applied to linear colors.
+///
+/// # Examples
+///
+/// ```
+/// use rtx::math::Vector3;
+/// use rtx::tone_mapping::{ToneMapOperator, ToneMapping};
+///
+/// let tone_mapping = ToneMapping::new(-1.0, ToneMapOperator::Reinhard);
+/// assert_eq!(tone_mapping.apply(Vector3::ones() * 2.0), Vector3::ones() * 0.5);
+/// ```
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct ToneMapping {
+    /// The exposure in stops (EV), colors are multiplied by `2^exposure`
+    pub exposure: f64,
+    pub operator: ToneMapOperator,
+}
+
+impl ToneMapping {
+    pub const fn new(exposure: f64, operator: ToneMapOperator) -> Self {
+        Self { exposure, operator }
+    }
+    pub fn with_exposure(&self, exposure: f64) -> Self {
+        Self { exposure, ..*self }
+    }
+    pub fn with_operator(&self, operator: ToneMapOperator) -> Self {
+        Self { operator, ..*self }
+    }
+    /// Maps a linear color to a linear color in `[0, 1]`.
+    ///
+    /// # Arguments
+    ///
+    /// * `color`: The linear color, like it was rendered
+    ///
+    /// returns: Vector3
+    ///     The exposed and tone mapped color, still without the sRGB transfer function
+    pub fn apply(&self, color: Vector3) -> Vector3 {
+        let color = (color * 2f64.powf(self.exposure)).max(Vector3::zeros());
+        let mapped = match self.operator {
+            ToneMapOperator::Clamp => color,
+            ToneMapOperator::Reinhard => per_channel(color, |c| c / (1.0 + c)),
+            ToneMapOperator::ExtendedReinhard { white } => {
+                per_channel(color, |c| c * (1.0 + c / (white * white)) / (1.0 + c))
+            }
+            ToneMapOperator::Aces => aces(color),
+            ToneMapOperator::Agx => agx(color),
+        };
+        mapped.min(Vector3::ones()).max(Vector3::zeros())
+    }
+    /// Applies the tone mapping to every pixel of a film, keeping the alpha
+    pub fn apply_to_film(&self, film: &Film) -> Film {
+        let mut film = film.clone();
+        for pixel in film.pixels_mut() {
+            let [r, g, b, a] = *pixel;
+            let color = self.apply(Vector3::new(r, g, b));
+            *pixel = [color.x as f32, color.y as f32, color.z as f32, a];
+        }
+        film
+    }
+}
+fn per_channel(color: Vector3, f: impl Fn(f64) -> f64) -> Vector3 {
+    Vector3::new(f(color.x), f(color.y), f(color.z))
+}
+fn aces(color: Vector3) -> Vector3 {
+    // sRGB to the ACES rendering space, with the RRT saturation baked in
+    let input = Mat3x3::new(
+        Vector3::new(0.59719, 0.35458, 0.04823),
+        Vector3::new(0.07600, 0.90834, 0.01566),
+        Vector3::new(0.02840, 0.13383, 0.83777),
+    );
+    // back to linear sRGB, with the ODT saturation baked in
+    let output = Mat3x3::new(
+        Vector3::new(1.60475, -0.53108, -0.07367),
+        Vector3::new(-0.10208, 1.10813, -0.00605),
+        Vector3::new(-0.00327, -0.07276, 1.07602),
+    );
+    let color = per_channel(input * color, |v| {
+        (v * (v + 0.0245786) - 0.000090537) / (v * (0.983729 * v + 0.4329510) + 0.238081)
+    });
+    output * color
+}
+/// The range of the log2 encoding that AgX works in, relative to middle gray
+const AGX_MIN_EV: f64 = -12.47393;
+const AGX_MAX_EV: f64 = 4.026069;
+fn agx(color: Vector3) -> Vector3 {
+    let inset = Mat3x3::new(
+        Vector3::new(0.842479062253094, 0.0784335999999992, 0.0792237451477643),
+        Vector3::new(0.0423282422610123, 0.878468636469772, 0.0791661274605434),
+        Vector3::new(0.0423756549057051, 0.0784336, 0.879142973793104),
+    );
+    let outset = Mat3x3::new(
+        Vector3::new(1.19687900512017, -0.0980208811401368, -0.0990297440797205),
+        Vector3::new(-0.0528968517574562, 1.15190312990417, -0.0989611768448433),
+        Vector3::new(-0.0529716355144438, -0.0980434501171241, 1.15107367264116),
+    );
+    let encoded = per_channel(inset * color, |c| {
+        let x = (c.max(1e-10).log2().clamp(AGX_MIN_EV, AGX_MAX_EV) - AGX_MIN_EV) / (AGX_MAX_EV - AGX_MIN_EV);
+        // the sigmoid of the base look, which outputs values with a 2.2 gamma
+        let (x2, x4) = (x * x, x * x * x * x);
+        15.5 * x4 * x2 - 40.14 * x4 * x + 31.96 * x4 - 6.868 * x2 * x + 0.4298 * x2 + 0.1191 * x - 0.00232
+    });
+    per_channel(outset * encoded, |c| c.max(0.0).powf(2.2))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const OPERATORS: [ToneMapOperator; 5] = [
+        ToneMapOperator::Clamp,
+        ToneMapOperator::Reinhard,
+        ToneMapOperator::ExtendedReinhard { white: 4.0 },
+        ToneMapOperator::Aces,
+        ToneMapOperator::Agx,
+    ];
+
+    #[test]
+    fn operators_are_monotonic_and_bounded() {
+        for operator in OPERATORS {
+            let tone_mapping = ToneMapping::new(0.0, operator);
+            let mut previous = -1.0;
+            for i in 0..200 {
+                let gray = tone_mapping.apply(Vector3::ones() * (i as f64 / 10.0).powi(2)).y;
+                assert!((0.0..=1.0).contains(&gray), "{operator:?} maps to {gray}");
+                assert!(gray >= previous - 1e-9, "{operator:?} isn't monotonic");
+                previous = gray;
+            }
+            assert!(tone_mapping.apply(Vector3::zeros()).len() < 1e-2, "{operator:?} doesn't keep black");
+        }
+    }
+    #[test]
+    fn white_point_and_exposure() {
+        let extended = ToneMapping::new(0.0, ToneMapOperator::ExtendedReinhard { white: 4.0 });
+        assert!((extended.apply(Vector3::ones() * 4.0) - Vector3::ones()).len() < 1e-12);
+        let exposed = ToneMapping::new(2.0, ToneMapOperator::Clamp);
+        assert_eq!(exposed.apply(Vector3::new(0.125, 0.2, -1.0)), Vector3::new(0.5, 0.8, 0.0));
+    }
+}